@@ -0,0 +1,122 @@
+use crate::ray;
+use crate::transform;
+use crate::WorldVec;
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+    pub min: WorldVec,
+    pub max: WorldVec,
+}
+
+impl Aabb {
+    pub fn new(min: WorldVec, max: WorldVec) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// The smallest box containing all of the given points.
+    pub fn from_points(points: &[WorldVec]) -> Aabb {
+        let mut result = Aabb::new(
+            WorldVec::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+            WorldVec::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for point in points {
+            result = result.union(&Aabb::new(*point, *point));
+        }
+        result
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb::new(
+            WorldVec::new(
+                self.min.0.min(other.min.0),
+                self.min.1.min(other.min.1),
+                self.min.2.min(other.min.2),
+            ),
+            WorldVec::new(
+                self.max.0.max(other.max.0),
+                self.max.1.max(other.max.1),
+                self.max.2.max(other.max.2),
+            ),
+        )
+    }
+
+    pub fn centroid(&self) -> WorldVec {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn corners(&self) -> [WorldVec; 8] {
+        let (min, max) = (self.min, self.max);
+        let corner = |i: usize| {
+            WorldVec::new(
+                if i & 1 == 0 { min.0 } else { max.0 },
+                if i & 2 == 0 { min.1 } else { max.1 },
+                if i & 4 == 0 { min.2 } else { max.2 },
+            )
+        };
+        [corner(0), corner(1), corner(2), corner(3), corner(4), corner(5), corner(6), corner(7)]
+    }
+
+    /// The box containing this box once it has been transformed.
+    pub fn transformed(&self, transform: &transform::Transform) -> Aabb {
+        let corners = self.corners().map(|corner| transform.transform_point(corner));
+        Aabb::from_points(&corners)
+    }
+
+    /// If the ray passes through the box somewhere between `t = 0` and `t_max`, return the `t` at
+    /// which it enters the box (or 0, if it starts inside the box).
+    pub fn hit(&self, ray: &ray::Ray, t_max: f64) -> Option<f64> {
+        self.hit_interval(ray, t_max).map(|(t_enter, _)| t_enter)
+    }
+
+    /// Like `hit`, but also returns the `t` at which the ray leaves the box (or `t_max`, if that
+    /// comes first).
+    pub fn hit_interval(&self, ray: &ray::Ray, t_max: f64) -> Option<(f64, f64)> {
+        let origin = ray.get_origin();
+        let direction = ray.get_direction();
+        let mut t_enter = 0.0_f64;
+        let mut t_exit = t_max;
+        for &(origin, direction, min, max) in &[
+            (origin.0, direction.0, self.min.0, self.max.0),
+            (origin.1, direction.1, self.min.1, self.max.1),
+            (origin.2, direction.2, self.min.2, self.max.2),
+        ] {
+            // Dividing by zero gives us infinities, which are what we want here.
+            let inverse = 1.0 / direction;
+            let (mut t0, mut t1) = ((min - origin) * inverse, (max - origin) * inverse);
+            if inverse < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            // Written this way around so that NaNs (0 * infinity) don't shrink the interval.
+            if t0 > t_enter {
+                t_enter = t0;
+            }
+            if t1 < t_exit {
+                t_exit = t1;
+            }
+            if t_exit < t_enter {
+                return None;
+            }
+        }
+        Some((t_enter, t_exit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_aabb() {
+        let aabb = Aabb::new(WorldVec::new(1.0, -1.0, -1.0), WorldVec::new(2.0, 1.0, 1.0));
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert_eq!(aabb.hit(&ray, f64::INFINITY), Some(1.0));
+        assert_eq!(aabb.hit(&ray, 0.5), None);
+
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(-1.0, 0.0, 0.0));
+        assert_eq!(aabb.hit(&ray, f64::INFINITY), None);
+
+        let ray = ray::Ray::new(WorldVec::new(0.0, 2.0, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert_eq!(aabb.hit(&ray, f64::INFINITY), None);
+    }
+}
@@ -0,0 +1,198 @@
+//! Rendering a sequence of frames. The camera follows a `CameraMotion`, while objects animate
+//! themselves from the time of each ray -- see `motion::AnimatedInstance` and
+//! `motion::MovingSphere`. Frame `n` is rendered at time `n / frames_per_second`.
+
+use crate::camera;
+use crate::hit::Hit;
+use crate::motion;
+use crate::transform;
+use crate::WorldVec;
+
+/// Where a camera is, and what it's looking at, at some moment.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct CameraPose {
+    pub origin: WorldVec,
+    pub looking_at: WorldVec,
+    pub up: WorldVec,
+    /// The horizontal field of view, in degrees.
+    pub fov: f64,
+}
+
+/// How a camera moves over time.
+pub trait CameraMotion: std::fmt::Debug {
+    fn pose_at(&self, time: f64) -> CameraPose;
+}
+
+/// A camera whose origin, target and field of view each follow their own track.
+#[derive(Debug, Clone)]
+pub struct KeyframedCamera {
+    pub origin: motion::Track<WorldVec>,
+    pub looking_at: motion::Track<WorldVec>,
+    pub fov: motion::Track<f64>,
+    pub up: WorldVec,
+}
+
+impl CameraMotion for KeyframedCamera {
+    fn pose_at(&self, time: f64) -> CameraPose {
+        CameraPose {
+            origin: self.origin.at(time),
+            looking_at: self.looking_at.at(time),
+            up: self.up,
+            fov: self.fov.at(time),
+        }
+    }
+}
+
+/// A camera circling `target` at a constant speed, always looking at it. This is the usual way of
+/// showing off a model from every side.
+#[derive(Debug, Clone)]
+pub struct Turntable {
+    pub target: WorldVec,
+    /// Where the camera starts, at time 0.
+    pub start: WorldVec,
+    /// The axis the camera circles around, anticlockwise when looking down it.
+    pub up: WorldVec,
+    pub fov: f64,
+    /// How long it takes to go all the way around.
+    pub period: f64,
+}
+
+impl Turntable {
+    /// A turntable which goes all the way around in `frames` frames, so that the sequence loops.
+    pub fn looping(
+        target: WorldVec,
+        start: WorldVec,
+        up: WorldVec,
+        fov: f64,
+        frames: usize,
+        frames_per_second: f64,
+    ) -> Turntable {
+        Turntable {
+            target,
+            start,
+            up,
+            fov,
+            period: frames as f64 / frames_per_second,
+        }
+    }
+}
+
+impl CameraMotion for Turntable {
+    fn pose_at(&self, time: f64) -> CameraPose {
+        let angle = 2.0 * std::f64::consts::PI * time / self.period;
+        let orbit = transform::Transform::rotation(self.up, angle);
+        CameraPose {
+            origin: self.target + orbit.transform_vector(self.start - self.target),
+            looking_at: self.target,
+            up: self.up,
+            fov: self.fov,
+        }
+    }
+}
+
+/// Which frames to render, and how.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    pub frames: std::ops::RangeInclusive<usize>,
+    pub frames_per_second: f64,
+    /// How much of each frame the shutter stays open for, from 0 (no motion blur) to 1.
+    pub shutter_fraction: f64,
+    pub x_size: usize,
+    pub y_size: usize,
+    pub bounces: usize,
+    pub samples_per_pixel: usize,
+}
+
+/// Where `render_sequence` writes frame `frame`: `frame_0001.png` and so on.
+pub fn frame_path(directory: &std::path::Path, frame: usize) -> std::path::PathBuf {
+    directory.join(format!("frame_{:04}.png", frame))
+}
+
+/// Render each frame of `sequence` with `camera` following `motion`, writing them into
+/// `directory`. Returns the paths written, in order.
+pub fn render_sequence<T: Hit>(
+    camera: &mut camera::Camera<T>,
+    motion: &dyn CameraMotion,
+    sequence: &Sequence,
+    directory: &std::path::Path,
+) -> std::io::Result<Vec<std::path::PathBuf>> {
+    let mut paths = Vec::new();
+    for frame in sequence.frames.clone() {
+        let time = frame as f64 / sequence.frames_per_second;
+        let pose = motion.pose_at(time);
+        camera.set_looking_at(pose.origin, pose.up, pose.looking_at);
+        camera.set_shutter(time, time + sequence.shutter_fraction / sequence.frames_per_second);
+        let image = camera.render(
+            sequence.x_size,
+            sequence.y_size,
+            pose.fov,
+            sequence.bounces,
+            sequence.samples_per_pixel,
+        );
+        let path = frame_path(directory, frame);
+        image.write_png(std::io::BufWriter::new(std::fs::File::create(&path)?))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turntable() {
+        let turntable = Turntable::looping(
+            WorldVec::new(0.0, 1.0, 0.0),
+            WorldVec::new(0.0, 1.0, -5.0),
+            WorldVec::new(0.0, 1.0, 0.0),
+            60.0,
+            4,
+            2.0,
+        );
+        let close = |a: WorldVec, b: WorldVec| (a - b).length() < 1e-9;
+        assert!(close(turntable.pose_at(0.0).origin, WorldVec::new(0.0, 1.0, -5.0)));
+        // A quarter of the way around after one frame, anticlockwise looking down from above.
+        assert!(close(turntable.pose_at(0.5).origin, WorldVec::new(-5.0, 1.0, 0.0)));
+        assert!(close(turntable.pose_at(2.0).origin, WorldVec::new(0.0, 1.0, -5.0)));
+    }
+
+    #[test]
+    fn test_render_sequence() {
+        let mut camera = crate::scene_description::parse(
+            "camera origin 0 0 -3 up 0 1 0 looking_at 0 0 0\n\
+             material grey lambertian 0.5 0.5 0.5\n\
+             sphere 0 0 0 1 grey",
+        )
+        .unwrap();
+        let motion = KeyframedCamera {
+            origin: motion::Track::new(vec![
+                (0.0, WorldVec::new(0.0, 0.0, -3.0)),
+                (1.0, WorldVec::new(0.0, 0.0, -6.0)),
+            ]),
+            looking_at: motion::Track::constant(WorldVec::new(0.0, 0.0, 0.0)),
+            fov: motion::Track::constant(60.0),
+            up: WorldVec::new(0.0, 1.0, 0.0),
+        };
+        let sequence = Sequence {
+            frames: 1..=2,
+            frames_per_second: 2.0,
+            shutter_fraction: 0.5,
+            x_size: 4,
+            y_size: 3,
+            bounces: 2,
+            samples_per_pixel: 1,
+        };
+        let directory = std::env::temp_dir().join(format!("path_tracer_sequence_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let paths = render_sequence(&mut camera, &motion, &sequence, &directory).unwrap();
+        assert_eq!(paths, vec![directory.join("frame_0001.png"), directory.join("frame_0002.png")]);
+        for path in &paths {
+            let frame = crate::raster::Raster::load(path).unwrap();
+            assert_eq!((frame.x_len(), frame.y_len()), (4, 3));
+        }
+        // The last frame was rendered from where the camera is at time 1.
+        assert_eq!(camera.origin(), WorldVec::new(0.0, 0.0, -6.0));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use crate::aabb;
+use crate::hit;
+use crate::ray;
+use crate::WorldVec;
+
+/// The most primitives we put in a single leaf.
+const MAX_LEAF_SIZE: usize = 4;
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    Interior { left: usize, right: usize },
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    bounds: aabb::Aabb,
+    kind: NodeKind,
+}
+
+/// A bounding volume hierarchy over some list of primitives, identified by their index. The tree
+/// doesn't own the primitives, so that it can be shared by anything which needs to search a list
+/// of boxes quickly -- a `Bvh` of objects, the triangles of a `Mesh`, or the instances in a
+/// `Scene`.
+#[derive(Debug, Clone)]
+pub struct BvhTree {
+    nodes: Vec<Node>,
+    /// Primitive indices, ordered so that each leaf refers to a contiguous run of them.
+    indices: Vec<usize>,
+}
+
+impl BvhTree {
+    /// Build a tree over primitives with the given bounds.
+    pub fn build(bounds: &[aabb::Aabb]) -> BvhTree {
+        let mut tree = BvhTree {
+            nodes: Vec::new(),
+            indices: (0..bounds.len()).collect(),
+        };
+        if !bounds.is_empty() {
+            tree.build_node(bounds, 0, bounds.len());
+        }
+        tree
+    }
+
+    /// Build the node covering `indices[first..first + count]`, returning its index. Children are
+    /// always stored after their parents.
+    fn build_node(&mut self, bounds: &[aabb::Aabb], first: usize, count: usize) -> usize {
+        let indices = &mut self.indices[first..first + count];
+        let node_bounds = indices
+            .iter()
+            .skip(1)
+            .fold(bounds[indices[0]], |acc, &i| acc.union(&bounds[i]));
+        let node_index = self.nodes.len();
+        self.nodes.push(Node {
+            bounds: node_bounds,
+            kind: NodeKind::Leaf { first, count },
+        });
+        if count <= MAX_LEAF_SIZE {
+            return node_index;
+        }
+
+        // Split at the median centroid along the axis the centroids are most spread out along.
+        let centroids =
+            aabb::Aabb::from_points(&indices.iter().map(|&i| bounds[i].centroid()).collect::<Vec<_>>());
+        let extent = centroids.max - centroids.min;
+        let axis = if extent.0 >= extent.1 && extent.0 >= extent.2 {
+            0
+        } else if extent.1 >= extent.2 {
+            1
+        } else {
+            2
+        };
+        let component = |v: WorldVec| match axis {
+            0 => v.0,
+            1 => v.1,
+            _ => v.2,
+        };
+        indices.sort_by(|&a, &b| {
+            component(bounds[a].centroid())
+                .partial_cmp(&component(bounds[b].centroid()))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let half = count / 2;
+        let left = self.build_node(bounds, first, half);
+        let right = self.build_node(bounds, first + half, count - half);
+        self.nodes[node_index].kind = NodeKind::Interior { left, right };
+        node_index
+    }
+
+    /// Update the bounds of every node for primitives which have moved, without changing the shape
+    /// of the tree. This is much cheaper than rebuilding, but the tree gets less efficient the
+    /// further the primitives move from where they were when it was built.
+    pub fn refit(&mut self, bounds: &[aabb::Aabb]) {
+        // Children come after their parents, so walking backwards visits children first.
+        for node_index in (0..self.nodes.len()).rev() {
+            self.nodes[node_index].bounds = match self.nodes[node_index].kind {
+                NodeKind::Leaf { first, count } => self.indices[first + 1..first + count]
+                    .iter()
+                    .fold(bounds[self.indices[first]], |acc, &i| acc.union(&bounds[i])),
+                NodeKind::Interior { left, right } => {
+                    self.nodes[left].bounds.union(&self.nodes[right].bounds)
+                }
+            };
+        }
+    }
+
+    /// Find the closest hit along `ray`. `intersect` is called with the index of each primitive
+    /// whose box the ray passes through, and the furthest `t` worth looking at (the closest hit so
+    /// far, or the end of the ray's range). It should return the `t` of the hit (if any) along
+    /// with the hit itself.
+    pub fn closest_hit<H>(
+        &self,
+        ray: &ray::Ray,
+        mut intersect: impl FnMut(usize, f64) -> Option<(f64, H)>,
+    ) -> Option<(f64, H)> {
+        let mut closest: Option<(f64, H)> = None;
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let mut stack = vec![0];
+        while let Some(node_index) = stack.pop() {
+            let node = &self.nodes[node_index];
+            let t_max = closest.as_ref().map_or(ray.get_t_max(), |(t, _)| *t);
+            if node.bounds.hit(ray, t_max).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for &index in &self.indices[first..first + count] {
+                        let t_max = closest.as_ref().map_or(ray.get_t_max(), |(t, _)| *t);
+                        if let Some((t, hit)) = intersect(index, t_max) {
+                            if closest.as_ref().is_none_or(|(closest_t, _)| t < *closest_t) {
+                                closest = Some((t, hit));
+                            }
+                        }
+                    }
+                }
+                NodeKind::Interior { left, right } => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+            }
+        }
+        closest
+    }
+
+    /// The bounds of everything in the tree.
+    pub fn bounds(&self) -> Option<aabb::Aabb> {
+        self.nodes.first().map(|node| node.bounds)
+    }
+}
+
+/// A BVH over some set of objects. This is useful as the shared, bottom level of an instanced
+/// object made of many other objects.
+#[derive(Debug)]
+pub struct Bvh {
+    objects: Vec<std::rc::Rc<dyn hit::Hit>>,
+    tree: BvhTree,
+    /// Objects without bounds (infinite planes, for instance) can't go in the tree, so they're
+    /// checked separately.
+    unbounded: Vec<std::rc::Rc<dyn hit::Hit>>,
+}
+
+impl Bvh {
+    pub fn new(objects: Vec<std::rc::Rc<dyn hit::Hit>>) -> Bvh {
+        let (bounded, unbounded): (Vec<_>, Vec<_>) = objects
+            .into_iter()
+            .partition(|object| object.bounding_box().is_some());
+        let bounds: Vec<_> = bounded
+            .iter()
+            .map(|object| object.bounding_box().unwrap())
+            .collect();
+        Bvh {
+            tree: BvhTree::build(&bounds),
+            objects: bounded,
+            unbounded,
+        }
+    }
+}
+
+impl hit::Hit for Bvh {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let mut closest = self.unbounded.iter().fold(None, |closest: Option<hit::MaterialHit>, object| {
+            let t_max = closest.as_ref().map_or(from.get_t_max(), |hit| hit.t);
+            object.hit(&from.with_t_range(from.get_t_min(), t_max)).or(closest)
+        });
+        let t_max = closest.as_ref().map_or(from.get_t_max(), |hit| hit.t);
+        let in_tree = self.tree.closest_hit(&from.with_t_range(from.get_t_min(), t_max), |index, t_max| {
+            let hit = self.objects[index].hit(&from.with_t_range(from.get_t_min(), t_max))?;
+            Some((hit.t, hit))
+        });
+        if let Some((_, hit)) = in_tree {
+            closest = Some(hit);
+        }
+        closest
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        if self.unbounded.is_empty() {
+            self.tree.bounds()
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+    use crate::scene;
+    use crate::sphere;
+
+    #[test]
+    fn test_bvh_matches_linear_search() {
+        let material: std::rc::Rc<dyn crate::material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        let mut objects: Vec<std::rc::Rc<dyn hit::Hit>> = Vec::new();
+        let mut linear = scene::Scene::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let sphere = std::rc::Rc::new(sphere::Sphere::new(
+                    WorldVec::new(i as f64 * 2.0, j as f64 * 2.0, (i * j % 7) as f64),
+                    0.8,
+                    material.clone(),
+                ));
+                objects.push(sphere.clone());
+                linear.add_object(sphere);
+            }
+        }
+        let bvh = Bvh::new(objects);
+        assert!(bvh.bounding_box().is_some());
+
+        for i in 0..20 {
+            for j in 0..20 {
+                let ray = ray::Ray::new(
+                    WorldVec::new(i as f64, j as f64, -10.0),
+                    WorldVec::new(0.01 * i as f64, -0.02, 1.0).normalised(),
+                );
+                let expected = linear.hit(&ray).map(|hit| *hit.intersected_surface_normal.get_origin());
+                let actual = bvh.hit(&ray).map(|hit| *hit.intersected_surface_normal.get_origin());
+                assert_eq!(expected, actual);
+            }
+        }
+    }
+}
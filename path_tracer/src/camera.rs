@@ -1,12 +1,35 @@
+use crate::checkpoint;
 use crate::colour;
+use crate::film;
+use crate::hit;
 use crate::hit::Hit;
 use crate::image;
+use crate::material;
+use crate::progressive;
+use crate::quad;
 use crate::ray;
+use crate::sampler;
+use crate::subsurface;
 use crate::vec3;
 use crate::WorldVec;
 
 use rand::distributions::{Distribution, Uniform};
-use rand::SeedableRng;
+
+/// Something which happens to light along a path.
+// Nearly every interaction is with a surface, so boxing the hit would only cost an allocation.
+#[allow(clippy::large_enum_variant)]
+enum Interaction {
+    /// Bouncing off (or through) a surface. Along with the hit, this has the direction the light
+    /// arrives from and the direction it leaves in.
+    Surface(hit::MaterialHit, WorldVec, WorldVec),
+    /// Wandering around inside a translucent object, which tints the light by this much.
+    Subsurface([f64; 3]),
+    /// Reaching a light source, which gives off this much light. Nothing beyond it counts.
+    Emission(colour::Colour),
+    /// Light picked out directly from a light source, which the surface reached just before
+    /// reflects this much of. It adds to whatever the surface reflects from further along.
+    Direct(colour::Colour),
+}
 
 #[derive(Debug)]
 pub struct Camera<T: Hit> {
@@ -15,6 +38,23 @@ pub struct Camera<T: Hit> {
     up: WorldVec,
     right: WorldVec,
     forward: WorldVec,
+    seed: u64,
+    /// When the shutter opens and closes. Each path is traced at a random time in between.
+    shutter: (f64, f64),
+    /// Lights which surfaces are lit from directly, as well as by the paths which happen to reach
+    /// them.
+    lights: Vec<std::rc::Rc<quad::Quad>>,
+}
+
+/// Settings for `Camera::render_adaptive`.
+#[derive(Debug, Clone)]
+pub struct AdaptiveSampling {
+    /// Anything below 2 is treated as 2, as the error can't be estimated from a single sample.
+    pub min_samples: usize,
+    pub max_samples: usize,
+    /// A pixel is considered converged once the standard error of its mean luminance is at most
+    /// this value. Colours are in the range [0, 1], so something like 0.01 is a reasonable start.
+    pub error_threshold: f64,
 }
 
 #[derive(Debug)]
@@ -41,7 +81,76 @@ impl<T: Hit> Camera<T> {
             up: up.normalised(),
             forward,
             right,
+            seed: rand::random(),
+            shutter: (0.0, 0.0),
+            lights: Vec::new(),
+        }
+    }
+
+    /// The seed which the per-pixel samplers are derived from. Rendering the same scene with the
+    /// same seed and settings always gives the same result. Cameras start with a random seed.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn shutter(&self) -> (f64, f64) {
+        self.shutter
+    }
+
+    /// Keep the shutter open from `open` to `close`, blurring anything which moves in that time.
+    /// By default it opens and closes instantly at time 0.
+    pub fn set_shutter(&mut self, open: f64, close: f64) {
+        self.shutter = (open, close);
+    }
+
+    /// At every bounce, pick a point on `light` and light the surface from it directly, rather
+    /// than waiting for paths to find it by chance. This is much less noisy for small lights. The
+    /// light should also be in the scene, with an `Emissive` material.
+    pub fn add_light(&mut self, light: std::rc::Rc<quad::Quad>) {
+        self.lights.push(light);
+    }
+
+    /// The density (per unit solid angle) with which `direct_light` picks the direction of `ray`.
+    fn light_pdf(&self, ray: &ray::Ray) -> f64 {
+        self.lights.iter().map(|light| light.pdf(ray)).sum::<f64>() / self.lights.len() as f64
+    }
+
+    /// Light reaching `hit` directly from a point picked on one of the lights, and then leaving
+    /// towards the viewer. Returns `None` if there are no lights, or the material can't be lit
+    /// like this. Weighted against the material finding the light by itself.
+    fn direct_light(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec, time: f64) -> Option<colour::Colour> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        let point = *hit.intersected_surface_normal.get_origin();
+        let pick = sampler::with_current(|rng| Uniform::from(0..self.lights.len()).sample(rng));
+        let light = &self.lights[pick];
+        let sample = light.sample(point);
+        let towards_light = (sample.point - point).normalised();
+        let (reflected, material_pdf) = hit.material.scattering(hit, towards_light, towards_viewer)?;
+        let black = colour::Colour::new(0.0, 0.0, 0.0);
+        let shadow =
+            ray::Ray::leaving_surface(point, *hit.intersected_surface_normal.get_direction(), towards_light).with_time(time);
+        // Anything in the way, short of the light, blocks it.
+        let light_hit = match light.hit(&shadow) {
+            Some(light_hit) if self.scene.hit(&shadow.with_t_range(0.0, light_hit.t * (1.0 - 1e-6))).is_none() => light_hit,
+            _ => return Some(black),
+        };
+        let emitted = light_hit.material.emitted(&light_hit).unwrap_or_else(|| black.clone());
+        let light_pdf = self.light_pdf(&shadow);
+        if light_pdf == 0.0 {
+            return Some(black);
         }
+        let weight = power_heuristic(light_pdf, material_pdf) / light_pdf;
+        Some(colour::Colour::unclamped(
+            reflected.get_red() * emitted.get_red() * weight,
+            reflected.get_green() * emitted.get_green() * weight,
+            reflected.get_blue() * emitted.get_blue() * weight,
+        ))
     }
 
     pub fn new_looking_at(
@@ -62,6 +171,29 @@ impl<T: Hit> Camera<T> {
         Camera::new(scene, origin, up, forward)
     }
 
+    /// Move the camera to `origin` and point it at `looking_at`, keeping everything else (the
+    /// scene, seed and shutter) the same.
+    pub fn set_looking_at(&mut self, origin: WorldVec, up: WorldVec, looking_at: WorldVec) {
+        let moved = Camera::new_looking_at(std::rc::Rc::clone(&self.scene), origin, up, looking_at);
+        self.origin = moved.origin;
+        self.up = moved.up;
+        self.right = moved.right;
+        self.forward = moved.forward;
+    }
+
+    pub fn scene(&self) -> &std::rc::Rc<T> {
+        &self.scene
+    }
+
+    pub fn origin(&self) -> WorldVec {
+        self.origin
+    }
+
+    /// The direction the camera is looking in.
+    pub fn forward(&self) -> WorldVec {
+        self.forward
+    }
+
     pub fn render(
         &self,
         x_size: usize,
@@ -82,6 +214,7 @@ impl<T: Hit> Camera<T> {
             .unwrap()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn render_region(
         &self,
         region_top_left: (usize, usize),
@@ -92,27 +225,215 @@ impl<T: Hit> Camera<T> {
         bounces: usize,
         samples_per_pixel: usize,
     ) -> Result<image::Image, RenderError> {
+        // Average the samples and convert this into an Image.
+        self.render_region_film(
+            region_top_left,
+            region_size,
+            x_size,
+            y_size,
+            fov,
+            bounces,
+            samples_per_pixel,
+        )
+        .map(|film| film.to_image())
+    }
+
+    /// The same as `render_region`, but returns the accumulated samples rather than resolving them
+    /// into an image. Films for separate regions can be combined without losing precision.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_region_film(
+        &self,
+        region_top_left: (usize, usize),
+        region_size: (usize, usize),
+        x_size: usize,
+        y_size: usize,
+        fov: f64,
+        bounces: usize,
+        samples_per_pixel: usize,
+    ) -> Result<film::Film, RenderError> {
         // First, check that the region is correct. For now, we only check that top_left <
-        // bottom_right && bottom_right < (x_size, y_size).
-        if region_top_left.0 + region_size.0 > x_size || region_top_left.1 + region_size.1 > y_size
-        {
-            return Err(RenderError::InvalidRegion {
-                region: (
-                            region_top_left,
-                            (
-                                region_top_left.0 + region_size.0,
-                                region_top_left.1 + region_size.1,
-                            ),
+        // bottom_right && bottom_right < (x_size, y_size). The region may have come over the
+        // network, so its far corner mustn't overflow.
+        let bottom_right = match (
+            region_top_left.0.checked_add(region_size.0),
+            region_top_left.1.checked_add(region_size.1),
+        ) {
+            (Some(right), Some(bottom)) if right <= x_size && bottom <= y_size => (right, bottom),
+            _ => {
+                return Err(RenderError::InvalidRegion {
+                    region: (
+                        region_top_left,
+                        (
+                            region_top_left.0.saturating_add(region_size.0),
+                            region_top_left.1.saturating_add(region_size.1),
                         ),
-                        size: (x_size, y_size),
-            });
+                    ),
+                    size: (x_size, y_size),
+                })
+            }
+        };
+        let projection = self.projection(x_size, y_size, fov);
+
+        // We store the data as floating point to average it later.
+        let mut film =
+            film::Film::seeded(region_size.0, region_size.1, self.seed, region_top_left);
+
+        for _ in 1..=samples_per_pixel {
+            for i in (region_top_left.0)..bottom_right.0 {
+                for j in (region_top_left.1)..bottom_right.1 {
+                    // Add to a total.
+                    let pixel = &mut film[(i - region_top_left.0, j - region_top_left.1)];
+                    let colour =
+                        self.sample_pixel(&projection, (i, j), bounces, &mut pixel.sampler);
+                    pixel.add_sample(&colour);
+                }
+            }
+            // (Potentially blit an update to the screen)
+        }
+        Ok(film)
+    }
+
+    /// Render the whole image, spending more samples on the pixels that need them. Every pixel
+    /// gets at least `min_samples` samples. After that, a pixel stops receiving samples once the
+    /// standard error of its mean luminance falls below `error_threshold`, or once it has received
+    /// `max_samples` samples. The returned film records how many samples each pixel took -- see
+    /// `Film::sample_count_heatmap`.
+    pub fn render_adaptive(
+        &self,
+        x_size: usize,
+        y_size: usize,
+        fov: f64,
+        bounces: usize,
+        adaptive: &AdaptiveSampling,
+    ) -> film::Film {
+        let projection = self.projection(x_size, y_size, fov);
+        let mut film = film::Film::seeded(x_size, y_size, self.seed, (0, 0));
+        let mut converged = vec![false; x_size * y_size];
+        let min_samples = adaptive.min_samples.max(2);
+
+        for pass in 1..=adaptive.max_samples {
+            let mut all_converged = true;
+            for i in 0..x_size {
+                for j in 0..y_size {
+                    if converged[j * x_size + i] {
+                        continue;
+                    }
+                    let pixel = &mut film[(i, j)];
+                    let colour =
+                        self.sample_pixel(&projection, (i, j), bounces, &mut pixel.sampler);
+                    pixel.add_sample(&colour);
+                    if pass >= min_samples
+                        && pixel.standard_error() <= adaptive.error_threshold
+                    {
+                        converged[j * x_size + i] = true;
+                    } else {
+                        all_converged = false;
+                    }
+                }
+            }
+            if all_converged {
+                break;
+            }
         }
+        film
+    }
+
+    /// Render the whole image in passes of one sample per pixel, yielding the image so far after
+    /// each pass. The render stops once `budget` is spent, or once `cancel` is cancelled.
+    pub fn render_progressive(
+        &self,
+        x_size: usize,
+        y_size: usize,
+        fov: f64,
+        bounces: usize,
+        budget: progressive::RenderBudget,
+        cancel: progressive::CancelToken,
+    ) -> progressive::ProgressiveRender<'_, T> {
+        progressive::ProgressiveRender::new(self, x_size, y_size, fov, bounces, budget, cancel)
+    }
+
+    /// Render the whole image, saving a checkpoint to `path` whenever at least `interval` has
+    /// passed since the last one, and once more when the render is complete. If the render is
+    /// interrupted, it can be carried on with `resume_from_checkpoint`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_with_checkpoints(
+        &self,
+        x_size: usize,
+        y_size: usize,
+        fov: f64,
+        bounces: usize,
+        samples_per_pixel: usize,
+        path: &std::path::Path,
+        interval: std::time::Duration,
+    ) -> std::io::Result<film::Film> {
+        let checkpoint = checkpoint::Checkpoint {
+            film: film::Film::seeded(x_size, y_size, self.seed, (0, 0)),
+            fov,
+            bounces,
+        };
+        self.continue_checkpoint(checkpoint, samples_per_pixel, path, interval)
+    }
+
+    /// Carry on with the render saved at `path` until every pixel has `samples_per_pixel`
+    /// samples, checkpointing as in `render_with_checkpoints`. This may also be used to add more
+    /// samples to a render which has already finished. Provided the scene hasn't changed, the
+    /// result is exactly the same as if the render had never been interrupted.
+    pub fn resume_from_checkpoint(
+        &self,
+        path: &std::path::Path,
+        samples_per_pixel: usize,
+        interval: std::time::Duration,
+    ) -> std::io::Result<film::Film> {
+        let checkpoint = checkpoint::Checkpoint::load(path)?;
+        self.continue_checkpoint(checkpoint, samples_per_pixel, path, interval)
+    }
+
+    fn continue_checkpoint(
+        &self,
+        mut checkpoint: checkpoint::Checkpoint,
+        samples_per_pixel: usize,
+        path: &std::path::Path,
+        interval: std::time::Duration,
+    ) -> std::io::Result<film::Film> {
+        let (x_size, y_size) = (checkpoint.film.x_len(), checkpoint.film.y_len());
+        let projection = self.projection(x_size, y_size, checkpoint.fov);
+        let mut last_saved = std::time::Instant::now();
+
+        // Work in passes so that, wherever we're interrupted, every pixel has had the same
+        // number of samples give or take one.
+        for pass in 1..=samples_per_pixel {
+            for i in 0..x_size {
+                for j in 0..y_size {
+                    let pixel = &mut checkpoint.film[(i, j)];
+                    if pixel.samples >= pass {
+                        continue;
+                    }
+                    let colour = self.sample_pixel(
+                        &projection,
+                        (i, j),
+                        checkpoint.bounces,
+                        &mut pixel.sampler,
+                    );
+                    pixel.add_sample(&colour);
+                }
+            }
+            if last_saved.elapsed() >= interval {
+                checkpoint.save(path)?;
+                last_saved = std::time::Instant::now();
+            }
+        }
+        checkpoint.save(path)?;
+        Ok(checkpoint.film)
+    }
+
+    /// Work out where the projection plane sits for an image of the given size.
+    pub(crate) fn projection(&self, x_size: usize, y_size: usize, fov: f64) -> Projection {
         // We define the FOV as the horizonal field of vision.
         // We define the projection plane to be at distance of 1. Therefore:
         let alpha = (fov / 180.0) * std::f64::consts::PI;
         let projection_plane_half_width = (alpha / 2.0).tan();
         let projection_plane_pixel_width =
-            (2.0 * (projection_plane_half_width as f64)) / (x_size as f64);
+            (2.0 * projection_plane_half_width) / (x_size as f64);
 
         // When we refer to the fov, we're referring to the horizontal fov. Therefore:
         let delta_i = (projection_plane_half_width * 2.0) / (x_size as f64);
@@ -121,143 +442,317 @@ impl<T: Hit> Camera<T> {
         // use the same delta as for the vertical case.
         let projection_plane_half_height = delta_i * (y_size as f64 / 2.0);
         let projection_plane_pixel_height =
-            (2.0 * (projection_plane_half_height as f64)) / (y_size as f64);
+            (2.0 * projection_plane_half_height) / (y_size as f64);
 
         let top_left = self.origin - self.right * projection_plane_half_width
             + self.up * projection_plane_half_height;
 
-        // For anti-aliasing:
-        let mut rng = rand::rngs::SmallRng::from_rng(rand::thread_rng()).unwrap();
+        Projection {
+            top_left,
+            delta_i,
+            pixel_width: projection_plane_pixel_width,
+            pixel_height: projection_plane_pixel_height,
+        }
+    }
+
+    /// Trace a single jittered sample through pixel `(i, j)`, returning the colour it gathered.
+    /// All random numbers for the sample are drawn from `sampler`, which is advanced.
+    pub(crate) fn sample_pixel(
+        &self,
+        projection: &Projection,
+        pixel: (usize, usize),
+        bounces: usize,
+        sampler: &mut sampler::Sampler,
+    ) -> colour::Colour {
+        sampler::using(sampler, || self.trace_path(projection, pixel, bounces))
+    }
+
+    fn trace_path(
+        &self,
+        projection: &Projection,
+        (i, j): (usize, usize),
+        bounces: usize,
+    ) -> colour::Colour {
         let jitter_between = Uniform::from(-0.5..=0.5);
+        let jitter = || sampler::with_current(|rng| jitter_between.sample(rng));
+        // Have a mutable coloured ray. Start it on the projection plane in the
+        // appropiate place.
+        let projection_plane_point = projection.top_left
+            + self.right * projection.delta_i * (i as f64)
+            - self.up * projection.delta_i * (j as f64)
+            + self.up // Antialiasing.
+                * jitter()
+                * projection.pixel_height
+            + self.right * jitter() * projection.pixel_width
+            + self.forward.normalised();
+        let mut current_ray = ray::Ray::new(
+            /*origin=*/ self.origin,
+            /*direction=*/ (projection_plane_point - self.origin).normalised(),
+        );
+        let (open, close) = self.shutter;
+        if close > open {
+            // Only draw a time when the shutter is actually open, so that still renders see the
+            // same random numbers as before.
+            let time = sampler::with_current(|rng| Uniform::from(open..close).sample(rng));
+            current_ray = current_ray.with_time(time);
+        } else {
+            current_ray = current_ray.with_time(open);
+        }
 
-        // We store the data as floating point to average it later.
-        let mut image_data = vec![(0.0, 0.0, 0.0); region_size.0 * region_size.1];
+        // Before we do anything, first get a pretty, sky-blue gradient.
+        let t = (current_ray.get_direction().normalised().1 + 1.0) * 0.5;
+        let colour = vec3::Vec3::new(1.0, 1.0, 1.0) * (1.0 - t)
+            + vec3::Vec3::new(0.5, 0.7, 1.0) * t;
+        let start_colour = colour::Colour::new(colour.0, colour.1, colour.2);
 
-        for _ in 1..=samples_per_pixel {
-            for i in (region_top_left.0)..(region_top_left.0 + region_size.0) {
-                for j in (region_top_left.1)..(region_top_left.1 + region_size.1) {
-                    // Have a mutable coloured ray. Start it on the projection plane in the
-                    // appropiate place.
-                    let projection_plane_point = top_left + self.right * delta_i * (i as f64)
-                        - self.up * delta_i * (j as f64)
-                        + self.up // Antialiasing.
-                        * if samples_per_pixel > 0 {
-                            jitter_between.sample(&mut rng)
-                                * projection_plane_pixel_height
-                        } else {
-                            0.0
-                        }
-                    + self.right
-                        * if samples_per_pixel > 0 {
-                            jitter_between.sample(&mut rng) * projection_plane_pixel_width
-                        } else {
-                            0.0
-                        }
-                    + self.forward.normalised();
-                    let mut current_ray = ray::Ray::new(
-                        /*origin=*/ self.origin,
-                        /*direction=*/ (projection_plane_point - self.origin).normalised(),
-                    );
+        let mut reverse_path = Vec::new();
+        let mut colour = start_colour;
+        // The density with which the last surface chose the way to here, if it was also lit from
+        // the lights directly. Reaching a light this way is then weighted against that.
+        let mut material_pdf: Option<f64> = None;
 
-                    // Before we do anything, first get a pretty, sky-blue gradient.
-                    let t = (current_ray.get_direction().normalised().1 + 1.0) * 0.5;
-                    let colour = vec3::Vec3::new(1.0, 1.0, 1.0) * (1.0 - t)
-                        + vec3::Vec3::new(0.5, 0.7, 1.0) * t;
-                    let start_colour = colour::Colour::new(colour.0, colour.1, colour.2);
-
-                    let mut reverse_path = Vec::new();
-                    let mut of_interest = false;
-                    let mut colour = start_colour.clone();
-
-                    // First, build the path that this will go.
-                    for bounce in 1..=bounces {
-                        // Find intersection. Have the Hit bounce it to a new direction and origin.
-                        current_ray = match self.scene.hit(&current_ray) {
-                            Some(material_hit) => {
-                                let normal = material_hit.intersected_surface_normal;
-                                let new_ray = material_hit
-                                    .material
-                                    .sample_gathering_ray(&current_ray, &normal);
-                                reverse_path.push(material_hit);
-                                new_ray
-                            }
-                            None => {
-                                if bounce > 2 {
-                                    of_interest = true;
+        // First, build the path that this will go. Along with each hit, remember which way the
+        // light arrives from (the way we bounced off in) and which way it leaves.
+        for bounce in 1..=bounces {
+            // Find intersection. Have the Hit bounce it to a new direction and origin.
+            current_ray = match self.scene.hit(&current_ray) {
+                Some(material_hit) => {
+                    let normal = material_hit.intersected_surface_normal;
+                    let towards_viewer = -current_ray.get_direction().normalised();
+                    let material_hit = material::resolve(material_hit, towards_viewer);
+                    if let Some(emitted) = material_hit.material.emitted(&material_hit) {
+                        let weight = material_pdf.map_or(1.0, |pdf| power_heuristic(pdf, self.light_pdf(&current_ray)));
+                        reverse_path.push(Interaction::Emission(colour::Colour::unclamped(
+                            emitted.get_red() * weight,
+                            emitted.get_green() * weight,
+                            emitted.get_blue() * weight,
+                        )));
+                        break;
+                    }
+                    // Only light the surface directly if there's a bounce left, with which the
+                    // path could have found the light by itself.
+                    let direct = if bounce < bounces {
+                        self.direct_light(&material_hit, towards_viewer, current_ray.get_time())
+                    } else {
+                        None
+                    };
+                    if let Some(direct) = direct.clone() {
+                        reverse_path.push(Interaction::Direct(direct));
+                    }
+                    let new_ray = material_hit
+                        .material
+                        .sample_gathering_ray(&current_ray, &material_hit);
+                    let towards_light = new_ray.get_direction().normalised();
+                    material_pdf = direct.and_then(|_| {
+                        material_hit.material.scattering(&material_hit, towards_light, towards_viewer).map(|(_, pdf)| pdf)
+                    });
+                    let interior = material_hit.material.interior();
+                    reverse_path.push(Interaction::Surface(material_hit, towards_light, towards_viewer));
+                    // Nudge the new ray off the surface, so that it doesn't hit it again.
+                    let new_ray =
+                        ray::Ray::leaving_surface(*normal.get_origin(), *normal.get_direction(), *new_ray.get_direction())
+                            .with_time(current_ray.get_time());
+                    match interior {
+                        // Light going into a translucent object wanders around inside it, and
+                        // carries on from wherever it comes out.
+                        Some(medium) if towards_light.dot(*normal.get_direction()) < 0.0 => {
+                            material_pdf = None;
+                            match subsurface::random_walk(&*self.scene, &medium, new_ray) {
+                                Some(exit) => {
+                                    reverse_path.push(Interaction::Subsurface(exit.throughput));
+                                    exit.ray
                                 }
-                                break;
+                                None => return colour::Colour::new(0.0, 0.0, 0.0),
                             }
                         }
+                        _ => new_ray,
                     }
-
-                    // Now, do some colouring.
-                    let mut path_iter = reverse_path.iter().rev();
-                    // For node 0, we'll say that the angle of incidence is exactly 90 degrees
-                    // (or, if you prefer, pi radians), indicating no attenuation due to viewing angle.
-                    if let Some(hit) = path_iter.next() {
-                        colour = hit.material.colour(
-                            colour,
-                            &hit.intersected_surface_normal,
-                            std::f64::consts::PI,
-                        );
-                    }
-
-                    for (prev, current) in reverse_path.iter().rev().zip(path_iter) {
-                        // We need to calculate the angle of incidence. For node _n_ in the path, the
-                        // direction of the bounced vector will be node _n_'s origin - node _n - 1_'s
-                        // origin, normalised.  Therefore, the angle of incidence is the angle between
-                        // the normal and this vector. We can calculate this by rearranging a . b = |a| |b|
-                        // cos(theta), to theta = arccos((a . b) / (|a| |b|)). With unit vectors, |a| =
-                        // |b| = 1.
-                        let travel_direction = (*prev.intersected_surface_normal.get_origin()
-                            - *current.intersected_surface_normal.get_origin())
-                            .normalised();
-                        let normal_direction = current
-                            .intersected_surface_normal
-                            .get_direction()
-                            .normalised();
-                        let angle_of_incidence = travel_direction.dot(normal_direction).acos();
-                        colour = current.material.colour(
-                            colour,
-                            &current.intersected_surface_normal,
-                            angle_of_incidence,
-                        );
-                    }
-
-                    if of_interest {
-                        // Print some stats about this path.
-                        //eprintln!("Path size: {}, Starting colour: {:?}, End colour: {:?}", reverse_path.len(), start_colour, colour);
-                    }
-
-                    // Add to a total.
-                    let image_data_i = i - region_top_left.0;
-                    let image_data_j = j - region_top_left.1;
-                    let pixel = &mut image_data[image_data_j * region_size.0 + image_data_i];
-                    pixel.0 += colour.get_red();
-                    pixel.1 += colour.get_green();
-                    pixel.2 += colour.get_blue();
+                }
+                None => {
+                    break;
                 }
             }
-            // (Potentially blit an update to the screen)
-        }
-        // Divide by number of samples to make an average.
-        for colour_tuple in image_data.iter_mut() {
-            colour_tuple.0 /= samples_per_pixel as f64;
-            colour_tuple.1 /= samples_per_pixel as f64;
-            colour_tuple.2 /= samples_per_pixel as f64;
         }
-        // Convert this into an Image.
-        let mut result = image::Image::new(region_size.0, region_size.1);
-        for i in 0..region_size.0 {
-            for j in 0..region_size.1 {
-                let pixel = image_data[j * region_size.0 + i];
-                result[(i, j)] = vec3::Vec3::new(
-                    (pixel.0 * 255.0).round() as u8,
-                    (pixel.1 * 255.0).round() as u8,
-                    (pixel.2 * 255.0).round() as u8,
-                );
-            }
+
+        // Now, do some colouring, from the far end of the path back towards the camera.
+        for interaction in reverse_path.iter().rev() {
+            colour = match interaction {
+                Interaction::Surface(hit, towards_light, towards_viewer) => hit
+                    .material
+                    .colour_between(colour, hit, *towards_light, *towards_viewer),
+                Interaction::Subsurface([r, g, b]) => colour::Colour::unclamped(
+                    colour.get_red() * r,
+                    colour.get_green() * g,
+                    colour.get_blue() * b,
+                ),
+                Interaction::Emission(emitted) => emitted.clone(),
+                Interaction::Direct(direct) => colour::Colour::unclamped(
+                    colour.get_red() + direct.get_red(),
+                    colour.get_green() + direct.get_green(),
+                    colour.get_blue() + direct.get_blue(),
+                ),
+            };
         }
-        Ok(result)
+        colour
+    }
+}
+
+/// How much weight to give a sample drawn with density `pdf`, when it could also have been drawn by
+/// another strategy with density `other`. The weights of the two always add up to 1.
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0.0 {
+        0.0
+    } else {
+        a / (a + b)
+    }
+}
+
+/// The projection plane, one unit in front of the camera, that we shoot rays through.
+pub(crate) struct Projection {
+    top_left: WorldVec,
+    delta_i: f64,
+    pixel_width: f64,
+    pixel_height: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambertian;
+    use crate::scene;
+    use crate::sphere;
+
+    fn camera_for(scene: scene::Scene<'static>) -> Camera<scene::Scene<'static>> {
+        Camera::new_looking_at(
+            std::rc::Rc::new(scene),
+            vec3::Vec3::new(0.0, 0.0, -3.0),
+            vec3::Vec3::new(0.0, 1.0, 0.0),
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_adaptive_sampling() {
+        let adaptive = AdaptiveSampling {
+            min_samples: 4,
+            max_samples: 64,
+            error_threshold: 0.01,
+        };
+
+        // The sky is the same colour for every sample through a pixel (up to jitter), so an empty
+        // scene should converge as soon as it is allowed to.
+        let camera = camera_for(scene::Scene::new());
+        let film = camera.render_adaptive(16, 8, 60.0, 10, &adaptive);
+        assert!(film.pixels().iter().all(|p| p.samples == adaptive.min_samples));
+        // Pixels can't converge on fewer than two samples, whatever the minimum is.
+        let too_few = AdaptiveSampling {
+            min_samples: 0,
+            ..adaptive.clone()
+        };
+        let film = camera.render_adaptive(16, 8, 60.0, 10, &too_few);
+        assert!(film.pixels().iter().all(|p| p.samples == 2));
+
+        // Paths off a diffuse sphere bounce a random number of times, so some of the pixels covering
+        // it should need more samples.
+        let mut scene = scene::Scene::new();
+        scene.add_object(std::rc::Rc::new(sphere::Sphere::new(
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.1, 0.1))),
+        )));
+        let film = camera_for(scene).render_adaptive(16, 8, 60.0, 10, &adaptive);
+        assert!(film.pixels().iter().any(|p| p.samples > adaptive.min_samples));
+        assert!(film.pixels().iter().all(|p| p.samples <= adaptive.max_samples));
+        assert_eq!(film.to_image().x_len(), 16);
+    }
+
+    #[test]
+    fn test_subsurface() {
+        // Light goes into the sphere and comes back out, having lost more green and blue.
+        let mut scene = scene::Scene::new();
+        scene.add_object(std::rc::Rc::new(sphere::Sphere::new(
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(subsurface::Subsurface::with_medium(subsurface::Medium {
+                mean_free_path: [0.1; 3],
+                albedo: [0.99, 0.6, 0.6],
+                anisotropy: 0.0,
+                ior: 1.3,
+            })),
+        )));
+        let mut camera = camera_for(scene);
+        camera.set_seed(12);
+        let image = camera.render(8, 8, 30.0, 10, 16);
+        let centre = image[(4, 4)];
+        assert!(centre.0 > 0 && centre.0 > centre.1, "{:?}", centre);
+    }
+
+    #[test]
+    fn test_direct_lighting() {
+        // A small light over a grey floor, inside a black sphere so that nothing else lights it.
+        let mut scene = scene::Scene::new();
+        let grey = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        let black = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.0, 0.0, 0.0)));
+        let light = std::rc::Rc::new(quad::Quad::axis_aligned(
+            vec3::Vec3::new(-0.25, 1.0, -0.25),
+            vec3::Vec3::new(0.25, 1.0, 0.25),
+            std::rc::Rc::new(crate::emissive::Emissive::new(colour::Colour::new(1.0, 1.0, 1.0))),
+        ));
+        scene.add_object(std::rc::Rc::new(crate::plane::Plane::new(
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+            vec3::Vec3::new(0.0, 1.0, 0.0),
+            grey,
+        )));
+        scene.add_object(std::rc::Rc::new(sphere::Sphere::new(vec3::Vec3::new(0.0, 0.0, 0.0), 100.0, black)));
+        scene.add_object(light.clone());
+        let mut camera = Camera::new_looking_at(
+            std::rc::Rc::new(scene),
+            vec3::Vec3::new(0.0, 3.0, -3.0),
+            vec3::Vec3::new(0.0, 1.0, 0.0),
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+        );
+
+        // The floor under the light, as seen through the middle of the picture.
+        let statistics = |camera: &Camera<scene::Scene<'static>>| {
+            let projection = camera.projection(9, 9, 1.0);
+            let mut sampler = sampler::Sampler::new(21);
+            let samples = 20000;
+            let values: Vec<f64> = (0..samples)
+                .map(|_| camera.sample_pixel(&projection, (4, 4), 3, &mut sampler).get_red())
+                .collect();
+            let mean = values.iter().sum::<f64>() / samples as f64;
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / samples as f64;
+            (mean, variance, (variance / samples as f64).sqrt())
+        };
+        let (by_chance, chance_variance, error) = statistics(&camera);
+        camera.add_light(light);
+        let (direct, direct_variance, _) = statistics(&camera);
+        assert!(by_chance > 0.0);
+        assert!((direct - by_chance).abs() < 4.0 * error, "{} vs {} (+- {})", direct, by_chance, error);
+        assert!(direct_variance < chance_variance / 4.0, "{} vs {}", direct_variance, chance_variance);
+    }
+
+    #[test]
+    fn test_motion_blur() {
+        // A black sphere crossing the middle of the view. Caught in the middle of its path, it
+        // covers the centre; caught as it sets off, it doesn't. With the shutter open all the way,
+        // the centre sees it for only part of the time.
+        let mut scene = scene::Scene::new();
+        scene.add_object(std::rc::Rc::new(crate::motion::MovingSphere::linear(
+            (vec3::Vec3::new(-2.0, 0.0, 0.0), 0.0),
+            (vec3::Vec3::new(2.0, 0.0, 0.0), 1.0),
+            0.5,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.0, 0.0, 0.0))),
+        )));
+        let mut camera = camera_for(scene);
+        camera.set_seed(20);
+        let mut centre = |open: f64, close: f64| {
+            camera.set_shutter(open, close);
+            camera.render(8, 8, 30.0, 10, 64)[(4, 4)].2
+        };
+        let (still, moved_off, blurred) = (centre(0.5, 0.5), centre(0.0, 0.0), centre(0.0, 1.0));
+        assert_eq!(still, 0);
+        assert!(blurred > still && blurred < moved_off, "{} {} {}", still, blurred, moved_off);
     }
 }
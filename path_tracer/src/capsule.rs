@@ -0,0 +1,179 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::polynomial;
+use crate::ray;
+use crate::WorldVec;
+
+/// A cylinder with hemispherical ends: every point within `radius` of the line from `start` to
+/// `end`.
+#[derive(Debug)]
+pub struct Capsule {
+    start: WorldVec,
+    end: WorldVec,
+    radius: f64,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Capsule {
+    pub fn new(
+        start: WorldVec,
+        end: WorldVec,
+        radius: f64,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Capsule {
+        Capsule {
+            start,
+            end,
+            radius,
+            material,
+        }
+    }
+
+    /// How far along the line from `start` to `end` the closest point to `point` is, from 0 to 1.
+    fn along_axis(&self, point: WorldVec) -> f64 {
+        let axis = self.end - self.start;
+        ((point - self.start).dot(axis) / axis.dot(axis)).clamp(0.0, 1.0)
+    }
+}
+
+impl hit::Hit for Capsule {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let origin = *from.get_origin();
+        let direction = *from.get_direction();
+        let axis = self.end - self.start;
+        let axis_length_squared = axis.dot(axis);
+        let radius_squared = self.radius * self.radius;
+
+        // The curved side: solve for the distance from the (infinite) axis being the radius,
+        // keeping hits between the two ends.
+        let offset = origin - self.start;
+        let d_axis = direction.dot(axis);
+        let o_axis = offset.dot(axis);
+        let a = axis_length_squared * direction.dot(direction) - d_axis * d_axis;
+        let b = 2.0 * (axis_length_squared * direction.dot(offset) - o_axis * d_axis);
+        let c = axis_length_squared * (offset.dot(offset) - radius_squared) - o_axis * o_axis;
+        let mut candidates: Vec<f64> = polynomial::real_roots(&[c, b, a])
+            .into_iter()
+            .filter(|&t| {
+                let along = o_axis + d_axis * t;
+                (0.0..=axis_length_squared).contains(&along)
+            })
+            .collect();
+
+        // The ends: spheres around each end of the axis, keeping the halves beyond the axis.
+        for (centre, beyond) in [(self.start, -1.0), (self.end, 1.0)] {
+            let offset = origin - centre;
+            let roots = polynomial::real_roots(&[
+                offset.dot(offset) - radius_squared,
+                2.0 * offset.dot(direction),
+                direction.dot(direction),
+            ]);
+            candidates.extend(roots.into_iter().filter(|&t| {
+                let point = origin + direction * t;
+                (point - centre).dot(axis) * beyond >= 0.0
+            }));
+        }
+
+        let t = candidates
+            .into_iter()
+            .filter(|&t| from.in_range(t))
+            .fold(None, |closest: Option<f64>, t| Some(closest.map_or(t, |c| c.min(t))))?;
+
+        let intersection_point = from.at(t);
+        // The normal points away from the closest point on the axis.
+        let along = self.along_axis(intersection_point);
+        let normal = (intersection_point - (self.start + axis * along)).normalised();
+
+        // u goes around the axis, v along the whole length, from the tip of one end to the other.
+        let axis_length = axis_length_squared.sqrt();
+        let (tangent, bitangent) = (axis * (1.0 / axis_length)).tangent_basis();
+        let distance_along = (intersection_point - self.start).dot(axis) / axis_length;
+        let uv = (
+            0.5 + normal.dot(bitangent).atan2(normal.dot(tangent)) / (2.0 * std::f64::consts::PI),
+            ((distance_along + self.radius) / (axis_length + 2.0 * self.radius)).clamp(0.0, 1.0),
+        );
+
+        Some(
+            hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv)
+                .with_tangent(axis.cross(normal)),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let radius = WorldVec::new(self.radius, self.radius, self.radius);
+        Some(
+            aabb::Aabb::new(self.start - radius, self.start + radius)
+                .union(&aabb::Aabb::new(self.end - radius, self.end + radius)),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_capsule() {
+        let capsule = Capsule::new(
+            WorldVec::new(0.0, 0.0, 5.0),
+            WorldVec::new(0.0, 4.0, 5.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0))),
+        );
+
+        // Cast a ray along the z axis, at the middle of the capsule. This should hit the side.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 2.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = capsule.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(0.0, 2.0, 4.0)).length() < 1e-12);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 0.0, -1.0)).length() < 1e-12);
+        assert!((hit.uv.1 - 0.5).abs() < 1e-12);
+
+        // Straight down onto the top end.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 10.0, 5.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = capsule.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(0.0, 5.0, 5.0)).length() < 1e-12);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 1.0, 0.0)).length() < 1e-12);
+
+        // Just inside the rounded bottom end, then just past it. The second should not hit anything.
+        let ray = ray::Ray::new(WorldVec::new(0.0, -0.9, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(capsule.hit(&ray).is_some());
+        let ray = ray::Ray::new(WorldVec::new(0.0, -1.1, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(capsule.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_capsule_bounds_and_uv() {
+        let capsule = Capsule::new(
+            WorldVec::new(0.0, 0.0, 5.0),
+            WorldVec::new(0.0, 4.0, 5.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0))),
+        );
+        let bounds = capsule.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (WorldVec::new(-1.0, -1.0, 4.0), WorldVec::new(1.0, 5.0, 6.0)));
+
+        // v runs from the tip of one end to the tip of the other.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 10.0, 5.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert!((capsule.hit(&ray).unwrap().uv.1 - 1.0).abs() < 1e-12);
+        let ray = ray::Ray::new(WorldVec::new(0.0, -10.0, 5.0), WorldVec::new(0.0, 1.0, 0.0));
+        assert!(capsule.hit(&ray).unwrap().uv.1.abs() < 1e-12);
+        // u goes a quarter of the way around between sides a quarter turn apart.
+        let front = capsule.hit(&ray::Ray::new(WorldVec::new(0.0, 2.0, 0.0), WorldVec::new(0.0, 0.0, 1.0)));
+        let side = capsule.hit(&ray::Ray::new(WorldVec::new(5.0, 2.0, 5.0), WorldVec::new(-1.0, 0.0, 0.0)));
+        let turn = (front.unwrap().uv.0 - side.unwrap().uv.0).rem_euclid(1.0);
+        assert!((turn - 0.25).abs() < 1e-12 || (turn - 0.75).abs() < 1e-12);
+
+        // Grazing the side, where the two roots meet, then just missing it.
+        let ray = ray::Ray::new(WorldVec::new(1.0, 2.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = capsule.hit(&ray).expect("The ray should graze the side.");
+        assert!((*hit.intersected_surface_normal.get_origin() - WorldVec::new(1.0, 2.0, 5.0)).length() < 1e-9);
+        let ray = ray::Ray::new(WorldVec::new(1.0 + 1e-6, 2.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(capsule.hit(&ray).is_none());
+    }
+}
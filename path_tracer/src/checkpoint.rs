@@ -0,0 +1,221 @@
+use crate::film;
+use crate::sampler;
+
+use std::convert::TryInto;
+use std::io::{Read, Write};
+
+const MAGIC: &[u8; 8] = b"PTCKPT01";
+
+/// Everything needed to carry on with an interrupted render: the accumulated film (which includes
+/// each pixel's sampler state) and the settings the render was started with.
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    pub film: film::Film,
+    pub fov: f64,
+    pub bounces: usize,
+}
+
+impl Checkpoint {
+    /// Serialise the checkpoint. All values are written in little-endian order so that checkpoints
+    /// can be moved between machines.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&self.fov.to_le_bytes())?;
+        writer.write_all(&(self.bounces as u64).to_le_bytes())?;
+        writer.write_all(&(self.film.x_len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.film.y_len() as u64).to_le_bytes())?;
+        for pixel in self.film.pixels() {
+            writer.write_all(&pixel.red.to_le_bytes())?;
+            writer.write_all(&pixel.green.to_le_bytes())?;
+            writer.write_all(&pixel.blue.to_le_bytes())?;
+            writer.write_all(&pixel.luminance_squared.to_le_bytes())?;
+            writer.write_all(&(pixel.samples as u64).to_le_bytes())?;
+            writer.write_all(&pixel.sampler.state().to_le_bytes())?;
+        }
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> std::io::Result<Checkpoint> {
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a path tracer checkpoint"));
+        }
+        let fov = read_f64(&mut reader)?;
+        let bounces = read_size(&mut reader)?;
+        let x_size = read_size(&mut reader)?;
+        let y_size = read_size(&mut reader)?;
+        let count = x_size
+            .checked_mul(y_size)
+            .ok_or_else(|| invalid_data("checkpoint is too large"))?;
+        // Only allocate the film once the pixels have actually been read, so that a bad size can't
+        // make us allocate everything.
+        let mut pixels = Vec::new();
+        for _ in 0..count {
+            pixels.push(film::FilmPixel {
+                red: read_f64(&mut reader)?,
+                green: read_f64(&mut reader)?,
+                blue: read_f64(&mut reader)?,
+                luminance_squared: read_f64(&mut reader)?,
+                samples: read_size(&mut reader)?,
+                sampler: sampler::Sampler::new(read_u64(&mut reader)?),
+            });
+        }
+        let mut film = film::Film::new(x_size, y_size);
+        for (index, pixel) in pixels.into_iter().enumerate() {
+            film[(index % x_size, index / x_size)] = pixel;
+        }
+        Ok(Checkpoint { film, fov, bounces })
+    }
+
+    /// Write the checkpoint to a file. We write to a temporary file first and then move it into
+    /// place, so that being killed part-way through a save doesn't lose the previous checkpoint.
+    pub fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let temporary = path.with_extension("partial");
+        self.write_to(std::io::BufWriter::new(std::fs::File::create(&temporary)?))?;
+        std::fs::rename(temporary, path)
+    }
+
+    pub fn load(path: &std::path::Path) -> std::io::Result<Checkpoint> {
+        Checkpoint::read_from(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+}
+
+pub(crate) fn read_u64<R: Read>(reader: &mut R) -> std::io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// Read a `u64` which must fit in a `usize`.
+fn read_size<R: Read>(reader: &mut R) -> std::io::Result<usize> {
+    read_u64(reader)?
+        .try_into()
+        .map_err(|_| invalid_data("size too large for this machine"))
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn read_f64<R: Read>(reader: &mut R) -> std::io::Result<f64> {
+    Ok(f64::from_bits(read_u64(reader)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aabb;
+    use crate::camera;
+    use crate::colour;
+    use crate::hit;
+    use crate::lambertian;
+    use crate::ray;
+    use crate::scene;
+    use crate::sphere;
+    use crate::vec3;
+
+    /// A scene which panics once it has been hit by a number of rays, as if the render had been
+    /// killed part of the way through.
+    #[derive(Debug)]
+    struct Tripwire {
+        scene: scene::Scene<'static>,
+        rays_left: std::cell::Cell<usize>,
+    }
+
+    impl hit::Hit for Tripwire {
+        fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+            let rays_left = self.rays_left.get();
+            if rays_left == 0 {
+                panic!("Interrupted");
+            }
+            self.rays_left.set(rays_left - 1);
+            self.scene.hit(from)
+        }
+
+        fn bounding_box(&self) -> Option<aabb::Aabb> {
+            self.scene.bounding_box()
+        }
+    }
+
+    fn camera(rays: usize) -> camera::Camera<Tripwire> {
+        let mut scene = scene::Scene::new();
+        scene.add_object(std::rc::Rc::new(sphere::Sphere::new(
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        )));
+        let mut camera = camera::Camera::new_looking_at(
+            std::rc::Rc::new(Tripwire {
+                scene,
+                rays_left: std::cell::Cell::new(rays),
+            }),
+            vec3::Vec3::new(0.0, 0.0, -3.0),
+            vec3::Vec3::new(0.0, 1.0, 0.0),
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+        );
+        camera.set_seed(1234);
+        camera
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut film = film::Film::seeded(3, 2, 99, (0, 0));
+        film[(1, 1)].add_sample(&colour::Colour::new(0.25, 0.5, 1.0));
+        let checkpoint = Checkpoint {
+            film,
+            fov: 45.0,
+            bounces: 7,
+        };
+        let mut bytes = Vec::new();
+        checkpoint.write_to(&mut bytes).unwrap();
+        let read = Checkpoint::read_from(&bytes[..]).unwrap();
+        assert_eq!(read.fov, 45.0);
+        assert_eq!(read.bounces, 7);
+        assert_eq!(read.film.pixels(), checkpoint.film.pixels());
+
+        assert!(Checkpoint::read_from(&b"not a checkpoint"[..]).is_err());
+    }
+
+    #[test]
+    fn test_bad_sizes() {
+        let header = |x_size: u64, y_size: u64| {
+            let mut bytes = MAGIC.to_vec();
+            for value in &[60f64.to_bits(), 10, x_size, y_size] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes
+        };
+        let error = Checkpoint::read_from(&header(u64::MAX, 2)[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        // Huge, but the pixels aren't there, so we find out before allocating the film.
+        let error = Checkpoint::read_from(&header(1 << 30, 1 << 30)[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_resume_matches_uninterrupted() {
+        let directory = std::env::temp_dir();
+        let path = directory.join(format!("path_tracer_resume_{}.ckpt", std::process::id()));
+        let interval = std::time::Duration::from_secs(0);
+
+        // Kill the render part of the way through, leaving the last checkpoint behind.
+        let interrupted = camera(200);
+        let render = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            interrupted.render_with_checkpoints(16, 8, 60.0, 10, 4, &path, interval)
+        }));
+        assert!(render.is_err());
+        let samples = Checkpoint::load(&path).unwrap().film.pixels()[0].samples;
+        assert!(samples > 0 && samples < 4, "{}", samples);
+
+        let resumed = camera(usize::MAX).resume_from_checkpoint(&path, 4, interval).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let uninterrupted = camera(usize::MAX)
+            .render_with_checkpoints(16, 8, 60.0, 10, 4, &path, interval)
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(resumed.pixels(), uninterrupted.pixels());
+    }
+}
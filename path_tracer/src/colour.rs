@@ -17,6 +17,12 @@ impl Colour {
         let max = max!(f64, 1.0, r, g, b);
         Colour(vec3::Vec3::new(r / max, g / max, b / max))
     }
+    /// Create a colour without normalising it. Light along a path, and the weights of samples,
+    /// can be brighter than 1; squashing them back into range would darken the render, so they
+    /// are only clamped when the film is resolved into an image.
+    pub fn unclamped(r: f64, g: f64, b: f64) -> Colour {
+        Colour(vec3::Vec3::new(r, g, b))
+    }
     pub fn get_red(&self) -> f64 {
         (self.0).0
     }
@@ -26,6 +32,25 @@ impl Colour {
     pub fn get_blue(&self) -> f64 {
         (self.0).2
     }
+    /// Blend from `self` (at `s = 0`) to `other` (at `s = 1`). Blending colours in range keeps
+    /// them in range, so this doesn't normalise.
+    pub fn mix(&self, other: &Colour, s: f64) -> Colour {
+        let blend = |a: f64, b: f64| a + (b - a) * s;
+        Colour::unclamped(
+            blend(self.get_red(), other.get_red()),
+            blend(self.get_green(), other.get_green()),
+            blend(self.get_blue(), other.get_blue()),
+        )
+    }
+    /// The relative luminance of this colour, using the Rec. 709 coefficients.
+    pub fn luminance(&self) -> f64 {
+        luminance(self.get_red(), self.get_green(), self.get_blue())
+    }
+}
+
+/// The relative luminance of some linear RGB triplet, using the Rec. 709 coefficients.
+pub fn luminance(r: f64, g: f64, b: f64) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
 }
 
 #[cfg(test)]
@@ -46,4 +71,11 @@ mod tests {
         assert_eq!(colour.get_blue(), 1.0);
     }
 
+    #[test]
+    fn test_unclamped() {
+        assert_eq!(Colour::new(4.0, 2.0, 1.0).get_red(), 1.0);
+        let colour = Colour::unclamped(4.0, 2.0, 1.0);
+        assert_eq!((colour.get_red(), colour.get_green(), colour.get_blue()), (4.0, 2.0, 1.0));
+    }
+
 }
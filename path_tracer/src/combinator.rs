@@ -0,0 +1,451 @@
+//! Materials made out of other materials: blends, clear coats, and cut-outs. Each picks one of its
+//! parts to handle every hit (see `Material::choose`), so any materials can be combined, including
+//! other combinations.
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::texture;
+use crate::WorldVec;
+
+/// A blend of two materials: `weight` (read as a scalar) is how much of `second` there is, from 0
+/// (all `first`) to 1 (all `second`).
+#[derive(Debug)]
+pub struct Mix {
+    first: std::rc::Rc<dyn material::Material>,
+    second: std::rc::Rc<dyn material::Material>,
+    weight: std::rc::Rc<dyn texture::Texture>,
+}
+
+impl Mix {
+    pub fn new(
+        first: std::rc::Rc<dyn material::Material>,
+        second: std::rc::Rc<dyn material::Material>,
+        weight: f64,
+    ) -> Mix {
+        Mix::textured(first, second, std::rc::Rc::new(texture::Constant::scalar(weight)))
+    }
+
+    /// A blend which varies across the surface.
+    pub fn textured(
+        first: std::rc::Rc<dyn material::Material>,
+        second: std::rc::Rc<dyn material::Material>,
+        weight: std::rc::Rc<dyn texture::Texture>,
+    ) -> Mix {
+        Mix { first, second, weight }
+    }
+
+    fn pick(&self, hit: &hit::MaterialHit) -> std::rc::Rc<dyn material::Material> {
+        if microfacet::random() < self.weight.scalar(hit) {
+            self.second.clone()
+        } else {
+            self.first.clone()
+        }
+    }
+}
+
+impl material::Material for Mix {
+    // The camera resolves a `Mix` into one of its parts before sampling, so these are only used
+    // when it isn't, and are approximate: the colour is the blend of what each part would give.
+
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        self.pick(hit).sample_gathering_ray(reflected_ray, hit)
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        let first = self.first.colour(start_colour.clone(), hit, angle_of_incidence);
+        let second = self.second.colour(start_colour, hit, angle_of_incidence);
+        first.mix(&second, self.weight.scalar(hit).clamp(0.0, 1.0))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let first = self.first.colour_between(start_colour.clone(), hit, towards_light, towards_viewer);
+        let second = self.second.colour_between(start_colour, hit, towards_light, towards_viewer);
+        first.mix(&second, self.weight.scalar(hit).clamp(0.0, 1.0))
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, _towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        Some(self.pick(hit))
+    }
+}
+
+/// The cosine of the angle between `direction` and the shading normal, on whichever side it's on.
+fn cos_to_normal(hit: &hit::MaterialHit, direction: WorldVec) -> f64 {
+    hit.shading_normal.normalised().dot(direction.normalised()).abs().min(1.0)
+}
+
+/// A clear dielectric coat, such as varnish or the lacquer on car paint, over any other material.
+/// Light either reflects off the coat, in proportion to its Fresnel reflectance, or passes
+/// through it (tinted by its colour) to the base and back out again. The coat is thin, so the
+/// bending of rays passing through it is ignored.
+#[derive(Debug)]
+pub struct Layered {
+    /// The refractive index of the coat.
+    ior: f64,
+    coat: std::rc::Rc<dyn material::Material>,
+    through_coat: std::rc::Rc<ThroughCoat>,
+}
+
+impl Layered {
+    /// `roughness` is the coat's, read as a scalar from 0 (glossy) to 1.
+    pub fn new(
+        base: std::rc::Rc<dyn material::Material>,
+        ior: f64,
+        roughness: std::rc::Rc<dyn texture::Texture>,
+    ) -> Layered {
+        Layered {
+            ior,
+            coat: std::rc::Rc::new(Coat { ior, roughness }),
+            through_coat: std::rc::Rc::new(ThroughCoat {
+                base,
+                ior,
+                tint: std::rc::Rc::new(texture::Constant::scalar(1.0)),
+            }),
+        }
+    }
+
+    /// The same layers, with a coloured coat. `tint` is the colour of light passing straight
+    /// through the coat and back; light passing through at an angle is tinted more.
+    pub fn with_tint(self, tint: std::rc::Rc<dyn texture::Texture>) -> Layered {
+        let through_coat = ThroughCoat {
+            base: self.through_coat.base.clone(),
+            ior: self.ior,
+            tint,
+        };
+        Layered {
+            through_coat: std::rc::Rc::new(through_coat),
+            ..self
+        }
+    }
+}
+
+impl material::Material for Layered {
+    // Only used when the camera doesn't resolve the layers, in which case this is just the base.
+
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        self.through_coat.sample_gathering_ray(reflected_ray, hit)
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        self.through_coat.colour(start_colour, hit, angle_of_incidence)
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        self.through_coat.colour_between(start_colour, hit, towards_light, towards_viewer)
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        // Reflecting off the coat in proportion to its reflectance cancels the Fresnel term for the
+        // way out, for both the coat and the base.
+        let reflectance = microfacet::fresnel_dielectric(cos_to_normal(hit, towards_viewer), self.ior);
+        if microfacet::random() < reflectance {
+            Some(self.coat.clone())
+        } else {
+            Some(self.through_coat.clone())
+        }
+    }
+}
+
+/// The reflection off the top of a `Layered` material, when it was chosen in proportion to the
+/// Fresnel reflectance of the whole surface.
+#[derive(Debug)]
+struct Coat {
+    ior: f64,
+    roughness: std::rc::Rc<dyn texture::Texture>,
+}
+
+impl material::Material for Coat {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        let h = if wo.2 > 0.0 {
+            microfacet::Ggx::from_roughness(self.roughness.scalar(hit), 0.0)
+                .sample_visible_normal(wo, (microfacet::random(), microfacet::random()))
+        } else {
+            WorldVec::new(0.0, 0.0, 1.0)
+        };
+        ray::Ray::new(
+            *hit.intersected_surface_normal.get_origin(),
+            frame.to_world(microfacet::reflect(wo, h)),
+        )
+    }
+
+    fn colour(&self, start_colour: colour::Colour, _hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        start_colour
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wi.2 <= 0.0 || wo.2 <= 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        // The facet's reflectance, relative to the whole surface's which chose this, and the
+        // shadowing the sampling didn't account for.
+        let ggx = microfacet::Ggx::from_roughness(self.roughness.scalar(hit), 0.0);
+        let h = (wi + wo).normalised();
+        let reflectance = microfacet::fresnel_dielectric(wo.dot(h), self.ior)
+            / microfacet::fresnel_dielectric(cos_to_normal(hit, towards_viewer), self.ior);
+        let weight = reflectance * ggx.masking_shadowing(wi, wo) / ggx.masking(wo);
+        material::attenuate(&start_colour, &colour::Colour::unclamped(weight, weight, weight))
+    }
+}
+
+/// Light reaching the base of a `Layered` material, through its coat. Having been chosen in
+/// proportion to the light getting out through the coat, what's left is the light getting in.
+#[derive(Debug)]
+struct ThroughCoat {
+    base: std::rc::Rc<dyn material::Material>,
+    ior: f64,
+    tint: std::rc::Rc<dyn texture::Texture>,
+}
+
+impl ThroughCoat {
+    /// How much light gets through the coat to the base, and back out.
+    fn transmittance(&self, hit: &hit::MaterialHit, towards_light: WorldVec, towards_viewer: WorldVec) -> colour::Colour {
+        let (cos_i, cos_o) = (cos_to_normal(hit, towards_light), cos_to_normal(hit, towards_viewer));
+        let entering = 1.0 - microfacet::fresnel_dielectric(cos_i, self.ior);
+        // The tint is for the path straight down and back up; slanted paths are longer.
+        let path = 0.5 * (1.0 / cos_i.max(1e-3) + 1.0 / cos_o.max(1e-3));
+        let tint = self.tint.colour(hit);
+        let channel = |value: f64| entering * value.powf(path);
+        colour::Colour::new(channel(tint.get_red()), channel(tint.get_green()), channel(tint.get_blue()))
+    }
+}
+
+impl material::Material for ThroughCoat {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        self.base.sample_gathering_ray(reflected_ray, hit)
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        let colour = self.base.colour(start_colour, hit, angle_of_incidence);
+        material::attenuate(&colour, &self.tint.colour(hit))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let colour = self.base.colour_between(start_colour, hit, towards_light, towards_viewer);
+        material::attenuate(&colour, &self.transmittance(hit, towards_light, towards_viewer))
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        // Keep the coat over whichever part of the base is chosen.
+        self.base.choose(hit, towards_viewer).map(|base| {
+            let chosen: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(ThroughCoat {
+                base,
+                ior: self.ior,
+                tint: self.tint.clone(),
+            });
+            chosen
+        })
+    }
+}
+
+/// A cut-out: where `opacity` (read as a scalar) is 0, rays pass straight through as if the
+/// surface wasn't there, and where it's 1 they meet `base`. This is for leaves, fences and the
+/// like, modelled as flat surfaces with a mask.
+#[derive(Debug)]
+pub struct Opacity {
+    base: std::rc::Rc<dyn material::Material>,
+    opacity: std::rc::Rc<dyn texture::Texture>,
+    pass_through: std::rc::Rc<dyn material::Material>,
+}
+
+impl Opacity {
+    pub fn new(base: std::rc::Rc<dyn material::Material>, opacity: std::rc::Rc<dyn texture::Texture>) -> Opacity {
+        Opacity {
+            base,
+            opacity,
+            pass_through: std::rc::Rc::new(PassThrough),
+        }
+    }
+}
+
+impl material::Material for Opacity {
+    // Only used when the camera doesn't resolve the mask.
+
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        self.choose(hit, towards_viewer)
+            .unwrap_or_else(|| self.base.clone())
+            .sample_gathering_ray(reflected_ray, hit)
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        self.base.colour(start_colour, hit, angle_of_incidence)
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        // A ray carrying straight on must have passed through.
+        if towards_light.dot(towards_viewer) < -1.0 + 1e-9 {
+            start_colour
+        } else {
+            self.base.colour_between(start_colour, hit, towards_light, towards_viewer)
+        }
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, _towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        if microfacet::random() < self.opacity.scalar(hit) {
+            Some(self.base.clone())
+        } else {
+            Some(self.pass_through.clone())
+        }
+    }
+}
+
+/// A surface which isn't there: rays carry straight on, unchanged.
+#[derive(Debug)]
+pub struct PassThrough;
+
+impl material::Material for PassThrough {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        ray::Ray::new(*hit.intersected_surface_normal.get_origin(), *reflected_ray.get_direction())
+    }
+
+    fn colour(&self, start_colour: colour::Colour, _hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        start_colour
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        _hit: &hit::MaterialHit,
+        _towards_light: WorldVec,
+        _towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        start_colour
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::hit_on_plane;
+    use crate::emissive;
+    use crate::lambertian;
+    use crate::sampler;
+
+    #[test]
+    fn test_mix() {
+        sampler::replace_current(sampler::Sampler::new(5));
+        let red: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(1.0, 0.0, 0.0)));
+        let blue: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.0, 0.0, 1.0)));
+        let mix: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(Mix::new(red.clone(), blue, 0.25));
+        let direction = WorldVec::new(0.0, 0.0, -1.0);
+        let (_, hit) = hit_on_plane(mix.clone(), direction);
+
+        // A quarter of hits go to the second material.
+        let chosen_red = (0..4000)
+            .filter(|_| std::rc::Rc::ptr_eq(&material::resolve(hit.clone(), -direction).material, &red))
+            .count();
+        assert!((2800..3200).contains(&chosen_red), "{}", chosen_red);
+
+        // Unresolved, the colour is the blend.
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let colour = mix.colour_between(white, &hit, -direction, -direction);
+        assert_eq!((colour.get_red(), colour.get_blue()), (0.75, 0.25));
+    }
+
+    #[test]
+    fn test_layered() {
+        sampler::replace_current(sampler::Sampler::new(6));
+        let base: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(1.0, 1.0, 1.0)));
+        let layered = Layered::new(base, 1.5, std::rc::Rc::new(texture::Constant::scalar(0.0)))
+            .with_tint(std::rc::Rc::new(texture::Constant::new(colour::Colour::new(1.0, 0.5, 0.5))));
+        let coat = layered.coat.clone();
+        let varnish: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(layered);
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+
+        // Head on, about 4% of hits reflect off the coat; at a grazing angle, most do.
+        let reflections = |direction: WorldVec| {
+            let (from, hit) = hit_on_plane(varnish.clone(), direction);
+            let mut mirrored = 0;
+            for _ in 0..2000 {
+                let hit = material::resolve(hit.clone(), -direction);
+                let towards_light = hit.material.sample_gathering_ray(&from, &hit).get_direction().normalised();
+                if std::rc::Rc::ptr_eq(&hit.material, &coat) {
+                    mirrored += 1;
+                    assert!((towards_light - WorldVec::new(direction.0, direction.1, -direction.2)).length() < 0.05);
+                    // The mirrored light isn't tinted.
+                    let colour = hit.material.colour_between(white.clone(), &hit, towards_light, -direction);
+                    assert_eq!(colour.get_red(), colour.get_green());
+                } else {
+                    // Light through the coat is.
+                    let colour = hit.material.colour_between(white.clone(), &hit, towards_light, -direction);
+                    assert!(colour.get_green() < colour.get_red());
+                }
+            }
+            mirrored
+        };
+        let head_on = reflections(WorldVec::new(0.0, 0.0, -1.0));
+        assert!((40..130).contains(&head_on), "{}", head_on);
+        let grazing = reflections(WorldVec::new(1.0, 0.0, -0.05).normalised());
+        assert!(grazing > 1000, "{}", grazing);
+    }
+
+    #[test]
+    fn test_opacity() {
+        sampler::replace_current(sampler::Sampler::new(7));
+        let light: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(emissive::Emissive::new(colour::Colour::new(1.0, 1.0, 1.0)));
+        let leaf: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(Opacity::new(
+            light,
+            std::rc::Rc::new(texture::Checker::new(
+                std::rc::Rc::new(texture::Constant::scalar(1.0)),
+                std::rc::Rc::new(texture::Constant::scalar(0.0)),
+                2.0,
+            )),
+        ));
+        let direction = WorldVec::new(0.3, 0.0, -1.0).normalised();
+        let (from, mut hit) = hit_on_plane(leaf, direction);
+
+        // Where the mask is clear, rays carry straight on, unchanged.
+        hit.uv = (0.75, 0.25);
+        let resolved = material::resolve(hit.clone(), -direction);
+        let through = resolved.material.sample_gathering_ray(&from, &resolved);
+        assert_eq!(*through.get_direction(), direction);
+        let colour = resolved.material.colour_between(colour::Colour::new(0.2, 0.4, 0.6), &resolved, direction, -direction);
+        assert_eq!(colour.get_blue(), 0.6);
+
+        // Where it's opaque, they meet the base.
+        hit.uv = (0.25, 0.25);
+        let resolved = material::resolve(hit, -direction);
+        assert_ne!(*resolved.material.sample_gathering_ray(&from, &resolved).get_direction(), direction);
+    }
+}
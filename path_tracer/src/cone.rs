@@ -0,0 +1,155 @@
+use crate::aabb;
+use crate::cylinder;
+use crate::hit;
+use crate::material;
+use crate::polynomial;
+use crate::ray;
+use crate::WorldVec;
+
+/// A cone standing upright (along the y axis), with a circular base of `radius` centred on `base`
+/// and its tip `height` above it. Wrap it in an `Instance` to tilt it.
+#[derive(Debug)]
+pub struct Cone {
+    base: WorldVec,
+    radius: f64,
+    height: f64,
+    /// Whether the base is closed.
+    capped: bool,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Cone {
+    pub fn new(
+        base: WorldVec,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Cone {
+        Cone {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+}
+
+impl hit::Hit for Cone {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        // Work relative to the base.
+        let origin = *from.get_origin() - self.base;
+        let direction = *from.get_direction();
+
+        // The side is x^2 + z^2 = k^2 (h - y)^2, where k is the radius per unit of height below the
+        // tip. We have to throw away hits on the mirrored cone above the tip.
+        let k2 = (self.radius / self.height).powi(2);
+        let below_tip = self.height - origin.1;
+        let a = direction.0 * direction.0 + direction.2 * direction.2 - k2 * direction.1 * direction.1;
+        let b = 2.0 * (origin.0 * direction.0 + origin.2 * direction.2 + k2 * below_tip * direction.1);
+        let c = origin.0 * origin.0 + origin.2 * origin.2 - k2 * below_tip * below_tip;
+        let side = polynomial::real_roots(&[c, b, a]).into_iter().find(|&t| {
+            let y = origin.1 + direction.1 * t;
+            from.in_range(t) && (0.0..=self.height).contains(&y)
+        });
+
+        let mut closest = side.map(|t| {
+            let point = origin + direction * t;
+            // The gradient of the surface. At the very tip, this vanishes, so point straight up.
+            let gradient = WorldVec::new(point.0, k2 * (self.height - point.1), point.2);
+            let normal = if gradient.length() < 1e-12 {
+                WorldVec::new(0.0, 1.0, 0.0)
+            } else {
+                gradient.normalised()
+            };
+            (
+                t,
+                normal,
+                (cylinder::angle_around_y(point.0, point.2), point.1 / self.height),
+            )
+        });
+        if self.capped {
+            if let Some(t) = cylinder::intersect_cap(from, origin, direction, 0.0, self.radius) {
+                if closest.as_ref().is_none_or(|(closest_t, _, _)| t < *closest_t) {
+                    let point = origin + direction * t;
+                    closest = Some((
+                        t,
+                        WorldVec::new(0.0, -1.0, 0.0),
+                        cylinder::cap_uv(point, self.radius),
+                    ));
+                }
+            }
+        }
+
+        let (t, normal, uv) = closest?;
+        Some(hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv))
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::new(
+            self.base - WorldVec::new(self.radius, 0.0, self.radius),
+            self.base + WorldVec::new(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_cone() {
+        let material = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0)));
+        let cone = Cone::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 1.0, true, material.clone());
+
+        // Cast a ray along the x axis, halfway up. The side slopes at 45 degrees.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.5, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = cone.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(4.5, 0.5, 0.0)).length() < 1e-12);
+        let expected_normal = WorldVec::new(-1.0, 1.0, 0.0).normalised();
+        assert!((*normal.get_direction() - expected_normal).length() < 1e-12);
+
+        // Straight up into the base.
+        let ray = ray::Ray::new(WorldVec::new(5.25, -3.0, 0.0), WorldVec::new(0.0, 1.0, 0.0));
+        let hit = cone.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(5.25, 0.0, 0.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, -1.0, 0.0));
+
+        // Without the base, the ray goes up through the inside and out through the side.
+        let open = Cone::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 1.0, false, material);
+        let hit = open.hit(&ray).expect("The ray should hit the inside.");
+        assert!((hit.intersected_surface_normal.get_origin().1 - 0.75).abs() < 1e-12);
+
+        // Pass through the mirrored cone above the tip. This should not hit anything.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 1.5, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(cone.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_cone_bounds_and_uv() {
+        let material = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0)));
+        let cone = Cone::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 1.0, true, material);
+        let bounds = cone.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (WorldVec::new(4.0, 0.0, -1.0), WorldVec::new(6.0, 1.0, 1.0)));
+
+        // A quarter of the way around, halfway up the side.
+        let ray = ray::Ray::new(WorldVec::new(5.0, 0.5, 10.0), WorldVec::new(0.0, 0.0, -1.0));
+        let hit = cone.hit(&ray).expect("The ray should hit.");
+        assert!((hit.uv.0 - 0.25).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+
+        // Grazing the side halfway up, where the two roots meet, then just missing it.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.5, 0.5), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = cone.hit(&ray).expect("The ray should graze the side.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(5.0, 0.5, 0.5)).length() < 1e-9);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 1.0, 1.0).normalised()).length() < 1e-9);
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.5, 0.5 + 1e-6), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(cone.hit(&ray).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use crate::aabb;
+use crate::hit;
+use crate::ray;
+
+/// How a `Csg` node combines its two objects.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operation {
+    /// Everything inside either object.
+    Union,
+    /// Everything inside both objects.
+    Intersection,
+    /// Everything inside the first object but not the second.
+    Difference,
+}
+
+impl Operation {
+    fn contains(self, in_left: bool, in_right: bool) -> bool {
+        match self {
+            Operation::Union => in_left || in_right,
+            Operation::Intersection => in_left && in_right,
+            Operation::Difference => in_left && !in_right,
+        }
+    }
+}
+
+/// A solid made by combining two others (constructive solid geometry). Both objects should be
+/// solid, with outward-facing normals. Nodes can be nested to build up more complicated shapes.
+#[derive(Debug)]
+pub struct Csg {
+    operation: Operation,
+    left: std::rc::Rc<dyn hit::Hit>,
+    right: std::rc::Rc<dyn hit::Hit>,
+}
+
+impl Csg {
+    pub fn new(
+        operation: Operation,
+        left: std::rc::Rc<dyn hit::Hit>,
+        right: std::rc::Rc<dyn hit::Hit>,
+    ) -> Csg {
+        Csg {
+            operation,
+            left,
+            right,
+        }
+    }
+
+    pub fn union(left: std::rc::Rc<dyn hit::Hit>, right: std::rc::Rc<dyn hit::Hit>) -> Csg {
+        Csg::new(Operation::Union, left, right)
+    }
+
+    pub fn intersection(left: std::rc::Rc<dyn hit::Hit>, right: std::rc::Rc<dyn hit::Hit>) -> Csg {
+        Csg::new(Operation::Intersection, left, right)
+    }
+
+    /// `left` with `right` cut out of it.
+    pub fn difference(left: std::rc::Rc<dyn hit::Hit>, right: std::rc::Rc<dyn hit::Hit>) -> Csg {
+        Csg::new(Operation::Difference, left, right)
+    }
+}
+
+/// A point where the ray goes into or out of one of the two objects.
+struct Event {
+    t: f64,
+    is_left: bool,
+    entering: bool,
+    /// `None` for the ends of intervals which start before, or never finish after, the ray.
+    crossing: Option<hit::Crossing>,
+}
+
+impl hit::Hit for Csg {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        self.intervals(from)
+            .into_iter()
+            .flat_map(|interval| interval.enter.into_iter().chain(interval.exit))
+            .find(|crossing| from.in_range(crossing.t))
+            .map(|crossing| crossing.hit)
+    }
+
+    fn intervals(&self, from: &ray::Ray) -> Vec<hit::Interval> {
+        // Sweep along the ray through every surface of both objects, keeping track of which we're
+        // inside, and note wherever that takes us into or out of the combined solid.
+        let mut events = Vec::new();
+        for (object, is_left) in [(&self.left, true), (&self.right, false)] {
+            for interval in object.intervals(from) {
+                events.push(Event {
+                    t: interval.enter.as_ref().map_or(f64::NEG_INFINITY, |c| c.t),
+                    is_left,
+                    entering: true,
+                    crossing: interval.enter,
+                });
+                events.push(Event {
+                    t: interval.exit.as_ref().map_or(f64::INFINITY, |c| c.t),
+                    is_left,
+                    entering: false,
+                    crossing: interval.exit,
+                });
+            }
+        }
+        events.sort_by(|a, b| a.t.partial_cmp(&b.t).unwrap_or(std::cmp::Ordering::Equal));
+
+        let (mut in_left, mut in_right) = (false, false);
+        let mut intervals = Vec::new();
+        let mut enter: Option<Option<hit::Crossing>> = None;
+        for event in events {
+            let was_inside = self.operation.contains(in_left, in_right);
+            if event.is_left {
+                in_left = event.entering;
+            } else {
+                in_right = event.entering;
+            }
+            let inside = self.operation.contains(in_left, in_right);
+            if inside == was_inside {
+                continue;
+            }
+            // Going into the combined solid by leaving one of the objects (or the other way
+            // around) means that surface faces the wrong way, so flip its normal.
+            let flip = inside != event.entering;
+            let crossing = event.crossing.map(|crossing| {
+                if flip {
+                    hit::Crossing {
+                        t: crossing.t,
+                        hit: crossing.hit.flipped(),
+                    }
+                } else {
+                    crossing
+                }
+            });
+            if inside {
+                enter = Some(crossing);
+            } else {
+                intervals.push(hit::Interval {
+                    enter: enter.take().flatten(),
+                    exit: crossing,
+                });
+            }
+        }
+        if let Some(enter) = enter {
+            intervals.push(hit::Interval { enter, exit: None });
+        }
+        intervals
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        match self.operation {
+            Operation::Union => {
+                let left = self.left.bounding_box()?;
+                Some(left.union(&self.right.bounding_box()?))
+            }
+            Operation::Intersection => self.left.bounding_box().or_else(|| self.right.bounding_box()),
+            Operation::Difference => self.left.bounding_box(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::cuboid;
+    use crate::hit::Hit;
+    use crate::lambertian;
+    use crate::sphere;
+    use crate::WorldVec;
+
+    /// Two unit spheres along the z axis, overlapping between z = 9.5 and z = 10.5.
+    fn spheres() -> (std::rc::Rc<dyn hit::Hit>, std::rc::Rc<dyn hit::Hit>) {
+        let material = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        (
+            std::rc::Rc::new(sphere::Sphere::new(WorldVec::new(0.0, 0.0, 9.5), 1.0, material.clone())),
+            std::rc::Rc::new(sphere::Sphere::new(WorldVec::new(0.0, 0.0, 10.5), 1.0, material)),
+        )
+    }
+
+    fn hit_point(object: &dyn hit::Hit, ray: &ray::Ray) -> Option<(WorldVec, WorldVec)> {
+        object.hit(ray).map(|hit| {
+            let normal = hit.intersected_surface_normal;
+            (*normal.get_origin(), *normal.get_direction())
+        })
+    }
+
+    #[test]
+    fn test_csg() {
+        let (near, far) = spheres();
+        let along_z = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let backwards = WorldVec::new(0.0, 0.0, -1.0);
+
+        let union = Csg::union(near.clone(), far.clone());
+        assert_eq!(hit_point(&union, &along_z), Some((WorldVec::new(0.0, 0.0, 8.5), backwards)));
+        let intervals = union.intervals(&along_z);
+        assert_eq!(intervals.len(), 1);
+        assert_eq!(intervals[0].exit.as_ref().unwrap().t, 11.5);
+
+        let intersection = Csg::intersection(near.clone(), far.clone());
+        assert_eq!(hit_point(&intersection, &along_z), Some((WorldVec::new(0.0, 0.0, 9.5), backwards)));
+
+        // Cutting the far sphere out of the near one leaves a hollow facing the ray, with its
+        // normal flipped to face out of what's left.
+        let difference = Csg::difference(near.clone(), far.clone());
+        assert_eq!(hit_point(&difference, &along_z), Some((WorldVec::new(0.0, 0.0, 8.5), backwards)));
+        let from_behind = ray::Ray::new(WorldVec::new(0.0, 0.0, 20.0), backwards);
+        assert_eq!(
+            hit_point(&difference, &from_behind),
+            Some((WorldVec::new(0.0, 0.0, 9.5), WorldVec::new(0.0, 0.0, 1.0)))
+        );
+
+        // Cutting the near sphere out of the far one from the inside of the near sphere.
+        let inside = ray::Ray::new(WorldVec::new(0.0, 0.0, 9.0), WorldVec::new(0.0, 0.0, 1.0));
+        let difference = Csg::difference(far, near);
+        assert_eq!(hit_point(&difference, &inside), Some((WorldVec::new(0.0, 0.0, 10.5), backwards)));
+
+        // A ray which misses the intersection, but hits both spheres.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.95, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(intersection.hit(&ray).is_none());
+        assert!(union.hit(&ray).is_some());
+    }
+
+    #[test]
+    fn test_march_intervals() {
+        // Cuboids don't have their own interval query, so they're stepped through with `hit`.
+        let cuboid = cuboid::Cuboid::new(
+            WorldVec::new(-1.0, -1.0, 2.0),
+            WorldVec::new(1.0, 1.0, 4.0),
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        );
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 2.0));
+        let intervals = cuboid.intervals(&ray);
+        assert_eq!(intervals.len(), 1);
+        assert!((intervals[0].enter.as_ref().unwrap().t - 1.0).abs() < 1e-12);
+        assert!((intervals[0].exit.as_ref().unwrap().t - 2.0).abs() < 1e-12);
+
+        let inside = ray::Ray::new(WorldVec::new(0.0, 0.0, 3.0), WorldVec::new(0.0, 0.0, 1.0));
+        let intervals = cuboid.intervals(&inside);
+        assert_eq!(intervals.len(), 1);
+        assert!(intervals[0].enter.is_none());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::WorldVec;
+
+/// An axis-aligned box. Wrap it in an `Instance` to rotate it.
+#[derive(Debug)]
+pub struct Cuboid {
+    bounds: aabb::Aabb,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Cuboid {
+    /// A box between two opposite corners.
+    pub fn new(corner: WorldVec, opposite: WorldVec, material: std::rc::Rc<dyn material::Material>) -> Cuboid {
+        Cuboid {
+            bounds: aabb::Aabb::from_points(&[corner, opposite]),
+            material,
+        }
+    }
+}
+
+fn component(v: WorldVec, axis: usize) -> f64 {
+    match axis {
+        0 => v.0,
+        1 => v.1,
+        _ => v.2,
+    }
+}
+
+fn unit(axis: usize, sign: f64) -> WorldVec {
+    match axis {
+        0 => WorldVec::new(sign, 0.0, 0.0),
+        1 => WorldVec::new(0.0, sign, 0.0),
+        _ => WorldVec::new(0.0, 0.0, sign),
+    }
+}
+
+impl hit::Hit for Cuboid {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        // Intersect the slabs between each pair of faces, keeping track of which face we enter
+        // and leave the box through.
+        let origin = *from.get_origin();
+        let direction = *from.get_direction();
+        let (mut t_enter, mut enter_axis) = (f64::NEG_INFINITY, 0);
+        let (mut t_exit, mut exit_axis) = (f64::INFINITY, 0);
+        for axis in 0..3 {
+            let o = component(origin, axis);
+            let d = component(direction, axis);
+            let (min, max) = (component(self.bounds.min, axis), component(self.bounds.max, axis));
+            if d == 0.0 {
+                if o < min || o > max {
+                    return None;
+                }
+                continue;
+            }
+            let (mut t0, mut t1) = ((min - o) / d, (max - o) / d);
+            if t0 > t1 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            if t0 > t_enter {
+                t_enter = t0;
+                enter_axis = axis;
+            }
+            if t1 < t_exit {
+                t_exit = t1;
+                exit_axis = axis;
+            }
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+        // If the near side is out of range (say, because we start inside the box), we hit it on
+        // the way out. Either way, the normal faces out.
+        let (t, axis, normal) = if from.in_range(t_enter) {
+            let sign = -component(direction, enter_axis).signum();
+            (t_enter, enter_axis, unit(enter_axis, sign))
+        } else if from.in_range(t_exit) {
+            let sign = component(direction, exit_axis).signum();
+            (t_exit, exit_axis, unit(exit_axis, sign))
+        } else {
+            return None;
+        };
+        let intersection_point = from.at(t);
+
+        // Map the face onto the unit square, using the other two axes.
+        let size = self.bounds.max - self.bounds.min;
+        let relative = intersection_point - self.bounds.min;
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        // A box with no thickness along some axis squashes the square into a line, down the
+        // middle of it.
+        let along = |axis: usize| {
+            let extent = component(size, axis);
+            if extent > 0.0 {
+                component(relative, axis) / extent
+            } else {
+                0.5
+            }
+        };
+        let uv = (along(u_axis), along(v_axis));
+        Some(
+            hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv)
+                .with_tangent(unit(u_axis, 1.0)),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_cuboid() {
+        let cuboid = Cuboid::new(
+            WorldVec::new(1.0, 1.0, 1.0),
+            WorldVec::new(-1.0, -1.0, 3.0),
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        );
+        let ray = ray::Ray::new(WorldVec::new(0.5, 0.0, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = cuboid.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(0.5, 0.0, 1.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, 0.0, -1.0));
+        assert_eq!(hit.uv, (0.75, 0.5));
+
+        // From inside, we hit the far side.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 2.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = cuboid.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(0.0, -1.0, 2.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, -1.0, 0.0));
+
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 5.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(cuboid.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_flat_cuboid() {
+        // No thickness along y. Hits on the sides still get UVs.
+        let cuboid = Cuboid::new(
+            WorldVec::new(-1.0, 0.0, 1.0),
+            WorldVec::new(1.0, 0.0, 3.0),
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        );
+        let ray = ray::Ray::new(WorldVec::new(0.5, 0.0, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = cuboid.hit(&ray).expect("The ray should hit.");
+        assert_eq!(hit.uv, (0.75, 0.5));
+        let ray = ray::Ray::new(WorldVec::new(0.5, 5.0, 2.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = cuboid.hit(&ray).expect("The ray should hit.");
+        assert!(!hit.uv.0.is_nan() && !hit.uv.1.is_nan());
+    }
+}
@@ -0,0 +1,181 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::polynomial;
+use crate::ray;
+use crate::WorldVec;
+
+/// A cylinder standing upright (along the y axis) on `base`. Wrap it in an `Instance` to tilt it.
+#[derive(Debug)]
+pub struct Cylinder {
+    base: WorldVec,
+    radius: f64,
+    height: f64,
+    /// Whether the ends are closed.
+    capped: bool,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: WorldVec,
+        radius: f64,
+        height: f64,
+        capped: bool,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Cylinder {
+        Cylinder {
+            base,
+            radius,
+            height,
+            capped,
+            material,
+        }
+    }
+}
+
+/// The u coordinate of a point at angle around the y axis, matching `Sphere`.
+pub(crate) fn angle_around_y(x: f64, z: f64) -> f64 {
+    0.5 + (-z).atan2(x) / (2.0 * std::f64::consts::PI)
+}
+
+/// Where `origin + t * direction` crosses the plane at height `y`, if that's within `radius` of
+/// the y axis and `from`'s range. Used for the caps of cylinders and cones, in their own space.
+pub(crate) fn intersect_cap(
+    from: &ray::Ray,
+    origin: WorldVec,
+    direction: WorldVec,
+    y: f64,
+    radius: f64,
+) -> Option<f64> {
+    if direction.1 == 0.0 {
+        return None;
+    }
+    let t = (y - origin.1) / direction.1;
+    let x = origin.0 + direction.0 * t;
+    let z = origin.2 + direction.2 * t;
+    if from.in_range(t) && x * x + z * z <= radius * radius {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+/// The texture coordinates of a point on a cap, mapping the disk onto the unit square.
+pub(crate) fn cap_uv(point: WorldVec, radius: f64) -> (f64, f64) {
+    (0.5 + 0.5 * point.0 / radius, 0.5 + 0.5 * point.2 / radius)
+}
+
+impl hit::Hit for Cylinder {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        // Work relative to the base.
+        let origin = *from.get_origin() - self.base;
+        let direction = *from.get_direction();
+
+        // Solve (o + td).x^2 + (o + td).z^2 = r^2 for the curved side.
+        let a = direction.0 * direction.0 + direction.2 * direction.2;
+        let b = 2.0 * (origin.0 * direction.0 + origin.2 * direction.2);
+        let c = origin.0 * origin.0 + origin.2 * origin.2 - self.radius * self.radius;
+        let side = if a == 0.0 {
+            None
+        } else {
+            polynomial::quadratic_roots(a, b, c).into_iter().find(|&t| {
+                let y = origin.1 + direction.1 * t;
+                from.in_range(t) && (0.0..=self.height).contains(&y)
+            })
+        };
+
+        let mut closest = side.map(|t| {
+            let point = origin + direction * t;
+            (
+                t,
+                WorldVec::new(point.0, 0.0, point.2) * (1.0 / self.radius),
+                (angle_around_y(point.0, point.2), point.1 / self.height),
+            )
+        });
+        if self.capped {
+            for &(y, normal_y) in &[(0.0, -1.0), (self.height, 1.0)] {
+                if let Some(t) = intersect_cap(from, origin, direction, y, self.radius) {
+                    if closest.as_ref().is_none_or(|(closest_t, _, _)| t < *closest_t) {
+                        let point = origin + direction * t;
+                        closest = Some((t, WorldVec::new(0.0, normal_y, 0.0), cap_uv(point, self.radius)));
+                    }
+                }
+            }
+        }
+
+        let (t, normal, uv) = closest?;
+        Some(hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv))
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::new(
+            self.base - WorldVec::new(self.radius, 0.0, self.radius),
+            self.base + WorldVec::new(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_cylinder() {
+        let material = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0)));
+        let cylinder = Cylinder::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 2.0, true, material.clone());
+
+        // Cast a ray along the x axis, just above the base. This should hit the side.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.5, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = cylinder.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(4.0, 0.5, 0.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(-1.0, 0.0, 0.0));
+        assert!((hit.uv.1 - 0.25).abs() < 1e-12);
+
+        // Straight down onto the top cap.
+        let ray = ray::Ray::new(WorldVec::new(5.5, 10.0, 0.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = cylinder.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(5.5, 2.0, 0.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, 1.0, 0.0));
+
+        // Without caps, the same ray goes in through the open top and hits the inside of the side.
+        let open = Cylinder::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 2.0, false, material);
+        let ray = ray::Ray::new(WorldVec::new(5.5, 10.0, 0.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert!(open.hit(&ray).is_none());
+        let ray = ray::Ray::new(WorldVec::new(5.0, 10.0, 0.0), WorldVec::new(0.5, -4.0, 0.0).normalised());
+        let hit = open.hit(&ray).expect("The ray should hit the inside.");
+        assert!((hit.intersected_surface_normal.get_origin().0 - 6.0).abs() < 1e-12);
+
+        // Cast a ray above the cylinder. This should not hit anything.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 2.5, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(cylinder.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_cylinder_bounds_and_uv() {
+        let material = std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0)));
+        let cylinder = Cylinder::new(WorldVec::new(5.0, 0.0, 0.0), 1.0, 2.0, true, material);
+        let bounds = cylinder.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (WorldVec::new(4.0, 0.0, -1.0), WorldVec::new(6.0, 2.0, 1.0)));
+
+        // A quarter of the way around, halfway up the side.
+        let ray = ray::Ray::new(WorldVec::new(5.0, 1.0, 10.0), WorldVec::new(0.0, 0.0, -1.0));
+        let hit = cylinder.hit(&ray).expect("The ray should hit.");
+        assert!((hit.uv.0 - 0.25).abs() < 1e-12 && (hit.uv.1 - 0.5).abs() < 1e-12);
+        // Halfway out along x on the top cap.
+        let ray = ray::Ray::new(WorldVec::new(5.5, 10.0, 0.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert_eq!(cylinder.hit(&ray).unwrap().uv, (0.75, 0.5));
+
+        // Grazing the side, where the two roots meet, then just missing it.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 1.0, 1.0), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = cylinder.hit(&ray).expect("The ray should graze the side.");
+        assert!((*hit.intersected_surface_normal.get_origin() - WorldVec::new(5.0, 1.0, 1.0)).length() < 1e-9);
+        let ray = ray::Ray::new(WorldVec::new(0.0, 1.0, 1.0 + 1e-6), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(cylinder.hit(&ray).is_none());
+    }
+}
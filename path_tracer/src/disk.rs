@@ -0,0 +1,112 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::plane;
+use crate::ray;
+use crate::WorldVec;
+
+/// A flat, circular disk.
+#[derive(Debug)]
+pub struct Disk {
+    centre: WorldVec,
+    normal: WorldVec,
+    radius: f64,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Disk {
+    pub fn new(
+        centre: WorldVec,
+        normal: WorldVec,
+        radius: f64,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Disk {
+        Disk {
+            centre,
+            normal: normal.normalised(),
+            radius,
+            material,
+        }
+    }
+}
+
+impl hit::Hit for Disk {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let t = plane::intersect_plane(from, self.centre, self.normal)?;
+        let offset = from.at(t) - self.centre;
+        let distance = offset.length();
+        if distance > self.radius {
+            return None;
+        }
+        // Polar coordinates: u is the distance from the centre, v is the angle around it.
+        let (tangent, bitangent) = self.normal.tangent_basis();
+        let angle = offset.dot(bitangent).atan2(offset.dot(tangent));
+        Some(hit::MaterialHit::new(
+            std::rc::Rc::clone(&self.material),
+            from,
+            t,
+            self.normal,
+            (
+                distance / self.radius,
+                0.5 + angle / (2.0 * std::f64::consts::PI),
+            ),
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // How far the disk extends along each axis is the radius times the sine of the angle
+        // between the axis and the normal.
+        let n = self.normal;
+        let extent = WorldVec::new(
+            (1.0 - n.0 * n.0).max(0.0).sqrt(),
+            (1.0 - n.1 * n.1).max(0.0).sqrt(),
+            (1.0 - n.2 * n.2).max(0.0).sqrt(),
+        ) * self.radius;
+        Some(aabb::Aabb::new(self.centre - extent, self.centre + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hit::Hit;
+    use crate::test_helpers::grey;
+
+    #[test]
+    fn test_hit_disk() {
+        let disk = Disk::new(WorldVec::new(0.0, 0.0, 5.0), WorldVec::new(0.0, 0.0, -2.0), 1.0, grey());
+
+        // Halfway out from the centre.
+        let ray = ray::Ray::new(WorldVec::new(0.5, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = disk.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(0.5, 0.0, 5.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, 0.0, -1.0));
+        assert!((hit.uv.0 - 0.5).abs() < 1e-12);
+
+        // v goes around the centre: a quarter turn apart is a quarter of the way along.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.5, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let quarter = disk.hit(&ray).expect("The ray should hit.").uv.1 - hit.uv.1;
+        assert!((quarter.rem_euclid(1.0) - 0.25).abs() < 1e-12 || (quarter.rem_euclid(1.0) - 0.75).abs() < 1e-12);
+
+        // Just outside the rim, and parallel to the disk.
+        let ray = ray::Ray::new(WorldVec::new(1.01, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(disk.hit(&ray).is_none());
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 4.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(disk.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_disk_bounds() {
+        // Facing along z, the disk is flat in z. Tilted 45 degrees about y, it's squashed in x.
+        let flat = Disk::new(WorldVec::new(1.0, 2.0, 3.0), WorldVec::new(0.0, 0.0, 1.0), 2.0, grey());
+        let bounds = flat.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (WorldVec::new(-1.0, 0.0, 3.0), WorldVec::new(3.0, 4.0, 3.0)));
+
+        let tilted = Disk::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(1.0, 0.0, 1.0), 2.0, grey());
+        let bounds = tilted.bounding_box().unwrap();
+        let half = 2.0 * std::f64::consts::FRAC_1_SQRT_2;
+        assert!((bounds.max - WorldVec::new(half, 2.0, half)).length() < 1e-12);
+        assert!((bounds.min + WorldVec::new(half, 2.0, half)).length() < 1e-12);
+    }
+}
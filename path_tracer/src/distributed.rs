@@ -0,0 +1,513 @@
+//! Rendering an image across several processes. A coordinator splits the image into tiles and
+//! hands them out over TCP to workers, which render each tile with `Camera::render_region_film` and
+//! send back the accumulated samples. If a worker dies, the tiles it was working on are given to
+//! the remaining workers.
+//!
+//! The scene is sent to each worker as an opaque blob of bytes, which the worker turns into a
+//! camera however it likes. `scene_description::parse` is the obvious choice.
+//!
+//! Every tile is rendered with the same seed, so the assembled image is exactly what a single
+//! process would have rendered.
+
+use crate::camera;
+use crate::checkpoint;
+use crate::film;
+use crate::hit::Hit;
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+use std::time::Duration;
+
+const SCENE: u8 = 1;
+const JOB: u8 = 2;
+const RESULT: u8 = 3;
+const FAILED: u8 = 4;
+
+/// The longest scene or error message we'll accept from the other end, so that a bad length can't
+/// make us allocate everything.
+const MAX_MESSAGE_LENGTH: u64 = 1 << 28;
+
+/// The widest or tallest image a worker will render, and the most pixels, so that a bad job can't
+/// make it allocate everything either.
+const MAX_DIMENSION: usize = 1 << 16;
+const MAX_PIXELS: usize = 1 << 26;
+
+/// The settings for the whole image, which are sent along with each tile.
+#[derive(Debug, Clone, Copy)]
+pub struct RenderSettings {
+    pub x_size: usize,
+    pub y_size: usize,
+    pub fov: f64,
+    pub bounces: usize,
+    pub samples_per_pixel: usize,
+    pub seed: u64,
+}
+
+#[derive(Debug)]
+pub enum DistributedError {
+    /// Every worker died (or couldn't be reached) before all the tiles were rendered.
+    NoWorkers {
+        remaining_tiles: usize,
+        /// Why each worker was given up on.
+        errors: Vec<(SocketAddr, String)>,
+    },
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Tile {
+    top_left: (usize, usize),
+    size: (usize, usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    pub workers: Vec<SocketAddr>,
+    /// The largest size of each tile. Tiles on the right and bottom edges may be smaller.
+    pub tile_size: (usize, usize),
+    /// How long to wait on a worker (to connect, or to render a tile) before assuming it has died.
+    /// If this is `None`, we wait forever.
+    pub timeout: Option<Duration>,
+}
+
+/// The tiles which still need to be handed out, and the number of tiles which haven't been
+/// finished yet (including those being worked on).
+struct Queue {
+    pending: std::collections::VecDeque<Tile>,
+    unfinished: usize,
+}
+
+enum Event {
+    Finished(Tile, film::Film),
+    Died(SocketAddr, String),
+}
+
+impl Coordinator {
+    pub fn new(workers: Vec<SocketAddr>) -> Coordinator {
+        Coordinator {
+            workers,
+            tile_size: (64, 64),
+            timeout: None,
+        }
+    }
+
+    /// Render `scene` across the workers, returning the assembled film.
+    pub fn render(
+        &self,
+        scene: &[u8],
+        settings: &RenderSettings,
+    ) -> Result<film::Film, DistributedError> {
+        let mut pending = std::collections::VecDeque::new();
+        for j in (0..settings.y_size).step_by(self.tile_size.1.max(1)) {
+            for i in (0..settings.x_size).step_by(self.tile_size.0.max(1)) {
+                pending.push_back(Tile {
+                    top_left: (i, j),
+                    size: (
+                        self.tile_size.0.min(settings.x_size - i),
+                        self.tile_size.1.min(settings.y_size - j),
+                    ),
+                });
+            }
+        }
+        let mut remaining_tiles = pending.len();
+        let queue = Arc::new((
+            Mutex::new(Queue {
+                unfinished: pending.len(),
+                pending,
+            }),
+            Condvar::new(),
+        ));
+        let scene = Arc::new(scene.to_vec());
+        let (events, received) = mpsc::channel();
+        let threads: Vec<_> = self
+            .workers
+            .iter()
+            .map(|&address| {
+                let queue = queue.clone();
+                let scene = scene.clone();
+                let events = events.clone();
+                let settings = *settings;
+                let timeout = self.timeout;
+                std::thread::spawn(move || {
+                    drive_worker(address, &scene, &settings, timeout, &queue, &events)
+                })
+            })
+            .collect();
+        drop(events);
+
+        let mut result = film::Film::new(settings.x_size, settings.y_size);
+        let mut errors = Vec::new();
+        let mut live_workers = self.workers.len();
+        while remaining_tiles > 0 && live_workers > 0 {
+            match received.recv() {
+                Ok(Event::Finished(tile, tile_film)) => {
+                    result.merge(&tile_film, tile.top_left);
+                    remaining_tiles -= 1;
+                }
+                Ok(Event::Died(address, error)) => {
+                    errors.push((address, error));
+                    live_workers -= 1;
+                }
+                Err(_) => break,
+            }
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        if remaining_tiles > 0 {
+            return Err(DistributedError::NoWorkers {
+                remaining_tiles,
+                errors,
+            });
+        }
+        Ok(result)
+    }
+}
+
+/// Feed tiles to a single worker until there are none left, or until the worker dies.
+fn drive_worker(
+    address: SocketAddr,
+    scene: &[u8],
+    settings: &RenderSettings,
+    timeout: Option<Duration>,
+    queue: &(Mutex<Queue>, Condvar),
+    events: &mpsc::Sender<Event>,
+) {
+    let (queue, tile_available) = queue;
+    let mut stream = match connect(address, scene, timeout) {
+        Ok(stream) => stream,
+        Err(error) => {
+            // If every worker has died, no-one will be waiting for this, so ignore failures.
+            let _ = events.send(Event::Died(address, error.to_string()));
+            return;
+        }
+    };
+    loop {
+        let tile = {
+            let mut queue = queue.lock().unwrap();
+            loop {
+                if queue.unfinished == 0 {
+                    return;
+                }
+                if let Some(tile) = queue.pending.pop_front() {
+                    break tile;
+                }
+                queue = tile_available.wait(queue).unwrap();
+            }
+        };
+        match render_tile(&mut stream, settings, &tile) {
+            Ok(tile_film) => {
+                let mut queue = queue.lock().unwrap();
+                queue.unfinished -= 1;
+                tile_available.notify_all();
+                let _ = events.send(Event::Finished(tile, tile_film));
+            }
+            Err(error) => {
+                // Give the tile to someone else.
+                let mut queue = queue.lock().unwrap();
+                queue.pending.push_back(tile);
+                tile_available.notify_all();
+                let _ = events.send(Event::Died(address, error.to_string()));
+                return;
+            }
+        }
+    }
+}
+
+fn connect(
+    address: SocketAddr,
+    scene: &[u8],
+    timeout: Option<Duration>,
+) -> std::io::Result<TcpStream> {
+    let mut stream = match timeout {
+        Some(timeout) => TcpStream::connect_timeout(&address, timeout)?,
+        None => TcpStream::connect(address)?,
+    };
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+    stream.write_all(&[SCENE])?;
+    write_bytes(&mut stream, scene)?;
+    Ok(stream)
+}
+
+fn render_tile(
+    stream: &mut TcpStream,
+    settings: &RenderSettings,
+    tile: &Tile,
+) -> std::io::Result<film::Film> {
+    let mut message = vec![JOB];
+    for value in &[
+        tile.top_left.0,
+        tile.top_left.1,
+        tile.size.0,
+        tile.size.1,
+        settings.x_size,
+        settings.y_size,
+        settings.bounces,
+        settings.samples_per_pixel,
+    ] {
+        message.extend_from_slice(&(*value as u64).to_le_bytes());
+    }
+    message.extend_from_slice(&settings.fov.to_le_bytes());
+    message.extend_from_slice(&settings.seed.to_le_bytes());
+    stream.write_all(&message)?;
+
+    let mut reader = std::io::BufReader::new(stream);
+    match read_tag(&mut reader)? {
+        RESULT => {
+            let tile_film = checkpoint::Checkpoint::read_from(&mut reader)?.film;
+            if (tile_film.x_len(), tile_film.y_len()) != tile.size {
+                return Err(invalid_data("the worker returned a tile of the wrong size"));
+            }
+            Ok(tile_film)
+        }
+        FAILED => Err(std::io::Error::other(read_string(&mut reader)?)),
+        _ => Err(invalid_data("unexpected message from worker")),
+    }
+}
+
+/// Accept coordinators on `listener` and render tiles for them, one coordinator at a time. `build`
+/// turns the scene sent by the coordinator into a camera. This only returns if accepting a
+/// connection fails, or something goes wrong with a coordinator; call it again to carry on serving.
+pub fn serve<T, F>(listener: &TcpListener, build: &F) -> std::io::Result<()>
+where
+    T: Hit,
+    F: Fn(&[u8]) -> Result<camera::Camera<T>, String>,
+{
+    for stream in listener.incoming() {
+        serve_coordinator(stream?, build)?;
+    }
+    Ok(())
+}
+
+/// Render tiles for a single coordinator until it disconnects.
+pub fn serve_coordinator<T, F>(stream: TcpStream, build: &F) -> std::io::Result<()>
+where
+    T: Hit,
+    F: Fn(&[u8]) -> Result<camera::Camera<T>, String>,
+{
+    let mut reader = std::io::BufReader::new(&stream);
+    let mut writer = std::io::BufWriter::new(&stream);
+    if read_tag(&mut reader)? != SCENE {
+        return Err(invalid_data("expected a scene"));
+    }
+    let scene = read_bytes(&mut reader)?;
+    let mut camera = match build(&scene) {
+        Ok(camera) => camera,
+        Err(error) => {
+            writer.write_all(&[FAILED])?;
+            write_bytes(&mut writer, error.as_bytes())?;
+            return writer.flush();
+        }
+    };
+    loop {
+        let mut tag = [0];
+        if reader.read(&mut tag)? == 0 {
+            // The coordinator has finished with us.
+            return Ok(());
+        }
+        if tag[0] != JOB {
+            return Err(invalid_data("expected a job"));
+        }
+        let mut values = [0; 8];
+        for value in values.iter_mut() {
+            *value = checkpoint::read_u64(&mut reader)? as usize;
+        }
+        let [left, top, width, height, x_size, y_size, bounces, samples_per_pixel] = values;
+        let fov = checkpoint::read_f64(&mut reader)?;
+        camera.set_seed(checkpoint::read_u64(&mut reader)?);
+
+        let rendered = check_job_size((width, height), (x_size, y_size)).and_then(|()| {
+            camera
+                .render_region_film(
+                    (left, top),
+                    (width, height),
+                    x_size,
+                    y_size,
+                    fov,
+                    bounces,
+                    samples_per_pixel,
+                )
+                .map_err(|error| format!("{:?}", error))
+        });
+        match rendered {
+            Ok(film) => {
+                writer.write_all(&[RESULT])?;
+                checkpoint::Checkpoint { film, fov, bounces }.write_to(&mut writer)?;
+            }
+            Err(error) => {
+                writer.write_all(&[FAILED])?;
+                write_bytes(&mut writer, error.as_bytes())?;
+                writer.flush()?;
+            }
+        }
+    }
+}
+
+/// Checks that neither the tile nor the whole image of a job is too big to render.
+fn check_job_size(tile: (usize, usize), image: (usize, usize)) -> Result<(), String> {
+    for &(x, y) in &[tile, image] {
+        if x > MAX_DIMENSION || y > MAX_DIMENSION || x * y > MAX_PIXELS {
+            return Err(format!("{}x{} is too big to render", x, y));
+        }
+    }
+    Ok(())
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn read_tag<R: Read>(reader: &mut R) -> std::io::Result<u8> {
+    let mut tag = [0];
+    reader.read_exact(&mut tag)?;
+    Ok(tag[0])
+}
+
+fn write_bytes<W: Write>(writer: &mut W, bytes: &[u8]) -> std::io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(reader: &mut R) -> std::io::Result<Vec<u8>> {
+    let len = checkpoint::read_u64(reader)?;
+    if len > MAX_MESSAGE_LENGTH {
+        return Err(invalid_data("message too long"));
+    }
+    let mut bytes = vec![0; len as usize];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(reader: &mut R) -> std::io::Result<String> {
+    String::from_utf8(read_bytes(reader)?).map_err(|_| invalid_data("invalid UTF-8"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene_description;
+
+    const SCENE_DESCRIPTION: &str = r#"
+        camera origin 0 0 -3 up 0 1 0 looking_at 0 0 0
+        material grey lambertian 0.5 0.5 0.5
+        sphere 0 0 0 1 grey
+    "#;
+
+    /// Start a worker which serves a single coordinator, returning its address.
+    fn spawn_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_coordinator(stream, &|scene: &[u8]| {
+                scene_description::parse(std::str::from_utf8(scene).unwrap())
+                    .map_err(|error| error.to_string())
+            })
+            .unwrap();
+        });
+        address
+    }
+
+    /// Start a "worker" which hangs up as soon as it has been sent the scene.
+    fn spawn_dying_worker() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = std::io::BufReader::new(&stream);
+            read_tag(&mut reader).unwrap();
+            read_bytes(&mut reader).unwrap();
+        });
+        address
+    }
+
+    #[test]
+    fn test_distributed_matches_local() {
+        let settings = RenderSettings {
+            x_size: 24,
+            y_size: 16,
+            fov: 60.0,
+            bounces: 5,
+            samples_per_pixel: 3,
+            seed: 5,
+        };
+        let mut coordinator =
+            Coordinator::new(vec![spawn_worker(), spawn_dying_worker(), spawn_worker()]);
+        coordinator.tile_size = (10, 10);
+        coordinator.timeout = Some(Duration::from_secs(30));
+        let film = coordinator
+            .render(SCENE_DESCRIPTION.as_bytes(), &settings)
+            .unwrap();
+
+        let mut camera = scene_description::parse(SCENE_DESCRIPTION).unwrap();
+        camera.set_seed(settings.seed);
+        let local = camera
+            .render_region_film((0, 0), (24, 16), 24, 16, 60.0, 5, 3)
+            .unwrap();
+        assert_eq!(film.pixels(), local.pixels());
+    }
+
+    #[test]
+    fn test_read_bytes() {
+        let mut message = 3u64.to_le_bytes().to_vec();
+        message.extend_from_slice(b"abc");
+        assert_eq!(read_bytes(&mut message.as_slice()).unwrap(), b"abc");
+
+        // Nothing is allocated for lengths which are too long, even if the bytes never arrive.
+        let message = u64::MAX.to_le_bytes();
+        let error = read_bytes(&mut &message[..]).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_rejects_bad_jobs() {
+        let mut stream = connect(spawn_worker(), SCENE_DESCRIPTION.as_bytes(), None).unwrap();
+        let settings = |x_size: usize, y_size: usize| RenderSettings {
+            x_size,
+            y_size,
+            fov: 60.0,
+            bounces: 5,
+            samples_per_pixel: 1,
+            seed: 5,
+        };
+        let tile = |top_left: (usize, usize), size: (usize, usize)| Tile { top_left, size };
+
+        // Too big to render, as a tile or as the whole image, and a region whose far corner
+        // overflows. The worker refuses each of them but carries on.
+        let bad_jobs = [
+            (settings(1 << 20, 1 << 20), tile((0, 0), (1 << 20, 1 << 20))),
+            (settings(1 << 20, 1 << 20), tile((0, 0), (8, 8))),
+            (settings(8, 8), tile((usize::MAX, 0), (8, 8))),
+        ];
+        for (settings, tile) in bad_jobs.iter() {
+            let error = render_tile(&mut stream, settings, tile).unwrap_err();
+            assert_eq!(error.kind(), std::io::ErrorKind::Other, "{}", error);
+        }
+        let film = render_tile(&mut stream, &settings(8, 8), &tile((0, 0), (8, 8))).unwrap();
+        assert_eq!((film.x_len(), film.y_len()), (8, 8));
+    }
+
+    #[test]
+    fn test_no_workers() {
+        let settings = RenderSettings {
+            x_size: 8,
+            y_size: 8,
+            fov: 60.0,
+            bounces: 5,
+            samples_per_pixel: 1,
+            seed: 5,
+        };
+        let coordinator = Coordinator::new(vec![spawn_dying_worker()]);
+        match coordinator.render(SCENE_DESCRIPTION.as_bytes(), &settings) {
+            Err(DistributedError::NoWorkers {
+                remaining_tiles,
+                errors,
+            }) => {
+                assert_eq!(remaining_tiles, 1);
+                assert_eq!(errors.len(), 1);
+            }
+            Ok(_) => panic!("There were no workers to render the image."),
+        }
+    }
+}
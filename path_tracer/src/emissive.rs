@@ -0,0 +1,38 @@
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::texture;
+
+/// A material which gives off light of its own, such as an area light. Whatever light arrives at
+/// the surface is ignored.
+#[derive(Debug)]
+pub struct Emissive {
+    colour: std::rc::Rc<dyn texture::Texture>,
+}
+
+impl Emissive {
+    pub fn new(colour: colour::Colour) -> Emissive {
+        Emissive::textured(std::rc::Rc::new(texture::Constant::new(colour)))
+    }
+
+    /// A light whose colour varies across its surface, like a screen.
+    pub fn textured(colour: std::rc::Rc<dyn texture::Texture>) -> Emissive {
+        Emissive { colour }
+    }
+}
+
+impl material::Material for Emissive {
+    fn sample_gathering_ray(&self, _reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        // Light sources don't gather light, so it doesn't matter where this goes.
+        hit.intersected_surface_normal
+    }
+
+    fn colour(&self, _start_colour: colour::Colour, hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        self.colour.colour(hit)
+    }
+
+    fn emitted(&self, hit: &hit::MaterialHit) -> Option<colour::Colour> {
+        Some(self.colour.colour(hit))
+    }
+}
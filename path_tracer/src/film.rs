@@ -0,0 +1,247 @@
+use crate::colour;
+use crate::image;
+use crate::sampler;
+use crate::vec3;
+
+/// The running totals for a single pixel. We keep sums rather than averages so that pixels (and
+/// whole films) can be merged by simply adding them together.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct FilmPixel {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+    /// Sum of the squared luminance of each sample. Used to estimate the variance of the pixel.
+    pub luminance_squared: f64,
+    pub samples: usize,
+    /// The state of the random number generator used to trace this pixel's next sample.
+    pub sampler: sampler::Sampler,
+}
+
+impl FilmPixel {
+    pub fn add_sample(&mut self, colour: &colour::Colour) {
+        self.red += colour.get_red();
+        self.green += colour.get_green();
+        self.blue += colour.get_blue();
+        self.luminance_squared += colour.luminance().powi(2);
+        self.samples += 1;
+    }
+
+    /// The average colour of all samples taken so far, or black if there are none.
+    pub fn mean(&self) -> (f64, f64, f64) {
+        if self.samples == 0 {
+            return (0.0, 0.0, 0.0);
+        }
+        let n = self.samples as f64;
+        (self.red / n, self.green / n, self.blue / n)
+    }
+
+    /// The unbiased sample variance of the luminance of this pixel's samples.
+    pub fn luminance_variance(&self) -> f64 {
+        if self.samples < 2 {
+            return f64::INFINITY;
+        }
+        let n = self.samples as f64;
+        let mean = colour::luminance(self.red, self.green, self.blue) / n;
+        ((self.luminance_squared - n * mean * mean) / (n - 1.0)).max(0.0)
+    }
+
+    /// The standard error of the pixel's mean luminance. This is our estimate of how far the pixel
+    /// is from its converged value.
+    pub fn standard_error(&self) -> f64 {
+        (self.luminance_variance() / self.samples as f64).sqrt()
+    }
+}
+
+/// A floating point accumulation buffer. Samples are added to the film as they're traced, and the
+/// film is resolved into an `Image` once we're done.
+#[derive(Debug, Clone)]
+pub struct Film {
+    pixels: Vec<FilmPixel>,
+    x_size: usize,
+    y_size: usize,
+}
+
+impl Film {
+    pub fn new(x_size: usize, y_size: usize) -> Film {
+        Film {
+            pixels: vec![FilmPixel::default(); x_size * y_size],
+            x_size,
+            y_size,
+        }
+    }
+
+    /// Create a film for the region of an image starting at `origin`, with each pixel's sampler
+    /// seeded from `seed` and the pixel's position in the full image.
+    pub fn seeded(x_size: usize, y_size: usize, seed: u64, origin: (usize, usize)) -> Film {
+        let mut film = Film::new(x_size, y_size);
+        for i in 0..x_size {
+            for j in 0..y_size {
+                film[(i, j)].sampler =
+                    sampler::Sampler::for_pixel(seed, (origin.0 + i, origin.1 + j));
+            }
+        }
+        film
+    }
+
+    pub fn x_len(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_len(&self) -> usize {
+        self.y_size
+    }
+
+    pub fn pixels(&self) -> &[FilmPixel] {
+        &self.pixels
+    }
+
+    /// Add the samples in `tile` to the pixels of this film, with the tile's top left corner at
+    /// `offset`. Pixels are weighted by their sample counts, so merging tiles which each hold some
+    /// of the samples for the same pixels gives the same result as having taken all the samples
+    /// in one film. Any part of the tile outside of this film is ignored.
+    pub fn merge(&mut self, tile: &Film, offset: (usize, usize)) {
+        let x_end = offset.0.saturating_add(tile.x_size).min(self.x_size);
+        let y_end = offset.1.saturating_add(tile.y_size).min(self.y_size);
+        for y in offset.1..y_end {
+            for x in offset.0..x_end {
+                let from = tile[(x - offset.0, y - offset.1)];
+                let to = &mut self[(x, y)];
+                if to.samples == 0 {
+                    // Carry on from the tile's sampler, so that the merged film can be resumed.
+                    to.sampler = from.sampler;
+                }
+                to.red += from.red;
+                to.green += from.green;
+                to.blue += from.blue;
+                to.luminance_squared += from.luminance_squared;
+                to.samples += from.samples;
+            }
+        }
+    }
+
+    /// The standard error of each pixel, averaged over the whole film. This is infinite until every
+    /// pixel has at least two samples.
+    pub fn mean_standard_error(&self) -> f64 {
+        if self.pixels.is_empty() {
+            return 0.0;
+        }
+        self.pixels.iter().map(|p| p.standard_error()).sum::<f64>() / self.pixels.len() as f64
+    }
+
+    /// Average the samples in each pixel to produce an image.
+    pub fn to_image(&self) -> image::Image {
+        let mut result = image::Image::new(self.x_size, self.y_size);
+        for i in 0..self.x_size {
+            for j in 0..self.y_size {
+                // Samples may be brighter than 1 (see `Colour::unclamped`), so this is where they
+                // are clamped.
+                let pixel = self[(i, j)].mean();
+                let channel = |value: f64| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+                result[(i, j)] = vec3::Vec3::new(channel(pixel.0), channel(pixel.1), channel(pixel.2));
+            }
+        }
+        result
+    }
+
+    /// Produce an image showing how many samples each pixel received. Blue pixels received the
+    /// fewest samples, and red pixels received the most.
+    pub fn sample_count_heatmap(&self) -> image::Image {
+        let min = self.pixels.iter().map(|p| p.samples).min().unwrap_or(0);
+        let max = self.pixels.iter().map(|p| p.samples).max().unwrap_or(0);
+        let mut result = image::Image::new(self.x_size, self.y_size);
+        for i in 0..self.x_size {
+            for j in 0..self.y_size {
+                let t = if max == min {
+                    0.0
+                } else {
+                    (self[(i, j)].samples - min) as f64 / (max - min) as f64
+                };
+                result[(i, j)] = heatmap_colour(t);
+            }
+        }
+        result
+    }
+}
+
+/// Map some t in [0, 1] onto a blue -> cyan -> green -> yellow -> red ramp.
+fn heatmap_colour(t: f64) -> vec3::Vec3<u8> {
+    const STOPS: [(f64, f64, f64); 5] = [
+        (0.0, 0.0, 1.0),
+        (0.0, 1.0, 1.0),
+        (0.0, 1.0, 0.0),
+        (1.0, 1.0, 0.0),
+        (1.0, 0.0, 0.0),
+    ];
+    let scaled = t.clamp(0.0, 1.0) * (STOPS.len() - 1) as f64;
+    let index = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let fraction = scaled - index as f64;
+    let (from, to) = (STOPS[index], STOPS[index + 1]);
+    let lerp = |a: f64, b: f64| ((a + (b - a) * fraction) * 255.0).round() as u8;
+    vec3::Vec3::new(lerp(from.0, to.0), lerp(from.1, to.1), lerp(from.2, to.2))
+}
+
+impl std::ops::Index<(usize, usize)> for Film {
+    type Output = FilmPixel;
+    fn index(&self, index: (usize, usize)) -> &Self::Output {
+        &self.pixels[index.0 + index.1 * self.x_size]
+    }
+}
+
+impl std::ops::IndexMut<(usize, usize)> for Film {
+    fn index_mut(&mut self, index: (usize, usize)) -> &mut Self::Output {
+        &mut self.pixels[index.0 + index.1 * self.x_size]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mean_and_variance() {
+        let mut pixel = FilmPixel::default();
+        pixel.add_sample(&colour::Colour::new(1.0, 1.0, 1.0));
+        pixel.add_sample(&colour::Colour::new(0.0, 0.0, 0.0));
+        assert_eq!(pixel.mean(), (0.5, 0.5, 0.5));
+        assert!((pixel.luminance_variance() - 0.5).abs() < 1e-12);
+
+        // A pixel which always sees the same colour has no error.
+        let mut pixel = FilmPixel::default();
+        for _ in 0..4 {
+            pixel.add_sample(&colour::Colour::new(0.25, 0.5, 1.0));
+        }
+        assert!(pixel.standard_error() < 1e-12);
+    }
+
+    #[test]
+    fn test_merge() {
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let black = colour::Colour::new(0.0, 0.0, 0.0);
+
+        // One sample of white and three of black should average to a quarter.
+        let mut film = Film::new(3, 3);
+        film[(1, 1)].add_sample(&white);
+        let mut tile = Film::new(2, 2);
+        for _ in 0..3 {
+            tile[(0, 0)].add_sample(&black);
+        }
+        film.merge(&tile, (1, 1));
+        assert_eq!(film[(1, 1)].mean(), (0.25, 0.25, 0.25));
+        assert_eq!(film[(1, 1)].samples, 4);
+
+        // Merging into an empty film is a copy.
+        let mut empty = Film::new(3, 3);
+        empty.merge(&film, (0, 0));
+        assert_eq!(empty.pixels(), film.pixels());
+    }
+
+    #[test]
+    fn test_heatmap() {
+        let mut film = Film::new(2, 1);
+        film[(0, 0)].samples = 4;
+        film[(1, 0)].samples = 64;
+        let heatmap = film.sample_count_heatmap();
+        assert_eq!(heatmap[(0, 0)], vec3::Vec3::new(0, 0, 255));
+        assert_eq!(heatmap[(1, 0)], vec3::Vec3::new(255, 0, 0));
+    }
+}
@@ -0,0 +1,341 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::polynomial;
+use crate::raster;
+use crate::ray;
+use crate::WorldVec;
+
+/// Terrain, given by a grid of heights. The grid is stretched over the rectangle from `corner` to
+/// `corner + size` in x and z, and heights from 0 to 1 are mapped to `corner.1` to
+/// `corner.1 + size.1`. Between grid points, the surface is bilinearly interpolated, and normals
+/// are interpolated between the grid points so that the terrain looks smooth.
+#[derive(Debug)]
+pub struct Heightfield {
+    /// Heights in world space, one row (along x) after another.
+    heights: Vec<f64>,
+    /// Smooth normals at each grid point.
+    normals: Vec<WorldVec>,
+    x_len: usize,
+    z_len: usize,
+    corner: WorldVec,
+    /// The size of a single cell along x and z.
+    cell_size: (f64, f64),
+    bounds: aabb::Aabb,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Heightfield {
+    /// Create a heightfield from `x_len * z_len` heights, one row along x after another. Panics if
+    /// there are fewer than two points along either side, or the heights are the wrong length.
+    pub fn new(
+        heights: &[f64],
+        x_len: usize,
+        z_len: usize,
+        corner: WorldVec,
+        size: WorldVec,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Heightfield {
+        assert!(x_len >= 2 && z_len >= 2, "A heightfield needs at least 2x2 points.");
+        assert_eq!(heights.len(), x_len * z_len, "Wrong number of heights for the grid size.");
+        let heights: Vec<f64> = heights.iter().map(|h| corner.1 + h * size.1).collect();
+        let cell_size = (size.0 / (x_len - 1) as f64, size.2 / (z_len - 1) as f64);
+
+        // Central differences (or one-sided, on the edges) give the slope at each point.
+        let height = |i: usize, j: usize| heights[j * x_len + i];
+        let mut normals = Vec::with_capacity(heights.len());
+        for j in 0..z_len {
+            for i in 0..x_len {
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(x_len - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(z_len - 1));
+                let dx = (height(right, j) - height(left, j)) / ((right - left) as f64 * cell_size.0);
+                let dz = (height(i, front) - height(i, back)) / ((front - back) as f64 * cell_size.1);
+                normals.push(WorldVec::new(-dx, 1.0, -dz).normalised());
+            }
+        }
+
+        let lowest = heights.iter().cloned().fold(f64::INFINITY, f64::min);
+        let highest = heights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let bounds = aabb::Aabb::new(
+            WorldVec::new(corner.0, lowest, corner.2),
+            WorldVec::new(corner.0 + size.0, highest, corner.2 + size.2),
+        );
+        Heightfield {
+            heights,
+            normals,
+            x_len,
+            z_len,
+            corner,
+            cell_size,
+            bounds,
+            material,
+        }
+    }
+
+    /// Create a heightfield from a greyscale image, with black being the lowest and white the
+    /// highest. The top of the image is at the lowest z. Colour images use their luminance. Images
+    /// smaller than 2x2 pixels are rejected as `InvalidData`.
+    pub fn from_raster(
+        raster: &raster::Raster,
+        corner: WorldVec,
+        size: WorldVec,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> std::io::Result<Heightfield> {
+        if raster.x_len() < 2 || raster.y_len() < 2 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "A heightfield needs at least 2x2 pixels",
+            ));
+        }
+        let mut heights = Vec::with_capacity(raster.x_len() * raster.y_len());
+        for y in 0..raster.y_len() {
+            for x in 0..raster.x_len() {
+                let [r, g, b] = raster[(x, y)];
+                heights.push(crate::colour::luminance(r, g, b));
+            }
+        }
+        Ok(Heightfield::new(&heights, raster.x_len(), raster.y_len(), corner, size, material))
+    }
+
+    /// Load a heightfield from a PNG or PGM file. See `from_raster`.
+    pub fn load(
+        path: impl AsRef<std::path::Path>,
+        corner: WorldVec,
+        size: WorldVec,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> std::io::Result<Heightfield> {
+        Heightfield::from_raster(&raster::Raster::load(path)?, corner, size, material)
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.x_len + i]
+    }
+
+    /// Intersect the bilinear patch over cell `(i, j)` between `t_start` and `t_end`. Points are
+    /// given in grid space, where each cell is a unit square; this doesn't change `t`.
+    fn intersect_cell(
+        &self,
+        (i, j): (usize, usize),
+        origin: WorldVec,
+        direction: WorldVec,
+        t_start: f64,
+        t_end: f64,
+    ) -> Option<f64> {
+        let (h00, h10) = (self.height(i, j), self.height(i + 1, j));
+        let (h01, h11) = (self.height(i, j + 1), self.height(i + 1, j + 1));
+
+        // Skip the cell if the ray is above or below all of it.
+        let (y_start, y_end) = (origin.1 + direction.1 * t_start, origin.1 + direction.1 * t_end);
+        let lowest = h00.min(h10).min(h01).min(h11);
+        let highest = h00.max(h10).max(h01).max(h11);
+        if y_start.min(y_end) > highest || y_start.max(y_end) < lowest {
+            return None;
+        }
+
+        // The surface is h00 + a u + b v + c u v, with u and v the position within the cell.
+        // Substituting in the ray and subtracting from its height gives a quadratic in t.
+        let (a, b, c) = (h10 - h00, h01 - h00, h00 - h10 - h01 + h11);
+        let (u0, v0) = (origin.0 - i as f64, origin.2 - j as f64);
+        let (du, dv) = (direction.0, direction.2);
+        let coefficients = [
+            origin.1 - h00 - a * u0 - b * v0 - c * u0 * v0,
+            direction.1 - a * du - b * dv - c * (u0 * dv + v0 * du),
+            -c * du * dv,
+        ];
+        let tolerance = 1e-9 * (1.0 + t_end.abs());
+        polynomial::real_roots(&coefficients)
+            .into_iter()
+            .find(|&t| t >= t_start - tolerance && t <= t_end + tolerance)
+    }
+
+    /// Turn a hit `t` along `from` (at `grid_point`, in grid space) into a `MaterialHit`.
+    fn material_hit(&self, from: &ray::Ray, t: f64, grid_point: WorldVec) -> hit::MaterialHit {
+        let i = (grid_point.0.floor().max(0.0) as usize).min(self.x_len - 2);
+        let j = (grid_point.2.floor().max(0.0) as usize).min(self.z_len - 2);
+        let (u, v) = (grid_point.0 - i as f64, grid_point.2 - j as f64);
+        let normal = |i: usize, j: usize| self.normals[j * self.x_len + i];
+        let normal = (normal(i, j) * ((1.0 - u) * (1.0 - v))
+            + normal(i + 1, j) * (u * (1.0 - v))
+            + normal(i, j + 1) * ((1.0 - u) * v)
+            + normal(i + 1, j + 1) * (u * v))
+            .normalised();
+        let uv = (
+            grid_point.0 / (self.x_len - 1) as f64,
+            grid_point.2 / (self.z_len - 1) as f64,
+        );
+        hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv)
+            .with_tangent(WorldVec::new(1.0, 0.0, 0.0))
+            .with_primitive_id(j * (self.x_len - 1) + i)
+    }
+
+    /// Move a ray into grid space.
+    fn to_grid(&self, from: &ray::Ray) -> (WorldVec, WorldVec) {
+        let origin = *from.get_origin() - self.corner;
+        let direction = *from.get_direction();
+        (
+            WorldVec::new(origin.0 / self.cell_size.0, origin.1 + self.corner.1, origin.2 / self.cell_size.1),
+            WorldVec::new(direction.0 / self.cell_size.0, direction.1, direction.2 / self.cell_size.1),
+        )
+    }
+}
+
+impl hit::Hit for Heightfield {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let (t_enter, t_exit) = self.bounds.hit_interval(from, from.get_t_max())?;
+        let t_enter = t_enter.max(from.get_t_min());
+        if t_enter > t_exit {
+            return None;
+        }
+        let (origin, direction) = self.to_grid(from);
+
+        // Walk through the cells the ray passes over, in order (a 2D DDA).
+        let start = origin + direction * t_enter;
+        let (last_i, last_j) = (self.x_len - 2, self.z_len - 2);
+        let mut i = (start.0.floor().max(0.0) as usize).min(last_i);
+        let mut j = (start.2.floor().max(0.0) as usize).min(last_j);
+        // For each axis: which way we step, the `t` of the next cell boundary, and how much `t`
+        // it takes to cross a whole cell.
+        let axis = |position: f64, direction: f64, cell: usize| {
+            if direction > 0.0 {
+                (1, (cell as f64 + 1.0 - position) / direction, 1.0 / direction)
+            } else if direction < 0.0 {
+                (-1, (cell as f64 - position) / direction, -1.0 / direction)
+            } else {
+                (0, f64::INFINITY, f64::INFINITY)
+            }
+        };
+        let (step_i, mut next_i, delta_i) = axis(origin.0, direction.0, i);
+        let (step_j, mut next_j, delta_j) = axis(origin.2, direction.2, j);
+
+        let mut t = t_enter;
+        while t <= t_exit {
+            let t_end = next_i.min(next_j).min(t_exit);
+            if let Some(t_hit) = self.intersect_cell((i, j), origin, direction, t, t_end) {
+                if from.in_range(t_hit) {
+                    return Some(self.material_hit(from, t_hit, origin + direction * t_hit));
+                }
+            }
+            t = t_end;
+            if next_i < next_j {
+                if (step_i < 0 && i == 0) || (step_i > 0 && i == last_i) {
+                    break;
+                }
+                i = (i as isize + step_i) as usize;
+                next_i += delta_i;
+            } else {
+                if step_j == 0 || (step_j < 0 && j == 0) || (step_j > 0 && j == last_j) {
+                    break;
+                }
+                j = (j as isize + step_j) as usize;
+                next_j += delta_j;
+            }
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(self.bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::grey;
+    use crate::hit::Hit;
+
+    #[test]
+    fn test_hit_heightfield() {
+        // A ramp rising along x, from 0 to 2 over 4 units.
+        let heights: Vec<f64> = (0..5 * 3).map(|index| (index % 5) as f64 / 4.0).collect();
+        let ramp = Heightfield::new(
+            &heights,
+            5,
+            3,
+            WorldVec::new(0.0, 0.0, 0.0),
+            WorldVec::new(4.0, 2.0, 2.0),
+            grey(),
+        );
+        let ray = ray::Ray::new(WorldVec::new(3.0, 10.0, 1.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = ramp.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(3.0, 1.5, 1.0)).length() < 1e-9);
+        let expected_normal = WorldVec::new(-0.5, 1.0, 0.0).normalised();
+        assert!((*normal.get_direction() - expected_normal).length() < 1e-9);
+        assert!((hit.uv.0 - 0.75).abs() < 1e-9 && (hit.uv.1 - 0.5).abs() < 1e-9);
+
+        // Skimming along parallel to the ramp, just above it. Then a shallower ray, which meets the
+        // bottom of the ramp.
+        let ray = ray::Ray::new(WorldVec::new(-1.0, -0.4, 1.0), WorldVec::new(1.0, 0.5, 0.0).normalised());
+        assert!(ramp.hit(&ray).is_none());
+        let ray = ray::Ray::new(WorldVec::new(-1.0, -0.4, 1.0), WorldVec::new(1.0, 0.4, 0.0).normalised());
+        let hit = ramp.hit(&ray).expect("The ray should hit.");
+        assert!((*hit.intersected_surface_normal.get_origin() - WorldVec::new(0.0, 0.0, 1.0)).length() < 1e-9);
+    }
+
+    #[test]
+    fn test_from_raster() {
+        let (corner, size) = (WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(1.0, 1.0, 1.0));
+        let ramp = raster::Raster::new(2, 2, vec![[0.0; 3], [1.0; 3], [0.0; 3], [1.0; 3]]);
+        let field = Heightfield::from_raster(&ramp, corner, size, grey()).unwrap();
+        assert_eq!(field.bounding_box().unwrap().max.1, 1.0);
+
+        // Too small to make any cells from.
+        let line = raster::Raster::new(3, 1, vec![[0.5; 3]; 3]);
+        let error = Heightfield::from_raster(&line, corner, size, grey()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_traversal_matches_brute_force() {
+        // Bumpy terrain, with rays from all over.
+        let (x_len, z_len) = (17, 13);
+        let heights: Vec<f64> = (0..x_len * z_len)
+            .map(|index| {
+                let (i, j) = ((index % x_len) as f64, (index / x_len) as f64);
+                0.5 + 0.5 * (i * 0.9).sin() * (j * 0.7).cos()
+            })
+            .collect();
+        let terrain = Heightfield::new(
+            &heights,
+            x_len,
+            z_len,
+            WorldVec::new(-8.0, -1.0, -6.0),
+            WorldVec::new(16.0, 3.0, 12.0),
+            grey(),
+        );
+        for n in 0..200 {
+            let n = n as f64;
+            let origin = WorldVec::new((n * 1.3).sin() * 12.0, 4.0 + (n * 0.3).cos(), (n * 0.7).cos() * 10.0);
+            let target = WorldVec::new((n * 2.1).cos() * 7.0, 0.0, (n * 1.7).sin() * 5.0);
+            let ray = ray::Ray::new(origin, (target - origin).normalised());
+
+            let (grid_origin, grid_direction) = terrain.to_grid(&ray);
+            let mut expected: Option<f64> = None;
+            for j in 0..z_len - 1 {
+                for i in 0..x_len - 1 {
+                    // The ray is only over this cell for part of its length.
+                    let range = |position: f64, direction: f64, cell: usize| {
+                        let (a, b) = ((cell as f64 - position) / direction, (cell as f64 + 1.0 - position) / direction);
+                        (a.min(b), a.max(b))
+                    };
+                    let (i0, i1) = range(grid_origin.0, grid_direction.0, i);
+                    let (j0, j1) = range(grid_origin.2, grid_direction.2, j);
+                    let (t0, t1) = (i0.max(j0).max(0.0), i1.min(j1));
+                    if t0 <= t1 {
+                        if let Some(t) = terrain.intersect_cell((i, j), grid_origin, grid_direction, t0, t1) {
+                            expected = Some(expected.map_or(t, |e: f64| e.min(t)));
+                        }
+                    }
+                }
+            }
+            let expected = expected.map(|t| *ray.get_direction() * t + *ray.get_origin());
+            let actual = terrain.hit(&ray).map(|hit| *hit.intersected_surface_normal.get_origin());
+            match (expected, actual) {
+                (Some(expected), Some(actual)) => assert!((expected - actual).length() < 1e-6),
+                (None, None) => {}
+                _ => panic!("Ray {} disagrees: {:?} vs {:?}", n, expected, actual),
+            }
+        }
+    }
+}
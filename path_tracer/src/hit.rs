@@ -1,12 +1,99 @@
-use crate::colour;
+use crate::aabb;
 use crate::material;
 use crate::ray;
-use crate::vec3;
+use crate::WorldVec;
 
 #[derive(Clone)]
 pub struct MaterialHit {
     pub material: std::rc::Rc<dyn material::Material>,
+    /// The point that was hit, and the geometric normal there. The normal always faces out of the
+    /// object, whichever side it was hit from.
     pub intersected_surface_normal: ray::Ray,
+    /// How far along the ray (in units of its direction) the hit is.
+    pub t: f64,
+    /// Whether the ray hit the outside of the surface (the side the normal faces).
+    pub front_face: bool,
+    /// Texture coordinates of the hit, each usually between 0 and 1.
+    pub uv: (f64, f64),
+    /// The normal used for shading, which may be bent away from the geometric normal by smooth
+    /// normals or a normal map. Like the geometric normal, it faces out of the object.
+    pub shading_normal: WorldVec,
+    /// A unit vector along the surface, perpendicular to the normal. Where the surface has UVs,
+    /// this points in the direction of increasing u.
+    pub tangent: WorldVec,
+    /// 1 if v increases along `normal x tangent`, or -1 if it runs the other way (as it does where
+    /// a texture has been mirrored).
+    pub handedness: f64,
+    /// Which part of the object was hit, for objects made of many parts (such as the triangles of
+    /// a mesh). Zero otherwise.
+    pub primitive_id: usize,
+}
+
+impl MaterialHit {
+    /// A hit `t` along `from`, on a surface with outward facing `normal`. The tangent is chosen
+    /// arbitrarily; use `with_tangent` if the surface has a natural one.
+    pub fn new(
+        material: std::rc::Rc<dyn material::Material>,
+        from: &ray::Ray,
+        t: f64,
+        normal: WorldVec,
+        uv: (f64, f64),
+    ) -> MaterialHit {
+        MaterialHit {
+            material,
+            intersected_surface_normal: ray::Ray::new(from.at(t), normal),
+            t,
+            front_face: normal.dot(*from.get_direction()) < 0.0,
+            uv,
+            shading_normal: normal,
+            tangent: normal.tangent_basis().0,
+            handedness: 1.0,
+            primitive_id: 0,
+        }
+    }
+
+    /// Use `tangent` as the tangent, after making it perpendicular to the normal. It's ignored if
+    /// it's parallel to the normal.
+    pub fn with_tangent(mut self, tangent: WorldVec) -> MaterialHit {
+        let normal = *self.intersected_surface_normal.get_direction();
+        let perpendicular = tangent - normal * normal.dot(tangent);
+        if perpendicular.length() > 1e-12 {
+            self.tangent = perpendicular.normalised();
+        }
+        self
+    }
+
+    pub fn with_handedness(mut self, handedness: f64) -> MaterialHit {
+        self.handedness = handedness;
+        self
+    }
+
+    /// Shade the hit as though the surface faced along `normal`.
+    pub fn with_shading_normal(mut self, normal: WorldVec) -> MaterialHit {
+        self.shading_normal = normal.normalised();
+        self
+    }
+
+    /// The direction of increasing v along the surface.
+    pub fn bitangent(&self) -> WorldVec {
+        self.intersected_surface_normal.get_direction().cross(self.tangent) * self.handedness
+    }
+
+    pub fn with_primitive_id(mut self, primitive_id: usize) -> MaterialHit {
+        self.primitive_id = primitive_id;
+        self
+    }
+
+    /// Flip the surface over, so that its normal faces the other way.
+    pub fn flipped(mut self) -> MaterialHit {
+        let normal = self.intersected_surface_normal;
+        self.intersected_surface_normal = ray::Ray::new(*normal.get_origin(), -*normal.get_direction());
+        self.shading_normal = -self.shading_normal;
+        // Keep v running the same way along the surface.
+        self.handedness = -self.handedness;
+        self.front_face = !self.front_face;
+        self
+    }
 }
 
 /// Defines a type which can be hit with a ray. The returned ray is colour-attenuated and reflected
@@ -14,6 +101,76 @@ pub struct MaterialHit {
 ///
 pub trait Hit: std::fmt::Debug {
     /// Given some ray `from`, return a MaterialHit representing the material and surface normal. Note
-    /// that this is "from" in the reverse direction.
+    /// that this is "from" in the reverse direction. Only the closest hit within the ray's t range
+    /// counts.
     fn hit(&self, from: &ray::Ray) -> Option<MaterialHit>;
+
+    /// A box containing the whole object, or `None` if the object is unbounded. Objects with
+    /// bounds can be put in a BVH.
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        None
+    }
+
+    /// Every stretch of `from` which is inside the object, in order along the ray, ignoring the
+    /// ray's t range. Objects which aren't solid (such as a single quad) give intervals which
+    /// never close.
+    fn intervals(&self, from: &ray::Ray) -> Vec<Interval> {
+        march_intervals(self, from)
+    }
 } 
+
+/// A point where a ray crosses the surface of an object.
+#[derive(Clone)]
+pub struct Crossing {
+    /// The same as `hit.t`.
+    pub t: f64,
+    pub hit: MaterialHit,
+}
+
+/// One stretch of a ray spent inside a solid object.
+#[derive(Clone)]
+pub struct Interval {
+    /// Where the ray enters the object, or `None` if it starts inside.
+    pub enter: Option<Crossing>,
+    /// Where the ray leaves the object, or `None` if it never does.
+    pub exit: Option<Crossing>,
+}
+
+/// The most surfaces `march_intervals` will find along a single ray.
+const MAX_MARCHED_CROSSINGS: usize = 64;
+
+/// How far past each surface `march_intervals` moves before looking for the next one.
+const MARCH_EPSILON: f64 = 1e-9;
+
+/// Find every interval along `from` which is inside `object`, by repeatedly hitting it and
+/// stepping through the surface. Each crossing is classed as entering or leaving the object by
+/// which way its normal faces, so this relies on the object's normals facing outwards and on the
+/// object being hittable from the inside.
+pub fn march_intervals<H: Hit + ?Sized>(object: &H, from: &ray::Ray) -> Vec<Interval> {
+    let mut intervals = Vec::new();
+    let mut enter: Option<Crossing> = None;
+    let mut t_min = 0.0;
+    for _ in 0..MAX_MARCHED_CROSSINGS {
+        let remaining = from.with_t_range(t_min, f64::INFINITY);
+        let hit = match object.hit(&remaining) {
+            Some(hit) => hit,
+            None => break,
+        };
+        let (t, entering) = (hit.t, hit.front_face);
+        let crossing = Crossing { t, hit };
+        if entering {
+            // Two entries in a row means we missed an exit; start again from the later one.
+            enter = Some(crossing);
+        } else {
+            intervals.push(Interval {
+                enter: enter.take(),
+                exit: Some(crossing),
+            });
+        }
+        t_min = t.max(t_min) + MARCH_EPSILON;
+    }
+    if enter.is_some() {
+        intervals.push(Interval { enter, exit: None });
+    }
+    intervals
+}
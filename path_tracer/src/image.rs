@@ -28,7 +28,7 @@ impl Image {
         result += &format!("{} {}\n", self.x_size, self.y_size);
 
         // Next, the maximum value for each number.
-        result += &format!("{}\n", ImageDataType::max_value());
+        result += &format!("{}\n", ImageDataType::MAX);
 
         // Next, follows a series of RGB triplets.
         for y in 0..self.y_size {
@@ -46,6 +46,22 @@ impl Image {
         result
     }
 
+    /// Encode the image as an 8-bit RGB PNG.
+    pub fn write_png<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.x_size as u32, self.y_size as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let bytes: Vec<u8> = self
+            .data
+            .iter()
+            .flat_map(|pixel| vec![pixel.0, pixel.1, pixel.2])
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&bytes))
+            .map_err(std::io::Error::other)
+    }
+
     pub fn x_len(&self) -> usize {
         self.x_size
     }
@@ -53,6 +69,182 @@ impl Image {
     pub fn y_len(&self) -> usize {
         self.y_size
     }
+
+    /// Copy `source` into this image, with its top left corner at `offset`. Any part of `source`
+    /// which falls outside of this image is ignored.
+    pub fn blit(&mut self, source: &Image, offset: (usize, usize)) {
+        let x_end = offset.0.saturating_add(source.x_size).min(self.x_size);
+        let y_end = offset.1.saturating_add(source.y_size).min(self.y_size);
+        for y in offset.1..y_end {
+            for x in offset.0..x_end {
+                self[(x, y)] = source[(x - offset.0, y - offset.1)];
+            }
+        }
+    }
+
+    /// Copy out the region of this image with its top left corner at `top_left`. The region is
+    /// shrunk to fit if it extends past the edges of the image.
+    pub fn crop(&self, top_left: (usize, usize), size: (usize, usize)) -> Image {
+        // A far corner too far away to represent is past the edge anyway.
+        let x_end = top_left.0.checked_add(size.0).map_or(self.x_size, |end| end.min(self.x_size));
+        let y_end = top_left.1.checked_add(size.1).map_or(self.y_size, |end| end.min(self.y_size));
+        let x_size = x_end.saturating_sub(top_left.0);
+        let y_size = y_end.saturating_sub(top_left.1);
+        let mut result = Image::new(x_size, y_size);
+        for y in 0..y_size {
+            for x in 0..x_size {
+                result[(x, y)] = self[(top_left.0 + x, top_left.1 + y)];
+            }
+        }
+        result
+    }
+
+    /// Mirror the image left to right.
+    pub fn flip_horizontal(&mut self) {
+        for row in self.data.chunks_mut(self.x_size.max(1)) {
+            row.reverse();
+        }
+    }
+
+    /// Mirror the image top to bottom.
+    pub fn flip_vertical(&mut self) {
+        for y in 0..self.y_size / 2 {
+            for x in 0..self.x_size {
+                self.data
+                    .swap(x + y * self.x_size, x + (self.y_size - 1 - y) * self.x_size);
+            }
+        }
+    }
+
+    /// Resample the image to a new size. The filter is widened when shrinking the image so that
+    /// every source pixel contributes to the result, which avoids aliasing.
+    pub fn resize(&self, x_size: usize, y_size: usize, filter: ResizeFilter) -> Image {
+        // Filter the rows, then the columns.
+        let data: Vec<[f64; 3]> = self
+            .data
+            .iter()
+            .map(|pixel| [pixel.0 as f64, pixel.1 as f64, pixel.2 as f64])
+            .collect();
+        let rows = resample(&data, (self.x_size, self.y_size), x_size, true, filter);
+        let columns = resample(&rows, (x_size, self.y_size), y_size, false, filter);
+
+        let mut result = Image::new(x_size, y_size);
+        for (pixel, value) in result.data.iter_mut().zip(columns) {
+            let to_u8 = |channel: f64| channel.round().clamp(0.0, 255.0) as u8;
+            *pixel = vec3::Vec3::new(to_u8(value[0]), to_u8(value[1]), to_u8(value[2]));
+        }
+        result
+    }
+}
+
+/// The reconstruction filters available to `Image::resize`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ResizeFilter {
+    /// Averages the pixels covered by each new pixel. Blocky when enlarging.
+    Box,
+    /// Linear interpolation (bilinear, when applied in both directions).
+    Triangle,
+    /// A windowed sinc. Sharp, but may ring around hard edges.
+    Lanczos3,
+}
+
+impl ResizeFilter {
+    fn radius(self) -> f64 {
+        match self {
+            ResizeFilter::Box => 0.5,
+            ResizeFilter::Triangle => 1.0,
+            ResizeFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f64) -> f64 {
+        let x = x.abs();
+        match self {
+            ResizeFilter::Box => {
+                if x <= 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            ResizeFilter::Triangle => (1.0 - x).max(0.0),
+            ResizeFilter::Lanczos3 => {
+                let sinc = |x: f64| {
+                    if x < 1e-8 {
+                        1.0
+                    } else {
+                        let pi_x = std::f64::consts::PI * x;
+                        pi_x.sin() / pi_x
+                    }
+                };
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Resample `data` (a row-major image of the given size) to `new_len` pixels along the rows if
+/// `horizontal` is set, or along the columns otherwise.
+fn resample(
+    data: &[[f64; 3]],
+    size: (usize, usize),
+    new_len: usize,
+    horizontal: bool,
+    filter: ResizeFilter,
+) -> Vec<[f64; 3]> {
+    // The length of each line we're resampling, the number of lines, and the distances between
+    // neighbouring pixels and lines in the source and result.
+    let (old_len, lines, stride, line_stride, result_stride, result_line_stride) = if horizontal {
+        (size.0, size.1, 1, size.0, 1, new_len)
+    } else {
+        (size.1, size.0, size.0, 1, size.0, 1)
+    };
+    let mut result = vec![[0.0; 3]; new_len * lines];
+    if old_len == 0 {
+        return result;
+    }
+
+    let scale = old_len as f64 / new_len as f64;
+    let filter_scale = scale.max(1.0);
+    let support = filter.radius() * filter_scale;
+    for new_index in 0..new_len {
+        // The centre of this pixel, in the source's coordinates.
+        let centre = (new_index as f64 + 0.5) * scale - 0.5;
+        let first = ((centre - support).ceil().max(0.0)) as usize;
+        let last = ((centre + support).floor() as usize).min(old_len - 1);
+        let mut weights: Vec<(usize, f64)> = (first..=last)
+            .map(|old_index| {
+                let distance = (old_index as f64 - centre) / filter_scale;
+                (old_index, filter.weight(distance))
+            })
+            .collect();
+        let total: f64 = weights.iter().map(|(_, weight)| weight).sum();
+        if total.abs() < 1e-12 {
+            // Fall back to the nearest pixel.
+            let nearest = (centre.round().max(0.0) as usize).min(old_len - 1);
+            weights = vec![(nearest, 1.0)];
+        } else {
+            for (_, weight) in weights.iter_mut() {
+                *weight /= total;
+            }
+        }
+
+        for line in 0..lines {
+            let mut value = [0.0; 3];
+            for &(old_index, weight) in &weights {
+                let pixel = data[old_index * stride + line * line_stride];
+                for channel in 0..3 {
+                    value[channel] += pixel[channel] * weight;
+                }
+            }
+            result[new_index * result_stride + line * result_line_stride] = value;
+        }
+    }
+    result
 }
 
 impl std::ops::Index<(usize, usize)> for Image {
@@ -72,6 +264,91 @@ impl std::ops::IndexMut<(usize, usize)> for Image {
 mod tests {
     use super::*;
 
+    fn gradient(x_size: usize, y_size: usize) -> Image {
+        let mut im = Image::new(x_size, y_size);
+        for y in 0..y_size {
+            for x in 0..x_size {
+                im[(x, y)] = vec3::Vec3::new(x as u8, y as u8, 0);
+            }
+        }
+        im
+    }
+
+    #[test]
+    fn test_blit_and_crop() {
+        let source = gradient(4, 3);
+        let mut im = Image::new(6, 6);
+        im.blit(&source, (1, 2));
+        assert_eq!(im[(1, 2)], vec3::Vec3::new(0, 0, 0));
+        assert_eq!(im[(4, 4)], vec3::Vec3::new(3, 2, 0));
+
+        let cropped = im.crop((1, 2), (4, 3));
+        assert_eq!(cropped.to_ppm(), source.to_ppm());
+
+        // Blitting or cropping past the edge is clipped.
+        im.blit(&source, (4, 4));
+        assert_eq!(im[(5, 5)], vec3::Vec3::new(1, 1, 0));
+        let cropped = im.crop((4, 4), (10, 10));
+        assert_eq!((cropped.x_len(), cropped.y_len()), (2, 2));
+
+        // Even if the far corner is too far away to represent.
+        im.blit(&source, (usize::MAX, 1));
+        let cropped = im.crop((4, 4), (usize::MAX, usize::MAX));
+        assert_eq!((cropped.x_len(), cropped.y_len()), (2, 2));
+    }
+
+    #[test]
+    fn test_flip() {
+        let mut im = gradient(3, 2);
+        im.flip_horizontal();
+        assert_eq!(im[(0, 0)], vec3::Vec3::new(2, 0, 0));
+        assert_eq!(im[(2, 1)], vec3::Vec3::new(0, 1, 0));
+        im.flip_vertical();
+        assert_eq!(im[(0, 0)], vec3::Vec3::new(2, 1, 0));
+        assert_eq!(im[(2, 1)], vec3::Vec3::new(0, 0, 0));
+    }
+
+    #[test]
+    fn test_resize() {
+        // A flat image stays flat, whatever the filter.
+        for filter in &[ResizeFilter::Box, ResizeFilter::Triangle, ResizeFilter::Lanczos3] {
+            let mut im = Image::new(7, 5);
+            for y in 0..5 {
+                for x in 0..7 {
+                    im[(x, y)] = vec3::Vec3::new(10, 20, 30);
+                }
+            }
+            for &(x_size, y_size) in &[(3, 2), (14, 11)] {
+                let resized = im.resize(x_size, y_size, *filter);
+                assert_eq!((resized.x_len(), resized.y_len()), (x_size, y_size));
+                assert_eq!(resized[(x_size - 1, y_size - 1)], vec3::Vec3::new(10, 20, 30));
+            }
+        }
+
+        // Halving a checkerboard with a box filter should average each 2x2 block.
+        let mut checkerboard = Image::new(4, 4);
+        for y in 0..4 {
+            for x in 0..4 {
+                let value = if (x + y) % 2 == 0 { 200 } else { 0 };
+                checkerboard[(x, y)] = vec3::Vec3::new(value, value, value);
+            }
+        }
+        let halved = checkerboard.resize(2, 2, ResizeFilter::Box);
+        assert_eq!(halved[(1, 1)], vec3::Vec3::new(100, 100, 100));
+    }
+
+    #[test]
+    fn test_write_png() {
+        let mut im = Image::new(2, 1);
+        im[(1, 0)] = vec3::Vec3::new(255, 0, 51);
+        let mut bytes = Vec::new();
+        im.write_png(&mut bytes).unwrap();
+        let raster = crate::raster::Raster::decode(&bytes).unwrap();
+        assert_eq!((raster.x_len(), raster.y_len()), (2, 1));
+        assert_eq!(raster[(0, 0)], [0.0, 0.0, 0.0]);
+        assert_eq!(raster[(1, 0)], [1.0, 0.0, 0.2]);
+    }
+
     #[test]
     fn test_serialise() {
         let mut im = Image::new(3, 2);
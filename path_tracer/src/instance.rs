@@ -0,0 +1,148 @@
+use crate::aabb;
+use crate::hit;
+use crate::ray;
+use crate::transform;
+use crate::WorldVec;
+
+/// Places some object in the world with a transform. The object itself is shared, so the same
+/// object (a large mesh, say) can be placed many times without copying it.
+#[derive(Debug)]
+pub struct Instance {
+    object: std::rc::Rc<dyn hit::Hit>,
+    transform: transform::Transform,
+    /// The transform's `normal_matrix`, which every hit needs.
+    normal_matrix: transform::Matrix4,
+}
+
+impl Instance {
+    /// `transform` takes points from the object's space into the world.
+    pub fn new(object: std::rc::Rc<dyn hit::Hit>, transform: transform::Transform) -> Instance {
+        Instance {
+            object,
+            normal_matrix: transform.normal_matrix(),
+            transform,
+        }
+    }
+
+    pub fn object(&self) -> &std::rc::Rc<dyn hit::Hit> {
+        &self.object
+    }
+
+    pub fn transform(&self) -> &transform::Transform {
+        &self.transform
+    }
+
+    pub fn set_transform(&mut self, transform: transform::Transform) {
+        self.normal_matrix = transform.normal_matrix();
+        self.transform = transform;
+    }
+}
+
+/// Move a hit on an object placed with `transform` out into the world. `t` stays the same, as rays
+/// aren't renormalised when they're transformed. `normal_matrix` is the transform's.
+fn to_world(
+    transform: &transform::Transform,
+    normal_matrix: &transform::Matrix4,
+    mut material_hit: hit::MaterialHit,
+) -> hit::MaterialHit {
+    let transform_normal = |normal: WorldVec| normal_matrix.transform_vector(normal).normalised();
+    let bitangent = transform.transform_vector(material_hit.bitangent());
+    let normal = material_hit.intersected_surface_normal;
+    material_hit.intersected_surface_normal = ray::Ray::new(
+        transform.transform_point(*normal.get_origin()),
+        transform_normal(*normal.get_direction()),
+    );
+    material_hit.shading_normal = transform_normal(material_hit.shading_normal);
+    let tangent = transform.transform_vector(material_hit.tangent);
+    let material_hit = material_hit.with_tangent(tangent);
+    // Transforms which mirror the object also mirror its textures.
+    let mirrored = material_hit.bitangent().dot(bitangent) < 0.0;
+    let handedness = if mirrored { -material_hit.handedness } else { material_hit.handedness };
+    material_hit.with_handedness(handedness)
+}
+
+/// Hit `object` as if it were placed with `transform`, without making an `Instance` of it.
+pub(crate) fn hit_placed(
+    object: &dyn hit::Hit,
+    transform: &transform::Transform,
+    normal_matrix: &transform::Matrix4,
+    from: &ray::Ray,
+) -> Option<hit::MaterialHit> {
+    // Rather than moving the object, move the ray into the object's space, and move the result
+    // back out again.
+    let object_ray = transform.inverse().transform_ray(from);
+    let material_hit = object.hit(&object_ray)?;
+    Some(to_world(transform, normal_matrix, material_hit))
+}
+
+impl hit::Hit for Instance {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        hit_placed(self.object.as_ref(), &self.transform, &self.normal_matrix, from)
+    }
+
+    fn intervals(&self, from: &ray::Ray) -> Vec<hit::Interval> {
+        // As the ray's direction isn't renormalised, the `t`s are the same in both spaces.
+        let object_ray = self.transform.inverse().transform_ray(from);
+        let to_world = |crossing: hit::Crossing| hit::Crossing {
+            t: crossing.t,
+            hit: to_world(&self.transform, &self.normal_matrix, crossing.hit),
+        };
+        self.object
+            .intervals(&object_ray)
+            .into_iter()
+            .map(|interval| hit::Interval {
+                enter: interval.enter.map(to_world),
+                exit: interval.exit.map(to_world),
+            })
+            .collect()
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        self.object
+            .bounding_box()
+            .map(|bounds| bounds.transformed(&self.transform))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+    use crate::sphere;
+
+    #[test]
+    fn test_hit_instance() {
+        let unit_sphere = std::rc::Rc::new(sphere::Sphere::new(
+            WorldVec::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        ));
+        // Stretch the sphere into an ellipsoid along x, then move it 10 units along z.
+        let ellipsoid = Instance::new(
+            unit_sphere,
+            transform::Transform::scaling(WorldVec::new(2.0, 1.0, 1.0))
+                .then(&transform::Transform::translation(WorldVec::new(0.0, 0.0, 10.0))),
+        );
+
+        // Cast a ray along the z axis. It should hit the front of the ellipsoid.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = ellipsoid.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(0.0, 0.0, 9.0)).length() < 1e-9);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 0.0, -1.0)).length() < 1e-9);
+
+        // Within the stretched part, but outside of where the unit sphere would be.
+        let ray = ray::Ray::new(WorldVec::new(1.5, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = ellipsoid.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        // On the ellipsoid (x/2)^2 + y^2 + (z - 10)^2 = 1, the normal is along (x/4, y, z - 10).
+        let point = *normal.get_origin();
+        let expected = WorldVec::new(point.0 / 4.0, point.1, point.2 - 10.0).normalised();
+        assert!((*normal.get_direction() - expected).length() < 1e-9);
+
+        let ray = ray::Ray::new(WorldVec::new(2.5, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(ellipsoid.hit(&ray).is_none());
+    }
+}
@@ -1,43 +1,92 @@
 use crate::colour;
+use crate::hit;
 use crate::material;
+use crate::microfacet;
 use crate::ray;
+use crate::texture;
+use crate::WorldVec;
 
 #[derive(Debug)]
 pub struct Lambertian {
-    colour: colour::Colour,
+    albedo: std::rc::Rc<dyn texture::Texture>,
 }
 
 impl Lambertian {
     pub fn new(colour: colour::Colour) -> Lambertian {
-        Lambertian { colour }
+        Lambertian::textured(std::rc::Rc::new(texture::Constant::new(colour)))
+    }
+
+    /// A Lambertian surface whose colour varies across it.
+    pub fn textured(albedo: std::rc::Rc<dyn texture::Texture>) -> Lambertian {
+        Lambertian { albedo }
     }
 }
 
 impl material::Material for Lambertian {
     fn sample_gathering_ray(
         &self,
-        _reflected_ray: &ray::Ray,
-        surface_normal: &ray::Ray,
+        reflected_ray: &ray::Ray,
+        hit: &hit::MaterialHit,
     ) -> ray::Ray {
+        // Scatter about the shading normal, on the viewer's side, in proportion to the cosine.
+        let frame = microfacet::Frame::new(hit, -*reflected_ray.get_direction());
         ray::Ray::new(
             /*origin=*/
-            *surface_normal.get_origin(),
+            *hit.intersected_surface_normal.get_origin(),
             /*direction=*/
-            material::random_unit_vector_in_sphere().normalised(),
+            frame.to_world(material::random_cosine_weighted_in_hemisphere()),
         )
     }
 
     fn colour(
         &self,
         start_colour: colour::Colour,
-        _surface_normal: &ray::Ray,
+        hit: &hit::MaterialHit,
         _angle_of_incidence: f64,
     ) -> colour::Colour {
         // Perfectly diffuse. Therefore, we ignore the angle of incidence.
-        colour::Colour::new(
-            self.colour.get_red() * start_colour.get_red(),
-            self.colour.get_green() * start_colour.get_green(),
-            self.colour.get_blue() * start_colour.get_blue(),
-        )
+        material::attenuate(&start_colour, &self.albedo.colour(hit))
+    }
+
+    fn scattering(
+        &self,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> Option<(colour::Colour, f64)> {
+        // Both the reflectance (with the cosine) and the density are the cosine over pi.
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let cosine = towards_light.dot(frame.normal).max(0.0) / std::f64::consts::PI;
+        let albedo = self.albedo.colour(hit);
+        Some((
+            colour::Colour::unclamped(albedo.get_red() * cosine, albedo.get_green() * cosine, albedo.get_blue() * cosine),
+            cosine,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler;
+    use crate::test_helpers::hit_on_plane;
+
+    #[test]
+    fn test_scatters_about_shading_normal() {
+        sampler::replace_current(sampler::Sampler::new(19));
+        let grey: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        let tilted = WorldVec::new(1.0, 0.0, 1.0).normalised();
+        let (from, hit) = hit_on_plane(grey.clone(), WorldVec::new(0.0, 0.0, -1.0));
+        let hit = hit.with_shading_normal(tilted);
+        let samples = 2000;
+        let mut mean = WorldVec::new(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let direction = grey.sample_gathering_ray(&from, &hit).get_direction().normalised();
+            assert!(direction.dot(tilted) >= -1e-9);
+            mean = mean + direction * (1.0 / samples as f64);
+        }
+        // Cosine weighting puts the mean direction along the normal, two thirds of the way out.
+        assert!((mean - tilted * (2.0 / 3.0)).length() < 0.05);
     }
 }
@@ -10,5 +10,46 @@ pub mod sphere;
 pub mod lambertian;
 pub mod debugon;
 pub mod metal;
+pub mod film;
+pub mod progressive;
+pub mod sampler;
+pub mod checkpoint;
+pub mod scene_description;
+pub mod distributed;
+pub mod transform;
+pub mod instance;
+pub mod aabb;
+pub mod bvh;
+pub mod mesh;
+pub mod plane;
+pub mod disk;
+pub mod quad;
+pub mod cuboid;
+pub mod emissive;
+pub mod polynomial;
+pub mod cylinder;
+pub mod cone;
+pub mod torus;
+pub mod capsule;
+pub mod csg;
+pub mod sdf;
+pub mod raster;
+pub mod heightfield;
+pub mod motion;
+pub mod animation;
+pub mod texture;
+pub mod noise;
+pub mod pattern;
+pub mod normal_map;
+pub mod microfacet;
+pub mod principled;
+pub mod oren_nayar;
+pub mod combinator;
+pub mod thin_film;
+pub mod merl;
+pub mod subsurface;
 
 pub type WorldVec = vec3::Vec3<f64>;
+
+#[cfg(test)]
+mod test_helpers;
@@ -2,14 +2,198 @@ use path_tracer::camera;
 use path_tracer::vec3;
 use path_tracer::scene;
 use path_tracer::sphere;
+use path_tracer::plane;
 use path_tracer::colour;
 use path_tracer::lambertian;
 use path_tracer::metal;
 use path_tracer::debugon;
+use path_tracer::distributed;
+use path_tracer::scene_description;
+use path_tracer::animation;
+use path_tracer::hit::Hit;
+use path_tracer::ray;
 
 const TOP_SPHERE_RADIUS: f64 = 1.0;
 
+const USAGE: &str = "Usage:
+    path_tracer                                              Render the built-in scene.
+    path_tracer worker <listen address>                      Render tiles for coordinators.
+    path_tracer coordinator <scene file> <worker address>... Render a scene across workers.
+    path_tracer turntable <scene file> <directory> <frames>  Render frames orbiting a scene.
+
+Options, for the coordinator and turntable:
+    --size <width>x<height>  Image size in pixels (800x600).
+    --samples <count>        Samples per pixel (100).
+    --bounces <count>        Maximum bounces per path (10).
+    --fov <degrees>          Vertical field of view (60).
+    --fps <rate>             Frames per second, for the turntable (24).";
+
+/// Settings given on the command line, with their defaults.
+struct Options {
+    x_size: usize,
+    y_size: usize,
+    samples_per_pixel: usize,
+    bounces: usize,
+    fov: f64,
+    frames_per_second: f64,
+}
+
+impl Options {
+    /// Split `args` into positional arguments and options.
+    fn parse(args: &[String]) -> Result<(Vec<&str>, Options), String> {
+        let mut options = Options {
+            x_size: 800,
+            y_size: 600,
+            samples_per_pixel: 100,
+            bounces: 10,
+            fov: 60.0,
+            frames_per_second: 24.0,
+        };
+        let mut positional = Vec::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.as_str());
+                continue;
+            }
+            let value = args.next().ok_or_else(|| format!("{} needs a value", arg))?;
+            let invalid = || format!("Invalid value for {}: {}", arg, value);
+            match arg.as_str() {
+                "--size" => {
+                    let (x_size, y_size) = value.split_once('x').ok_or_else(invalid)?;
+                    options.x_size = x_size.parse().map_err(|_| invalid())?;
+                    options.y_size = y_size.parse().map_err(|_| invalid())?;
+                    if options.x_size == 0 || options.y_size == 0 {
+                        return Err(invalid());
+                    }
+                }
+                "--samples" => options.samples_per_pixel = value.parse().map_err(|_| invalid())?,
+                "--bounces" => options.bounces = value.parse().map_err(|_| invalid())?,
+                "--fov" => {
+                    options.fov = value.parse().map_err(|_| invalid())?;
+                    if !(options.fov > 0.0 && options.fov < 180.0) {
+                        return Err(invalid());
+                    }
+                }
+                "--fps" => {
+                    options.frames_per_second = value.parse().map_err(|_| invalid())?;
+                    if !(options.frames_per_second > 0.0 && options.frames_per_second.is_finite()) {
+                        return Err(invalid());
+                    }
+                }
+                _ => return Err(format!("Unknown option: {}", arg)),
+            }
+        }
+        Ok((positional, options))
+    }
+}
+
 fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        None => render_builtin_scene(),
+        Some("worker") if args.len() == 3 => run_worker(&args[2]),
+        Some("coordinator") => match parse_options(&args[2..]) {
+            (positional, options) if positional.len() >= 2 => {
+                run_coordinator(positional[0], &positional[1..], &options)
+            }
+            _ => usage(),
+        },
+        Some("turntable") => match parse_options(&args[2..]) {
+            (positional, options) if positional.len() == 3 => {
+                run_turntable(positional[0], positional[1], positional[2], &options)
+            }
+            _ => usage(),
+        },
+        _ => usage(),
+    }
+}
+
+/// `Options::parse`, exiting with the usage if the options are invalid.
+fn parse_options(args: &[String]) -> (Vec<&str>, Options) {
+    Options::parse(args).unwrap_or_else(|error| {
+        eprintln!("{}\n{}", error, USAGE);
+        std::process::exit(1);
+    })
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    std::process::exit(1);
+}
+
+fn run_worker(address: &str) {
+    let listener = std::net::TcpListener::bind(address).expect("Couldn't listen on address");
+    let build = |scene: &[u8]| {
+        let description = std::str::from_utf8(scene).map_err(|error| error.to_string())?;
+        scene_description::parse(description).map_err(|error| error.to_string())
+    };
+    // Keep serving whatever happens to each coordinator.
+    loop {
+        if let Err(error) = distributed::serve(&listener, &build) {
+            eprintln!("Lost connection to coordinator: {}", error);
+        }
+    }
+}
+
+fn run_coordinator(scene_file: &str, workers: &[&str], options: &Options) {
+    let scene = std::fs::read(scene_file).expect("Couldn't read scene file");
+    let workers = workers
+        .iter()
+        .map(|worker| worker.parse().expect("Invalid worker address"))
+        .collect();
+    let settings = distributed::RenderSettings {
+        x_size: options.x_size,
+        y_size: options.y_size,
+        fov: options.fov,
+        bounces: options.bounces,
+        samples_per_pixel: options.samples_per_pixel,
+        seed: rand::random(),
+    };
+    match distributed::Coordinator::new(workers).render(&scene, &settings) {
+        Ok(film) => println!("{}", film.to_image().to_ppm()),
+        Err(error) => {
+            eprintln!("Render failed: {:?}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn run_turntable(scene_file: &str, directory: &str, frames: &str, options: &Options) {
+    let description = std::fs::read_to_string(scene_file).expect("Couldn't read scene file");
+    let mut camera = scene_description::parse(&description).unwrap_or_else(|error| {
+        eprintln!("Invalid scene: {}", error);
+        std::process::exit(1);
+    });
+    let frames: usize = frames.parse().expect("Invalid frame count");
+    // Orbit whatever is in the middle of the view.
+    let target = camera
+        .scene()
+        .hit(&ray::Ray::new(camera.origin(), camera.forward()))
+        .map_or(camera.origin() + camera.forward(), |hit| *hit.intersected_surface_normal.get_origin());
+    let turntable = animation::Turntable::looping(
+        target,
+        camera.origin(),
+        /*up=*/vec3::Vec3::new(0.0, 1.0, 0.0),
+        options.fov,
+        frames,
+        options.frames_per_second,
+    );
+    let sequence = animation::Sequence {
+        frames: 1..=frames,
+        frames_per_second: options.frames_per_second,
+        shutter_fraction: 0.0,
+        x_size: options.x_size,
+        y_size: options.y_size,
+        bounces: options.bounces,
+        samples_per_pixel: options.samples_per_pixel,
+    };
+    let directory = std::path::Path::new(directory);
+    std::fs::create_dir_all(directory).expect("Couldn't create output directory");
+    animation::render_sequence(&mut camera, &turntable, &sequence, directory).expect("Couldn't write frame");
+}
+
+fn render_builtin_scene() {
     let mut scene = scene::Scene::new();
 
     let metal = std::rc::Rc::new(metal::Metal::new(colour::Colour::new(0.5, 0.5, 0.5), 0.25));
@@ -18,11 +202,11 @@ fn main() {
 
     let top_sphere1 = std::rc::Rc::new(sphere::Sphere::new(vec3::Vec3::new(-TOP_SPHERE_RADIUS, 0.0, 0.0), TOP_SPHERE_RADIUS, metal.clone()));
     let top_sphere2 = std::rc::Rc::new(sphere::Sphere::new(vec3::Vec3::new(TOP_SPHERE_RADIUS, 0.0, 0.0), TOP_SPHERE_RADIUS, debugon.clone()));
-    let bottom_sphere = std::rc::Rc::new(sphere::Sphere::new(vec3::Vec3::new(0.0, -100.0 - TOP_SPHERE_RADIUS, 0.0), 100.0, gray_lambertian.clone()));
+    let ground = std::rc::Rc::new(plane::Plane::new(vec3::Vec3::new(0.0, -TOP_SPHERE_RADIUS, 0.0), vec3::Vec3::new(0.0, 1.0, 0.0), gray_lambertian.clone()));
 
     scene.add_object(top_sphere1.clone());
     scene.add_object(top_sphere2.clone());
-    scene.add_object(bottom_sphere.clone()); 
+    scene.add_object(ground.clone());
 
     let camera = camera::Camera::new_looking_at(
         /*scene=*/std::rc::Rc::new(scene),
@@ -1,18 +1,82 @@
 use crate::colour;
+use crate::hit;
 use crate::ray;
+use crate::sampler;
+use crate::subsurface;
 use crate::WorldVec;
 use crate::vec3;
 
 pub trait Material: std::fmt::Debug {
     /// Given some reflected ray, trace it in the backward direction. This should provide the BDRF
     /// for the material. Returns the new direction vector of the traced ray -- the origin of the
-    /// ray is the origin of the surface_normal.
-    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, surface_normal: &ray::Ray) -> ray::Ray;
+    /// ray is the point that was hit.
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray;
 
     /// Given some ray, colour it in the forward direction based on the angle of incidence. This
     /// gives the material its colour attenuation properties. The angle of incidence is in radians.
-    fn colour(&self, start_colour: colour::Colour, surface_normal: &ray::Ray, angle_of_incidence: f64) -> colour::Colour;
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour;
 
+    /// Colour light arriving from `towards_light` as it leaves towards `towards_viewer`. Both are
+    /// unit vectors pointing away from the surface, and `towards_light` is the direction that
+    /// `sample_gathering_ray` chose, so this is the weight of that sample. Materials which depend
+    /// on both directions, such as glossy ones, override this; by default it calls `colour` with
+    /// the angle between the light and the normal.
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        _towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let normal = hit.intersected_surface_normal.get_direction().normalised();
+        let angle_of_incidence = towards_light.dot(normal).clamp(-1.0, 1.0).acos();
+        self.colour(start_colour, hit, angle_of_incidence)
+    }
+
+    /// Combinations of other materials, such as blends and layers, pick one of their parts to
+    /// handle each hit, at random, in proportion to how much each part contributes. The chosen
+    /// part then samples and colours the hit. Materials which handle hits themselves return
+    /// `None`, as by default. See `resolve`.
+    fn choose(&self, _hit: &hit::MaterialHit, _towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn Material>> {
+        None
+    }
+
+    /// What fills objects of this material, if light goes inside them and scatters around (see
+    /// `subsurface`). The camera follows light going in through the surface on a random walk
+    /// until it comes out again. Most materials are opaque or clear, and return `None`.
+    fn interior(&self) -> Option<subsurface::Medium> {
+        None
+    }
+
+    /// The light given off by the surface itself, if it's a light source. Paths end at light
+    /// sources: whatever would have lit them from beyond is ignored.
+    fn emitted(&self, _hit: &hit::MaterialHit) -> Option<colour::Colour> {
+        None
+    }
+
+    /// How much of the light arriving from `towards_light` leaves towards `towards_viewer`, with
+    /// the cosine of the angle of incidence included, and the density (per unit solid angle) with
+    /// which `sample_gathering_ray` would have chosen `towards_light`. With these, the camera can
+    /// light the surface from points it picks on lights itself. Materials which can't say, such as
+    /// mirrors, which only reflect in one direction, return `None`, as by default; they're only lit
+    /// along the rays they choose.
+    fn scattering(
+        &self,
+        _hit: &hit::MaterialHit,
+        _towards_light: WorldVec,
+        _towards_viewer: WorldVec,
+    ) -> Option<(colour::Colour, f64)> {
+        None
+    }
+}
+
+/// Replace the material of `hit` with whichever part of it handles the hit, repeatedly, until it
+/// is a material which handles hits itself. This should be done before sampling a hit.
+pub fn resolve(mut hit: hit::MaterialHit, towards_viewer: WorldVec) -> hit::MaterialHit {
+    while let Some(chosen) = hit.material.choose(&hit, towards_viewer) {
+        hit.material = chosen;
+    }
+    hit
 }
 
 /// Produces a ray starting at some origin, pointing to some uniformly-distributed vector along
@@ -29,10 +93,32 @@ pub fn random_unit_vector_in_sphere() -> WorldVec {
             radius * inclination.cos(),
         )
     }
-    from_spherical(
-        1.0,
-        between.sample(&mut rand::thread_rng()) * std::f64::consts::PI,
-        between.sample(&mut rand::thread_rng()) * 2.0 * std::f64::consts::PI,
-    )
+    sampler::with_current(|rng| {
+        from_spherical(
+            1.0,
+            between.sample(rng) * std::f64::consts::PI,
+            between.sample(rng) * 2.0 * std::f64::consts::PI,
+        )
+    })
 }
 
+/// A random direction in the hemisphere around +z, more likely the closer it is to z (in
+/// proportion to the cosine), as light is scattered by a diffuse surface.
+pub fn random_cosine_weighted_in_hemisphere() -> WorldVec {
+    use rand::distributions::{Distribution, Uniform};
+    let between = Uniform::from(0.0..1.0);
+    let (u1, u2) = sampler::with_current(|rng| (between.sample(rng), between.sample(rng)));
+    let r = f64::sqrt(u1);
+    let phi = 2.0 * std::f64::consts::PI * u2;
+    vec3::Vec3(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt())
+}
+
+/// Multiply two colours together, channel by channel. This is how a surface tints the light which
+/// it reflects. Either may be brighter than 1, and so may the result.
+pub fn attenuate(light: &colour::Colour, tint: &colour::Colour) -> colour::Colour {
+    colour::Colour::unclamped(
+        light.get_red() * tint.get_red(),
+        light.get_green() * tint.get_green(),
+        light.get_blue() * tint.get_blue(),
+    )
+}
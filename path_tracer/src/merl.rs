@@ -0,0 +1,373 @@
+//! Measured materials from the MERL BRDF database, for checking analytic materials against real
+//! ones. Each `.binary` file tabulates an isotropic BRDF over the half and difference angles of
+//! the two directions (Rusinkiewicz's parameterisation).
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::WorldVec;
+
+use std::io::Read;
+
+const THETA_HALF_BINS: usize = 90;
+const THETA_DIFF_BINS: usize = 90;
+const PHI_DIFF_BINS: usize = 180;
+const ENTRIES: usize = THETA_HALF_BINS * THETA_DIFF_BINS * PHI_DIFF_BINS;
+
+/// The tables are stored scaled by these, for red, green and blue.
+const SCALE: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+/// How finely directions are tabulated for importance sampling: the angle of the outgoing
+/// direction to the normal, and the two coordinates of the incoming one.
+const OUTGOING_BINS: usize = 16;
+const U_BINS: usize = 16;
+const V_BINS: usize = 32;
+
+/// How much of the time to sample uniformly (by the cosine) rather than from the tables, so that
+/// directions the tables missed still get some samples.
+const UNIFORM_FRACTION: f64 = 0.2;
+
+const HALF_PI: f64 = std::f64::consts::FRAC_PI_2;
+
+fn rotate_z(v: WorldVec, angle: f64) -> WorldVec {
+    let (sin, cos) = angle.sin_cos();
+    WorldVec::new(v.0 * cos - v.1 * sin, v.0 * sin + v.1 * cos, v.2)
+}
+
+fn rotate_y(v: WorldVec, angle: f64) -> WorldVec {
+    let (sin, cos) = angle.sin_cos();
+    WorldVec::new(v.0 * cos + v.2 * sin, v.1, -v.0 * sin + v.2 * cos)
+}
+
+/// The bin of `value` out of `bins` equal bins between 0 and `range`.
+fn bin(value: f64, range: f64, bins: usize) -> usize {
+    ((value / range * bins as f64).max(0.0) as usize).min(bins - 1)
+}
+
+/// The cosine-weighted map from the unit square to the hemisphere, and back.
+fn square_to_hemisphere((u, v): (f64, f64)) -> WorldVec {
+    let r = u.sqrt();
+    let phi = 2.0 * std::f64::consts::PI * v;
+    WorldVec::new(r * phi.cos(), r * phi.sin(), (1.0 - u).max(0.0).sqrt())
+}
+
+fn hemisphere_to_square(w: WorldVec) -> (f64, f64) {
+    let v = w.1.atan2(w.0) / (2.0 * std::f64::consts::PI);
+    ((w.0 * w.0 + w.1 * w.1).min(1.0), v.rem_euclid(1.0))
+}
+
+/// A piecewise-constant distribution over the unit square, for sampling incoming directions.
+#[derive(Debug)]
+struct Table {
+    /// The density in each cell, row (u) by row. It averages 1.
+    density: Vec<f64>,
+    /// The cumulative probability of each row, and of each cell within its row.
+    row_cdf: Vec<f64>,
+    cell_cdf: Vec<f64>,
+}
+
+impl Table {
+    fn new(weights: Vec<f64>) -> Table {
+        let total: f64 = weights.iter().sum();
+        let weights = if total > 0.0 {
+            weights
+        } else {
+            vec![1.0; U_BINS * V_BINS]
+        };
+        let total: f64 = weights.iter().sum();
+        let density = weights.iter().map(|w| w / total * (U_BINS * V_BINS) as f64).collect();
+        let mut row_cdf = Vec::with_capacity(U_BINS);
+        let mut cell_cdf = Vec::with_capacity(U_BINS * V_BINS);
+        let mut running = 0.0;
+        for row in weights.chunks(V_BINS) {
+            let row_total: f64 = row.iter().sum();
+            running += row_total / total;
+            row_cdf.push(running);
+            let mut within = 0.0;
+            for w in row {
+                within += if row_total > 0.0 { w / row_total } else { 1.0 / V_BINS as f64 };
+                cell_cdf.push(within);
+            }
+        }
+        Table {
+            density,
+            row_cdf,
+            cell_cdf,
+        }
+    }
+
+    /// A point in the unit square, drawn in proportion to the density.
+    fn sample(&self) -> (f64, f64) {
+        let pick = |cdf: &[f64]| {
+            let u = microfacet::random();
+            cdf.iter().position(|&c| u < c).unwrap_or(cdf.len() - 1)
+        };
+        let row = pick(&self.row_cdf);
+        let cell = pick(&self.cell_cdf[row * V_BINS..(row + 1) * V_BINS]);
+        (
+            (row as f64 + microfacet::random()) / U_BINS as f64,
+            (cell as f64 + microfacet::random()) / V_BINS as f64,
+        )
+    }
+
+    fn density(&self, (u, v): (f64, f64)) -> f64 {
+        self.density[bin(u, 1.0, U_BINS) * V_BINS + bin(v, 1.0, V_BINS)]
+    }
+}
+
+/// A measured isotropic BRDF.
+pub struct Merl {
+    /// The BRDF for red, green and blue, unscaled, in the file's order.
+    values: Vec<[f32; 3]>,
+    /// Tables for sampling incoming directions, for each bin of outgoing angle.
+    tables: Vec<Table>,
+}
+
+impl std::fmt::Debug for Merl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // The table has millions of entries.
+        f.debug_struct("Merl").field("entries", &self.values.len()).finish()
+    }
+}
+
+impl Merl {
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Merl> {
+        Merl::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    /// Read a `.binary` file: three little-endian 32-bit dimensions, which must be 90, 90 and
+    /// 180, followed by the red, green and blue tables as little-endian doubles.
+    pub fn read(mut reader: impl Read) -> std::io::Result<Merl> {
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        let dimension = |i: usize| i32::from_le_bytes([header[i], header[i + 1], header[i + 2], header[i + 3]]);
+        let dimensions = (dimension(0), dimension(4), dimension(8));
+        if dimensions != (THETA_HALF_BINS as i32, THETA_DIFF_BINS as i32, PHI_DIFF_BINS as i32) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Unexpected MERL dimensions {:?}", dimensions),
+            ));
+        }
+        let mut data = vec![0; ENTRIES * 3 * 8];
+        reader.read_exact(&mut data)?;
+        let mut values = vec![[0.0f32; 3]; ENTRIES];
+        for (i, bytes) in data.chunks_exact(8).enumerate() {
+            let mut double = [0; 8];
+            double.copy_from_slice(bytes);
+            let (channel, entry) = (i / ENTRIES, i % ENTRIES);
+            // Missing measurements are negative.
+            values[entry][channel] = (f64::from_le_bytes(double) * SCALE[channel]).max(0.0) as f32;
+        }
+        Ok(Merl::new(values))
+    }
+
+    fn new(values: Vec<[f32; 3]>) -> Merl {
+        let mut merl = Merl {
+            values,
+            tables: Vec::new(),
+        };
+        merl.tables = (0..OUTGOING_BINS)
+            .map(|o| {
+                let theta = (o as f64 + 0.5) / OUTGOING_BINS as f64 * HALF_PI;
+                let wo = WorldVec::new(theta.sin(), 0.0, theta.cos());
+                let mut weights = Vec::with_capacity(U_BINS * V_BINS);
+                for u in 0..U_BINS {
+                    for v in 0..V_BINS {
+                        let centre = ((u as f64 + 0.5) / U_BINS as f64, (v as f64 + 0.5) / V_BINS as f64);
+                        let [r, g, b] = merl.evaluate(square_to_hemisphere(centre), wo);
+                        weights.push(colour::luminance(r, g, b));
+                    }
+                }
+                Table::new(weights)
+            })
+            .collect();
+        merl
+    }
+
+    /// The BRDF between two local directions, both above the surface, for red, green and blue.
+    pub fn evaluate(&self, wi: WorldVec, wo: WorldVec) -> [f64; 3] {
+        // Find the half vector, and the incoming direction relative to it.
+        let h = (wi + wo).normalised();
+        let theta_half = h.2.clamp(-1.0, 1.0).acos();
+        let phi_half = h.1.atan2(h.0);
+        let diff = rotate_y(rotate_z(wi, -phi_half), -theta_half);
+        let theta_diff = diff.2.clamp(-1.0, 1.0).acos();
+        // The BRDF is reciprocal, so only half of the difference azimuths are stored.
+        let phi_diff = diff.1.atan2(diff.0);
+        let phi_diff = if phi_diff < 0.0 { phi_diff + std::f64::consts::PI } else { phi_diff };
+
+        // Half angles are binned more finely near the normal, where highlights change quickly.
+        let half_index = bin((theta_half / HALF_PI).sqrt(), 1.0, THETA_HALF_BINS);
+        let diff_index = bin(theta_diff, HALF_PI, THETA_DIFF_BINS);
+        let phi_index = bin(phi_diff, std::f64::consts::PI, PHI_DIFF_BINS);
+        let [r, g, b] = self.values[(half_index * THETA_DIFF_BINS + diff_index) * PHI_DIFF_BINS + phi_index];
+        [r as f64, g as f64, b as f64]
+    }
+
+    /// The table for sampling light to `wo`, and the rotation about the normal which takes its
+    /// outgoing direction (in the xz plane) to `wo`.
+    fn table(&self, wo: WorldVec) -> (&Table, f64) {
+        let theta = wo.2.clamp(-1.0, 1.0).acos();
+        (&self.tables[bin(theta, HALF_PI, OUTGOING_BINS)], wo.1.atan2(wo.0))
+    }
+
+    /// The probability density of sampling `wi` for `wo`, relative to sampling by the cosine.
+    fn relative_density(&self, wi: WorldVec, wo: WorldVec) -> f64 {
+        let (table, phi) = self.table(wo);
+        let point = hemisphere_to_square(rotate_z(wi, -phi));
+        UNIFORM_FRACTION + (1.0 - UNIFORM_FRACTION) * table.density(point)
+    }
+
+    /// The weight of light from `wi` to `wo`, when `wi` was sampled by `sample_gathering_ray`.
+    fn weight(&self, wi: WorldVec, wo: WorldVec) -> [f64; 3] {
+        // Sampling by the cosine alone would give a weight of pi times the BRDF.
+        let scale = std::f64::consts::PI / self.relative_density(wi, wo);
+        let [r, g, b] = self.evaluate(wi, wo);
+        [r * scale, g * scale, b * scale]
+    }
+}
+
+impl material::Material for Merl {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        let (table, phi) = self.table(wo);
+        let point = if microfacet::random() < UNIFORM_FRACTION {
+            (microfacet::random(), microfacet::random())
+        } else {
+            table.sample()
+        };
+        ray::Ray::new(
+            *hit.intersected_surface_normal.get_origin(),
+            frame.to_world(rotate_z(square_to_hemisphere(point), phi)),
+        )
+    }
+
+    fn colour(&self, start_colour: colour::Colour, _hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        // Without the viewing direction, use the reflectance seen head on.
+        let up = WorldVec::new(0.0, 0.0, 1.0);
+        let [r, g, b] = self.evaluate(up, up);
+        let pi = std::f64::consts::PI;
+        material::attenuate(&start_colour, &colour::Colour::unclamped(r * pi, g * pi, b * pi))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wi.2 <= 0.0 || wo.2 <= 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let [r, g, b] = self.weight(wi, wo);
+        material::attenuate(&start_colour, &colour::Colour::unclamped(r, g, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lambertian;
+    use crate::sampler;
+    use material::Material;
+
+    /// A `.binary` file with the same value everywhere in each channel.
+    fn constant_file(brdf: [f64; 3]) -> Vec<u8> {
+        let mut file = Vec::with_capacity(12 + ENTRIES * 24);
+        for &dimension in &[90i32, 90, 180] {
+            file.extend_from_slice(&dimension.to_le_bytes());
+        }
+        for channel in 0..3 {
+            let stored = (brdf[channel] / SCALE[channel]).to_le_bytes();
+            for _ in 0..ENTRIES {
+                file.extend_from_slice(&stored);
+            }
+        }
+        file
+    }
+
+    #[test]
+    fn test_tabulated_lambertian() {
+        sampler::replace_current(sampler::Sampler::new(8));
+        let albedo = colour::Colour::new(0.8, 0.5, 0.2);
+        let pi = std::f64::consts::PI;
+        let file = constant_file([0.8 / pi, 0.5 / pi, 0.2 / pi]);
+        let merl = std::rc::Rc::new(Merl::read(&file[..]).unwrap());
+        let lambertian = lambertian::Lambertian::new(albedo);
+
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        for &direction in &[WorldVec::new(0.0, 0.0, -1.0), WorldVec::new(0.7, 0.3, -0.4).normalised()] {
+            let from = ray::Ray::new(-direction, direction);
+            let hit = hit::MaterialHit::new(merl.clone(), &from, 1.0, WorldVec::new(0.0, 0.0, 1.0), (0.0, 0.0));
+            for _ in 0..200 {
+                let towards_light = merl.sample_gathering_ray(&from, &hit).get_direction().normalised();
+                assert!(towards_light.2 >= 0.0);
+                // Sampling a constant BRDF by the cosine leaves exactly the albedo.
+                let measured = merl.colour_between(white.clone(), &hit, towards_light, -direction);
+                let analytic = lambertian.colour_between(white.clone(), &hit, towards_light, -direction);
+                for (m, a) in [
+                    (measured.get_red(), analytic.get_red()),
+                    (measured.get_green(), analytic.get_green()),
+                    (measured.get_blue(), analytic.get_blue()),
+                ]
+                .iter()
+                {
+                    assert!((m - a).abs() < 1e-6, "{} {}", m, a);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_importance_sampling() {
+        sampler::replace_current(sampler::Sampler::new(9));
+        // A glossy BRDF: bright where the half vector is near the normal, dim elsewhere.
+        let values = (0..ENTRIES)
+            .map(|i| if i / (THETA_DIFF_BINS * PHI_DIFF_BINS) < 20 { [2.0f32; 3] } else { [0.02; 3] })
+            .collect();
+        let merl = std::rc::Rc::new(Merl::new(values));
+        let direction = WorldVec::new(0.5, 0.0, -1.0).normalised();
+        let from = ray::Ray::new(-direction, direction);
+        let hit = hit::MaterialHit::new(merl.clone(), &from, 1.0, WorldVec::new(0.0, 0.0, 1.0), (0.0, 0.0));
+        let wo = -direction;
+
+        // The reflectance, integrated over a fine grid...
+        let steps = 400;
+        let mut expected = 0.0;
+        for u in 0..steps {
+            for v in 0..steps {
+                let wi = square_to_hemisphere(((u as f64 + 0.5) / steps as f64, (v as f64 + 0.5) / steps as f64));
+                expected += merl.evaluate(wi, wo)[0] * std::f64::consts::PI / (steps * steps) as f64;
+            }
+        }
+        // ...matches the average weight of importance sampled directions, some of which are well
+        // above 1.
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let samples = 20000;
+        let weights: Vec<f64> = (0..samples)
+            .map(|_| {
+                let towards_light = merl.sample_gathering_ray(&from, &hit).get_direction().normalised();
+                merl.colour_between(white.clone(), &hit, towards_light, wo).get_red()
+            })
+            .collect();
+        assert!(weights.iter().any(|&weight| weight > 1.5));
+        let mean = weights.iter().sum::<f64>() / samples as f64;
+        assert!((mean - expected).abs() < 0.05 * expected, "{} {}", mean, expected);
+    }
+
+    #[test]
+    fn test_read_errors() {
+        let mut file = constant_file([0.1; 3]);
+        file.truncate(1000);
+        assert!(Merl::read(&file[..]).is_err());
+        file[8] = 90;
+        assert!(Merl::read(&file[..]).is_err());
+    }
+}
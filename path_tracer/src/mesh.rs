@@ -0,0 +1,278 @@
+use crate::aabb;
+use crate::bvh;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::WorldVec;
+
+/// A triangle mesh with its own BVH. To place the same mesh in the world many times, wrap it in
+/// an `Rc` and add it to the scene through several `Instance`s -- the triangles and BVH are then
+/// shared between all of them.
+#[derive(Debug)]
+pub struct Mesh {
+    vertices: Vec<WorldVec>,
+    triangles: Vec<[usize; 3]>,
+    material: std::rc::Rc<dyn material::Material>,
+    tree: bvh::BvhTree,
+    /// Texture coordinates for each vertex, if the mesh has them.
+    uvs: Option<Vec<(f64, f64)>>,
+    /// For each vertex, the direction of increasing u and the handedness of the UVs there. Only
+    /// present along with `uvs`.
+    tangents: Vec<(WorldVec, f64)>,
+}
+
+impl Mesh {
+    /// Create a mesh from a list of vertices and triangles indexing into them. Triangles should be
+    /// wound anticlockwise when viewed from outside of the mesh, so that their normals face out.
+    /// Panics if a triangle refers to a vertex which doesn't exist.
+    pub fn new(
+        vertices: Vec<WorldVec>,
+        triangles: Vec<[usize; 3]>,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Mesh {
+        let bounds: Vec<_> = triangles
+            .iter()
+            .map(|triangle| {
+                aabb::Aabb::from_points(&[
+                    vertices[triangle[0]],
+                    vertices[triangle[1]],
+                    vertices[triangle[2]],
+                ])
+            })
+            .collect();
+        Mesh {
+            tree: bvh::BvhTree::build(&bounds),
+            vertices,
+            triangles,
+            material,
+            uvs: None,
+            tangents: Vec::new(),
+        }
+    }
+
+    /// Give each vertex texture coordinates. Hits then interpolate these, along with tangents
+    /// worked out from them for normal mapping. Panics if there isn't one UV for each vertex.
+    pub fn with_uvs(mut self, uvs: Vec<(f64, f64)>) -> Mesh {
+        assert_eq!(uvs.len(), self.vertices.len(), "There must be a UV for each vertex.");
+        self.tangents = vertex_tangents(&self.vertices, &self.triangles, &uvs);
+        self.uvs = Some(uvs);
+        self
+    }
+
+    pub fn vertices(&self) -> &[WorldVec] {
+        &self.vertices
+    }
+
+    pub fn triangles(&self) -> &[[usize; 3]] {
+        &self.triangles
+    }
+
+    /// Intersect `from` with a single triangle, using the Möller-Trumbore algorithm. Returns the
+    /// `t` of the intersection, the triangle's normal and the barycentric coordinates of the hit.
+    fn intersect_triangle(
+        &self,
+        index: usize,
+        from: &ray::Ray,
+    ) -> Option<(f64, WorldVec, (f64, f64))> {
+        let [a, b, c] = self.triangles[index];
+        let (a, b, c) = (self.vertices[a], self.vertices[b], self.vertices[c]);
+        let edge1 = b - a;
+        let edge2 = c - a;
+        let direction = *from.get_direction();
+        let p = direction.cross(edge2);
+        let determinant = edge1.dot(p);
+        if determinant.abs() < 1e-12 {
+            // The ray is parallel to the triangle.
+            return None;
+        }
+        let inverse_determinant = 1.0 / determinant;
+        let to_origin = *from.get_origin() - a;
+        let u = to_origin.dot(p) * inverse_determinant;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = to_origin.cross(edge1);
+        let v = direction.dot(q) * inverse_determinant;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = edge2.dot(q) * inverse_determinant;
+        if !from.in_range(t) {
+            return None;
+        }
+        Some((t, edge1.cross(edge2).normalised(), (u, v)))
+    }
+}
+
+impl hit::Hit for Mesh {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let (t, (index, normal, uv)) = self.tree.closest_hit(from, |index, t_max| {
+            let from = from.with_t_range(from.get_t_min(), t_max);
+            let (t, normal, uv) = self.intersect_triangle(index, &from)?;
+            Some((t, (index, normal, uv)))
+        })?;
+        let [a, b, c] = self.triangles[index];
+        let (u, v) = uv;
+        let weights = [1.0 - u - v, u, v];
+        let material_hit = match &self.uvs {
+            Some(uvs) => {
+                let interpolate = |value: &dyn Fn(usize) -> WorldVec| {
+                    value(a) * weights[0] + value(b) * weights[1] + value(c) * weights[2]
+                };
+                let uv = interpolate(&|i| WorldVec::new(uvs[i].0, uvs[i].1, 0.0));
+                let tangent = interpolate(&|i| self.tangents[i].0);
+                hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, (uv.0, uv.1))
+                    .with_tangent(tangent)
+                    .with_handedness(self.tangents[a].1)
+            }
+            // Without UVs, use the barycentric coordinates; u runs along the first edge.
+            None => hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, normal, uv)
+                .with_tangent(self.vertices[b] - self.vertices[a]),
+        };
+        Some(material_hit.with_primitive_id(index))
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        self.tree.bounds()
+    }
+}
+
+/// Per-vertex tangents, in the manner of MikkTSpace: each triangle's tangent and bitangent (the
+/// directions of increasing u and v) are summed at its corners, weighted by the angle there. Each
+/// vertex's tangent is then made perpendicular to its normal, and its handedness is whether the
+/// bitangent agrees with `normal x tangent`.
+fn vertex_tangents(
+    vertices: &[WorldVec],
+    triangles: &[[usize; 3]],
+    uvs: &[(f64, f64)],
+) -> Vec<(WorldVec, f64)> {
+    let zero = WorldVec::new(0.0, 0.0, 0.0);
+    let mut normals = vec![zero; vertices.len()];
+    let mut tangents = vec![zero; vertices.len()];
+    let mut bitangents = vec![zero; vertices.len()];
+    for triangle in triangles {
+        let [a, b, c] = *triangle;
+        let (edge1, edge2) = (vertices[b] - vertices[a], vertices[c] - vertices[a]);
+        let (du1, dv1) = (uvs[b].0 - uvs[a].0, uvs[b].1 - uvs[a].1);
+        let (du2, dv2) = (uvs[c].0 - uvs[a].0, uvs[c].1 - uvs[a].1);
+        let area = du1 * dv2 - du2 * dv1;
+        let normal = edge1.cross(edge2).normalised();
+        let (tangent, bitangent) = if area.abs() > 1e-12 {
+            (
+                (edge1 * dv2 - edge2 * dv1) * (1.0 / area),
+                (edge2 * du1 - edge1 * du2) * (1.0 / area),
+            )
+        } else {
+            // The UVs are degenerate, so there's no sensible tangent.
+            (zero, zero)
+        };
+        for corner in 0..3 {
+            let vertex = triangle[corner];
+            let to_next = vertices[triangle[(corner + 1) % 3]] - vertices[vertex];
+            let to_previous = vertices[triangle[(corner + 2) % 3]] - vertices[vertex];
+            let cos = to_next.normalised().dot(to_previous.normalised());
+            let angle = cos.clamp(-1.0, 1.0).acos();
+            if !angle.is_finite() {
+                continue;
+            }
+            normals[vertex] = normals[vertex] + normal * angle;
+            tangents[vertex] = tangents[vertex] + tangent * angle;
+            bitangents[vertex] = bitangents[vertex] + bitangent * angle;
+        }
+    }
+    normals
+        .into_iter()
+        .zip(tangents)
+        .zip(bitangents)
+        .map(|((normal, tangent), bitangent)| {
+            let tangent = tangent - normal * (normal.dot(tangent) / normal.dot(normal));
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 { -1.0 } else { 1.0 };
+            (tangent, handedness)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    /// A cube from (0, 0, 0) to (1, 1, 1).
+    fn cube() -> Mesh {
+        let mut vertices = Vec::new();
+        for &x in &[0.0, 1.0] {
+            for &y in &[0.0, 1.0] {
+                for &z in &[0.0, 1.0] {
+                    vertices.push(WorldVec::new(x, y, z));
+                }
+            }
+        }
+        // Vertex i has x = bit 2, y = bit 1, z = bit 0.
+        let triangles = vec![
+            [0, 1, 3], [0, 3, 2], // x = 0
+            [4, 6, 7], [4, 7, 5], // x = 1
+            [0, 4, 5], [0, 5, 1], // y = 0
+            [2, 3, 7], [2, 7, 6], // y = 1
+            [0, 2, 6], [0, 6, 4], // z = 0
+            [1, 5, 7], [1, 7, 3], // z = 1
+        ];
+        Mesh::new(
+            vertices,
+            triangles,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        )
+    }
+
+    #[test]
+    fn test_hit_mesh() {
+        let cube = cube();
+        let ray = ray::Ray::new(WorldVec::new(0.5, 0.25, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = cube.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(0.5, 0.25, 0.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, 0.0, -1.0));
+
+        // Every face's normal should point out of the cube.
+        let centre = WorldVec::new(0.5, 0.5, 0.5);
+        for index in 0..cube.triangles().len() {
+            let [a, b, c] = cube.triangles()[index];
+            let vertices = cube.vertices();
+            let face_centre = (vertices[a] + vertices[b] + vertices[c]) * (1.0 / 3.0);
+            let ray = ray::Ray::new(centre, (face_centre - centre).normalised());
+            let (_, normal, _) = cube.intersect_triangle(index, &ray).unwrap();
+            assert!(normal.dot(*ray.get_direction()) > 0.0);
+        }
+
+        let ray = ray::Ray::new(WorldVec::new(1.5, 0.25, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(cube.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_uv_tangents() {
+        // A unit square in the z = 0 plane, facing -z, with u along x and v along y.
+        let vertices = vec![
+            WorldVec::new(0.0, 0.0, 0.0),
+            WorldVec::new(1.0, 0.0, 0.0),
+            WorldVec::new(1.0, 1.0, 0.0),
+            WorldVec::new(0.0, 1.0, 0.0),
+        ];
+        let material: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        let square = |uvs: Vec<(f64, f64)>| {
+            Mesh::new(vertices.clone(), vec![[0, 2, 1], [0, 3, 2]], material.clone()).with_uvs(uvs)
+        };
+        let ray = ray::Ray::new(WorldVec::new(0.25, 0.75, -1.0), WorldVec::new(0.0, 0.0, 1.0));
+
+        let hit = square(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]).hit(&ray).unwrap();
+        assert!((hit.uv.0 - 0.25).abs() < 1e-9 && (hit.uv.1 - 0.75).abs() < 1e-9);
+        assert!((hit.tangent - WorldVec::new(1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((hit.bitangent() - WorldVec::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        // Mirroring the texture left to right flips the tangent, but v still runs up.
+        let hit = square(vec![(1.0, 0.0), (0.0, 0.0), (0.0, 1.0), (1.0, 1.0)]).hit(&ray).unwrap();
+        assert!((hit.tangent - WorldVec::new(-1.0, 0.0, 0.0)).length() < 1e-9);
+        assert!((hit.bitangent() - WorldVec::new(0.0, 1.0, 0.0)).length() < 1e-9);
+    }
+}
@@ -1,15 +1,28 @@
 use crate::colour;
+use crate::hit;
 use crate::material;
 use crate::ray;
+use crate::texture;
 
 #[derive(Debug)]
 pub struct Metal {
-    colour: colour::Colour,
-    fuzziness: f32,
+    colour: std::rc::Rc<dyn texture::Texture>,
+    fuzziness: std::rc::Rc<dyn texture::Texture>,
 }
 
 impl Metal {
     pub fn new(colour: colour::Colour, fuzziness: f32) -> Metal {
+        Metal::textured(
+            std::rc::Rc::new(texture::Constant::new(colour)),
+            std::rc::Rc::new(texture::Constant::scalar(fuzziness as f64)),
+        )
+    }
+
+    /// A metal whose colour and fuzziness vary across it. The fuzziness is read as a scalar.
+    pub fn textured(
+        colour: std::rc::Rc<dyn texture::Texture>,
+        fuzziness: std::rc::Rc<dyn texture::Texture>,
+    ) -> Metal {
         Metal { colour, fuzziness }
     }
 }
@@ -18,8 +31,9 @@ impl material::Material for Metal {
     fn sample_gathering_ray(
         &self,
         reflected_ray: &ray::Ray,
-        surface_normal: &ray::Ray,
+        hit: &hit::MaterialHit,
     ) -> ray::Ray {
+        let surface_normal = ray::Ray::new(*hit.intersected_surface_normal.get_origin(), hit.shading_normal);
         // Metals reflect the ray with the same angle of reflection as angle of incidence (relative
         // to the normal). We know the direction of the incident ray (we're tracing in reverse)
         // should be o + reflected_ray + 2 * |reflected_ray| * cos(theta) * N, where o represents
@@ -34,20 +48,16 @@ impl material::Material for Metal {
                         .get_direction()
                         .normalised()
                         .dot(*reflected_ray.get_direction())
-                    * 2.0).normalised() + material::random_unit_vector_in_sphere() * self.fuzziness as f64,
+                    * 2.0).normalised() + material::random_unit_vector_in_sphere() * self.fuzziness.scalar(hit),
         )
     }
     fn colour(
         &self,
         start_colour: colour::Colour,
-        _surface_normal: &ray::Ray,
+        hit: &hit::MaterialHit,
         _angle_of_incidence: f64,
     ) -> colour::Colour {
-        colour::Colour::new(
-            self.colour.get_red() * start_colour.get_red(),
-            self.colour.get_green() * start_colour.get_green(),
-            self.colour.get_blue() * start_colour.get_blue(),
-        )
+        material::attenuate(&start_colour, &self.colour.colour(hit))
     }
 }
 
@@ -60,12 +70,19 @@ mod tests {
     fn test_reflected_ray() {
         use material::Material;
 
-        let metal = Metal::new(colour::Colour::new(1.0, 1.0, 1.0), 0.0);
+        let metal = std::rc::Rc::new(Metal::new(colour::Colour::new(1.0, 1.0, 1.0), 0.0));
         // We expect that the angle of incidence == angle of reflectance.
         let normal = ray::Ray::new(
             vec3::Vec3::new(0.0, 0.0, 0.0),
             vec3::Vec3::new(0.0, 1.0, 0.0).normalised(),
         );
+        let hit = hit::MaterialHit::new(
+            metal.clone(),
+            &ray::Ray::new(vec3::Vec3::new(0.0, 1.0, 0.0), vec3::Vec3::new(0.0, -1.0, 0.0)),
+            1.0,
+            *normal.get_direction(),
+            (0.0, 0.0),
+        );
         use rand::distributions::{Distribution, Uniform};
         let between = Uniform::from(-1.0..=1.0);
 
@@ -86,7 +103,7 @@ mod tests {
                 .dot(normal.get_direction().normalised());
 
             // Now, send a ray to bounce off the metal.
-            let reflected_ray = metal.sample_gathering_ray(&incident_ray, &normal);
+            let reflected_ray = metal.sample_gathering_ray(&incident_ray, &hit);
             let cos_reflected_ray = reflected_ray
                 .get_direction()
                 .normalised()
@@ -0,0 +1,487 @@
+//! Physically based glossy materials, modelling the surface as a field of tiny mirrors (microfacets)
+//! whose normals follow the GGX (Trowbridge-Reitz) distribution. Directions are sampled from the
+//! distribution of normals visible from the viewer, which keeps the weight of each sample close
+//! to 1 and never sends rays below the facets it bounced off.
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::sampler;
+use crate::texture;
+use crate::WorldVec;
+
+/// Roughnesses below this are treated as this, as a perfectly smooth GGX surface is a delta
+/// function which the sampling can't represent.
+const MIN_ALPHA: f64 = 1e-4;
+
+/// A uniform random number in [0, 1).
+pub(crate) fn random() -> f64 {
+    use rand::distributions::{Distribution, Uniform};
+    sampler::with_current(|rng| Uniform::from(0.0..1.0).sample(rng))
+}
+
+/// An orthonormal basis around a surface's shading normal, which is z in local space. The normal
+/// faces whichever side of the surface the viewer is on.
+#[derive(Debug, Copy, Clone)]
+pub struct Frame {
+    pub tangent: WorldVec,
+    pub bitangent: WorldVec,
+    pub normal: WorldVec,
+}
+
+impl Frame {
+    pub fn new(hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Frame {
+        let geometric = *hit.intersected_surface_normal.get_direction();
+        let side = if geometric.dot(towards_viewer) < 0.0 { -1.0 } else { 1.0 };
+        let normal = hit.shading_normal.normalised() * side;
+        let tangent = hit.tangent - normal * normal.dot(hit.tangent);
+        let tangent = if tangent.length() > 1e-9 {
+            tangent.normalised()
+        } else {
+            normal.tangent_basis().0
+        };
+        Frame {
+            tangent,
+            bitangent: normal.cross(tangent),
+            normal,
+        }
+    }
+
+    pub fn to_local(&self, v: WorldVec) -> WorldVec {
+        WorldVec::new(v.dot(self.tangent), v.dot(self.bitangent), v.dot(self.normal))
+    }
+
+    pub fn to_world(&self, v: WorldVec) -> WorldVec {
+        self.tangent * v.0 + self.bitangent * v.1 + self.normal * v.2
+    }
+}
+
+/// A GGX distribution of microfacet normals, possibly stretched along the tangent or bitangent.
+/// Directions are in a local space where the surface normal is z.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ggx {
+    pub alpha_x: f64,
+    pub alpha_y: f64,
+}
+
+impl Ggx {
+    /// Map a perceptual roughness (0 for a mirror, 1 for very rough) and anisotropy (0 for none,
+    /// towards 1 for highlights stretched along the tangent) to a distribution, as Disney do.
+    pub fn from_roughness(roughness: f64, anisotropy: f64) -> Ggx {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        Ggx {
+            alpha_x: (alpha / aspect).max(MIN_ALPHA),
+            alpha_y: (alpha * aspect).max(MIN_ALPHA),
+        }
+    }
+
+    /// The density of microfacet normals around `h`, per unit area of the macro surface.
+    pub fn distribution(&self, h: WorldVec) -> f64 {
+        if h.2 <= 0.0 {
+            return 0.0;
+        }
+        let (x, y) = (h.0 / self.alpha_x, h.1 / self.alpha_y);
+        let denominator = x * x + y * y + h.2 * h.2;
+        1.0 / (std::f64::consts::PI * self.alpha_x * self.alpha_y * denominator * denominator)
+    }
+
+    /// Smith's lambda function, from which the shadowing terms are built.
+    fn lambda(&self, w: WorldVec) -> f64 {
+        let (x, y) = (w.0 * self.alpha_x, w.1 * self.alpha_y);
+        let tan2 = (x * x + y * y) / (w.2 * w.2);
+        if !tan2.is_finite() {
+            return f64::INFINITY;
+        }
+        ((1.0 + tan2).sqrt() - 1.0) * 0.5
+    }
+
+    /// The fraction of facets facing `w` which aren't hidden from it by other facets.
+    pub fn masking(&self, w: WorldVec) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// The fraction of facets which can see both `a` and `b` (the height-correlated form).
+    pub fn masking_shadowing(&self, a: WorldVec, b: WorldVec) -> f64 {
+        1.0 / (1.0 + self.lambda(a) + self.lambda(b))
+    }
+
+    /// Sample a microfacet normal visible from `towards_viewer` (which must be above the surface),
+    /// using Heitz's method: stretch the view into the space where the distribution is a
+    /// hemisphere, sample the projected disc, then unstretch.
+    pub fn sample_visible_normal(&self, towards_viewer: WorldVec, (u1, u2): (f64, f64)) -> WorldVec {
+        let v = WorldVec::new(
+            self.alpha_x * towards_viewer.0,
+            self.alpha_y * towards_viewer.1,
+            towards_viewer.2,
+        )
+        .normalised();
+        let length2 = v.0 * v.0 + v.1 * v.1;
+        let t1 = if length2 > 0.0 {
+            WorldVec::new(-v.1, v.0, 0.0) * (1.0 / length2.sqrt())
+        } else {
+            WorldVec::new(1.0, 0.0, 0.0)
+        };
+        let t2 = v.cross(t1);
+
+        let r = u1.sqrt();
+        let phi = 2.0 * std::f64::consts::PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.2);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * r * phi.sin();
+        let n = t1 * p1 + t2 * p2 + v * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+        WorldVec::new(self.alpha_x * n.0, self.alpha_y * n.1, n.2.max(0.0)).normalised()
+    }
+}
+
+/// Mirror `towards_viewer` about a facet with normal `h`.
+pub(crate) fn reflect(towards_viewer: WorldVec, h: WorldVec) -> WorldVec {
+    h * (2.0 * towards_viewer.dot(h)) - towards_viewer
+}
+
+/// Refract `towards_viewer` through a facet with normal `h` (on the viewer's side), where `eta` is
+/// the ratio of the refractive index beyond the facet to the one on the viewer's side. Returns
+/// `None` on total internal reflection.
+pub(crate) fn refract(towards_viewer: WorldVec, h: WorldVec, eta: f64) -> Option<WorldVec> {
+    let cos_i = towards_viewer.dot(h);
+    let sin2_t = (1.0 - cos_i * cos_i).max(0.0) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some((-towards_viewer * (1.0 / eta) + h * (cos_i / eta - cos_t)).normalised())
+}
+
+/// The Fresnel reflectance of a dielectric, for light meeting it at `cos_i` to the normal. `eta` is
+/// the ratio of the refractive index on the far side to the near side.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let s = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let p = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (s * s + p * p) * 0.5
+}
+
+/// The Fresnel reflectance of a conductor with complex refractive index `eta + ik`.
+pub fn fresnel_conductor(cos_i: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_i.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_i.clamp(0.0, 1.0) * a;
+    let s = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let p = s * (t3 - t4) / (t3 + t4);
+    (s + p) * 0.5
+}
+
+/// A rough metal, described by its complex refractive index for red, green and blue light.
+#[derive(Debug)]
+pub struct Conductor {
+    eta: [f64; 3],
+    k: [f64; 3],
+    roughness: std::rc::Rc<dyn texture::Texture>,
+    anisotropy: f64,
+}
+
+impl Conductor {
+    /// `roughness` is read as a scalar, from 0 (a mirror) to 1. `anisotropy` stretches highlights
+    /// along the surface's tangent, from 0 (not at all) towards 1.
+    pub fn new(
+        eta: [f64; 3],
+        k: [f64; 3],
+        roughness: std::rc::Rc<dyn texture::Texture>,
+        anisotropy: f64,
+    ) -> Conductor {
+        Conductor {
+            eta,
+            k,
+            roughness,
+            anisotropy,
+        }
+    }
+
+    pub fn gold(roughness: std::rc::Rc<dyn texture::Texture>) -> Conductor {
+        Conductor::new([0.143, 0.374, 1.442], [3.983, 2.385, 1.603], roughness, 0.0)
+    }
+
+    pub fn silver(roughness: std::rc::Rc<dyn texture::Texture>) -> Conductor {
+        Conductor::new([0.155, 0.117, 0.138], [4.828, 3.122, 2.147], roughness, 0.0)
+    }
+
+    pub fn copper(roughness: std::rc::Rc<dyn texture::Texture>) -> Conductor {
+        Conductor::new([0.200, 0.924, 1.102], [3.912, 2.452, 2.142], roughness, 0.0)
+    }
+
+    pub fn aluminium(roughness: std::rc::Rc<dyn texture::Texture>) -> Conductor {
+        Conductor::new([1.657, 0.880, 0.521], [9.224, 6.270, 4.837], roughness, 0.0)
+    }
+
+    pub fn iron(roughness: std::rc::Rc<dyn texture::Texture>) -> Conductor {
+        Conductor::new([2.911, 2.950, 2.585], [3.089, 2.932, 2.767], roughness, 0.0)
+    }
+
+    pub fn set_anisotropy(&mut self, anisotropy: f64) {
+        self.anisotropy = anisotropy;
+    }
+
+    fn ggx(&self, hit: &hit::MaterialHit) -> Ggx {
+        Ggx::from_roughness(self.roughness.scalar(hit), self.anisotropy)
+    }
+}
+
+impl material::Material for Conductor {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        // Seen exactly edge on (or from behind the shading normal), just mirror the ray.
+        let h = if wo.2 > 0.0 {
+            self.ggx(hit).sample_visible_normal(wo, (random(), random()))
+        } else {
+            WorldVec::new(0.0, 0.0, 1.0)
+        };
+        ray::Ray::new(
+            *hit.intersected_surface_normal.get_origin(),
+            frame.to_world(reflect(wo, h)),
+        )
+    }
+
+    fn colour(&self, start_colour: colour::Colour, _hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        // Without the directions, fall back on the reflectance seen head on.
+        let tint = |channel: usize| fresnel_conductor(1.0, self.eta[channel], self.k[channel]);
+        material::attenuate(&start_colour, &colour::Colour::new(tint(0), tint(1), tint(2)))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wi.2 <= 0.0 || wo.2 <= 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        // Sampling visible normals leaves just the Fresnel term and the shadowing which the
+        // sampling didn't account for.
+        let ggx = self.ggx(hit);
+        let h = (wi + wo).normalised();
+        let shadowing = ggx.masking_shadowing(wi, wo) / ggx.masking(wo);
+        let weight = |channel: usize| fresnel_conductor(wo.dot(h), self.eta[channel], self.k[channel]) * shadowing;
+        material::attenuate(&start_colour, &colour::Colour::new(weight(0), weight(1), weight(2)))
+    }
+}
+
+/// Rough glass, or any other clear material: light is either reflected off or refracted through
+/// the microfacets, in proportion to the Fresnel reflectance.
+#[derive(Debug)]
+pub struct RoughDielectric {
+    /// The refractive index inside the material, relative to outside.
+    ior: f64,
+    roughness: std::rc::Rc<dyn texture::Texture>,
+    anisotropy: f64,
+    /// The colour of light passing through.
+    tint: std::rc::Rc<dyn texture::Texture>,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: f64, roughness: std::rc::Rc<dyn texture::Texture>) -> RoughDielectric {
+        RoughDielectric {
+            ior,
+            roughness,
+            anisotropy: 0.0,
+            tint: std::rc::Rc::new(texture::Constant::scalar(1.0)),
+        }
+    }
+
+    pub fn set_anisotropy(&mut self, anisotropy: f64) {
+        self.anisotropy = anisotropy;
+    }
+
+    pub fn set_tint(&mut self, tint: std::rc::Rc<dyn texture::Texture>) {
+        self.tint = tint;
+    }
+
+    /// The ratio of the refractive index across the surface to the one on the viewer's side.
+    fn eta(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> f64 {
+        let outside = hit.intersected_surface_normal.get_direction().dot(towards_viewer) >= 0.0;
+        if outside {
+            self.ior
+        } else {
+            1.0 / self.ior
+        }
+    }
+
+    fn ggx(&self, hit: &hit::MaterialHit) -> Ggx {
+        Ggx::from_roughness(self.roughness.scalar(hit), self.anisotropy)
+    }
+}
+
+impl material::Material for RoughDielectric {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        let h = if wo.2 > 0.0 {
+            self.ggx(hit).sample_visible_normal(wo, (random(), random()))
+        } else {
+            WorldVec::new(0.0, 0.0, 1.0)
+        };
+        let eta = self.eta(hit, towards_viewer);
+        let reflectance = fresnel_dielectric(wo.dot(h), eta);
+        let wi = if random() < reflectance {
+            reflect(wo, h)
+        } else {
+            refract(wo, h, eta).unwrap_or_else(|| reflect(wo, h))
+        };
+        ray::Ray::new(*hit.intersected_surface_normal.get_origin(), frame.to_world(wi))
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        material::attenuate(&start_colour, &self.tint.colour(hit))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wo.2 <= 0.0 || wi.2 == 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let ggx = self.ggx(hit);
+        // The choice between reflecting and refracting was made in proportion to the Fresnel
+        // term, so that cancels out, leaving the unsampled shadowing. The half vector of a
+        // refraction is found from the refractive indices.
+        let reflected = wi.2 > 0.0;
+        let h = if reflected {
+            wi + wo
+        } else {
+            wo + wi * self.eta(hit, towards_viewer)
+        };
+        let h = h.normalised();
+        let h = if h.2 < 0.0 { -h } else { h };
+        // Rays must stay on the side of each facet that the sampling put them on.
+        if wo.dot(h) <= 0.0 || (wi.dot(h) > 0.0) != reflected {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let wi_above = WorldVec::new(wi.0, wi.1, wi.2.abs());
+        let shadowing = ggx.masking_shadowing(wi_above, wo) / ggx.masking(wo);
+        let weight = colour::Colour::new(shadowing, shadowing, shadowing);
+        let weight = if reflected {
+            weight
+        } else {
+            material::attenuate(&weight, &self.tint.colour(hit))
+        };
+        material::attenuate(&start_colour, &weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{hit_on_plane, mean_weight};
+    use material::Material;
+
+    #[test]
+    fn test_ggx_normalised() {
+        // The projected area of all the microfacets is the area of the surface.
+        let ggx = Ggx::from_roughness(0.6, 0.5);
+        let steps = 400;
+        let mut total = 0.0;
+        for i in 0..steps {
+            let theta = (i as f64 + 0.5) / steps as f64 * std::f64::consts::FRAC_PI_2;
+            for j in 0..steps {
+                let phi = (j as f64 + 0.5) / steps as f64 * 2.0 * std::f64::consts::PI;
+                let h = WorldVec::new(theta.sin() * phi.cos(), theta.sin() * phi.sin(), theta.cos());
+                let solid_angle = theta.sin() * (std::f64::consts::FRAC_PI_2 / steps as f64)
+                    * (2.0 * std::f64::consts::PI / steps as f64);
+                total += ggx.distribution(h) * h.2 * solid_angle;
+            }
+        }
+        assert!((total - 1.0).abs() < 1e-2, "{}", total);
+
+        // Visible normals always face the viewer.
+        let viewer = WorldVec::new(0.8, 0.0, 0.6);
+        for i in 0..100 {
+            let h = ggx.sample_visible_normal(viewer, (i as f64 / 100.0, (i * 37 % 100) as f64 / 100.0));
+            assert!(h.dot(viewer) >= 0.0 && h.2 >= 0.0);
+        }
+    }
+
+    #[test]
+    fn test_fresnel() {
+        // Glass reflects about 4% head on, and everything at grazing angles.
+        assert!((fresnel_dielectric(1.0, 1.5) - 0.04).abs() < 1e-9);
+        assert!(fresnel_dielectric(0.0, 1.5) > 0.999);
+        // Total internal reflection, coming out of the glass at a shallow angle.
+        assert_eq!(fresnel_dielectric(0.2, 1.0 / 1.5), 1.0);
+        // With no absorption, the conductor formula agrees with the dielectric one.
+        assert!((fresnel_conductor(0.7, 1.5, 0.0) - fresnel_dielectric(0.7, 1.5)).abs() < 1e-9);
+        // Gold reflects red more than blue.
+        assert!(fresnel_conductor(1.0, 0.143, 3.983) > fresnel_conductor(1.0, 1.442, 1.603));
+    }
+
+    #[test]
+    fn test_conductor() {
+        sampler::replace_current(sampler::Sampler::new(1));
+        let direction = WorldVec::new(0.3, 0.0, -1.0).normalised();
+        // Energy is never created, and a perfect reflector loses only a little to shadowing.
+        let perfect = std::rc::Rc::new(Conductor::new(
+            [1.0; 3],
+            [1e6; 3],
+            std::rc::Rc::new(texture::Constant::scalar(0.5)),
+            0.0,
+        ));
+        let ([r, _, _], _) = mean_weight(perfect.clone(), direction, 2000);
+        assert!(r <= 1.0 && r > 0.9, "{}", r);
+
+        // Reflections from a mirror-like conductor leave at the mirror angle.
+        let mirror = Conductor::gold(std::rc::Rc::new(texture::Constant::scalar(0.0)));
+        let (from, hit) = hit_on_plane(perfect, direction);
+        let reflected = mirror.sample_gathering_ray(&from, &hit).get_direction().normalised();
+        assert!((reflected - WorldVec::new(direction.0, 0.0, -direction.2)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_rough_dielectric() {
+        sampler::replace_current(sampler::Sampler::new(2));
+        let glass = std::rc::Rc::new(RoughDielectric::new(1.5, std::rc::Rc::new(texture::Constant::scalar(0.3))));
+        let direction = WorldVec::new(0.0, 0.0, -1.0);
+        let (from, hit) = hit_on_plane(glass.clone(), direction);
+        let mut reflected = 0;
+        for _ in 0..4000 {
+            if glass.sample_gathering_ray(&from, &hit).get_direction().2 > 0.0 {
+                reflected += 1;
+            }
+        }
+        // Around 4% head on, and a little more as the facets tilt.
+        assert!((100..400).contains(&reflected), "{}", reflected);
+
+        let ([r, _, _], _) = mean_weight(glass.clone(), direction, 2000);
+        assert!(r <= 1.0 && r > 0.9, "{}", r);
+
+        // From inside, at a shallow angle, everything is reflected back in.
+        let inside = WorldVec::new(0.9, 0.0, 0.3).normalised();
+        let (from, hit) = hit_on_plane(glass.clone(), inside);
+        let smooth = RoughDielectric::new(1.5, std::rc::Rc::new(texture::Constant::scalar(0.0)));
+        for _ in 0..100 {
+            assert!(smooth.sample_gathering_ray(&from, &hit).get_direction().2 < 0.0);
+        }
+    }
+}
@@ -0,0 +1,370 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::sphere;
+use crate::transform;
+use crate::WorldVec;
+
+/// Values which can be blended between.
+pub trait Interpolate: Copy {
+    /// Blend from `self` (at `s = 0`) to `other` (at `s = 1`).
+    fn interpolate(&self, other: &Self, s: f64) -> Self;
+}
+
+impl Interpolate for f64 {
+    fn interpolate(&self, other: &f64, s: f64) -> f64 {
+        self + (other - self) * s
+    }
+}
+
+impl Interpolate for WorldVec {
+    fn interpolate(&self, other: &WorldVec, s: f64) -> WorldVec {
+        *self + (*other - *self) * s
+    }
+}
+
+/// Where an object is, split into parts which interpolate nicely: it's scaled, then rotated,
+/// then moved.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Pose {
+    pub translation: WorldVec,
+    pub rotation: transform::Quaternion,
+    pub scale: WorldVec,
+    /// Which way around `rotation` is reached from the pose before it in a `Track`.
+    pub arc: transform::Arc,
+}
+
+impl Pose {
+    pub fn identity() -> Pose {
+        Pose {
+            translation: WorldVec::new(0.0, 0.0, 0.0),
+            rotation: transform::Quaternion::identity(),
+            scale: WorldVec::new(1.0, 1.0, 1.0),
+            arc: transform::Arc::Short,
+        }
+    }
+
+    pub fn to_transform(&self) -> transform::Transform {
+        transform::Transform::scaling(self.scale)
+            .then(&transform::Transform::from_quaternion(self.rotation))
+            .then(&transform::Transform::translation(self.translation))
+    }
+}
+
+impl Interpolate for Pose {
+    fn interpolate(&self, other: &Pose, s: f64) -> Pose {
+        Pose {
+            translation: self.translation.interpolate(&other.translation, s),
+            rotation: self.rotation.slerp_along(&other.rotation, s, other.arc),
+            scale: self.scale.interpolate(&other.scale, s),
+            arc: other.arc,
+        }
+    }
+}
+
+/// How a `Track` gets from one key to the next.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation<T> {
+    /// At a constant rate.
+    Linear,
+    /// Along a cubic Bezier curve, with the given control values: the first leaving this key, the
+    /// second arriving at the next. These are blended with `Interpolate`, so rotations follow
+    /// smooth curves too.
+    Bezier(T, T),
+}
+
+#[derive(Debug, Clone)]
+struct Key<T> {
+    time: f64,
+    value: T,
+    /// How to get to the next key.
+    interpolation: Interpolation<T>,
+}
+
+/// A value which changes over time, given by its value at a number of key times. Between keys,
+/// the value is interpolated; before the first key and after the last, it holds still.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keys: Vec<Key<T>>,
+}
+
+impl<T: Interpolate> Track<T> {
+    /// A track which moves linearly between `(time, value)` keys. Panics if there are no keys.
+    pub fn new(keys: Vec<(f64, T)>) -> Track<T> {
+        Track::with_interpolation(
+            keys.into_iter()
+                .map(|(time, value)| (time, value, Interpolation::Linear))
+                .collect(),
+        )
+    }
+
+    /// A track where each key also says how to move on to the next. Panics if there are no keys.
+    pub fn with_interpolation(keys: Vec<(f64, T, Interpolation<T>)>) -> Track<T> {
+        assert!(!keys.is_empty(), "A track needs at least one key.");
+        let mut keys: Vec<_> = keys
+            .into_iter()
+            .map(|(time, value, interpolation)| Key {
+                time,
+                value,
+                interpolation,
+            })
+            .collect();
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(std::cmp::Ordering::Equal));
+        Track { keys }
+    }
+
+    /// A value which never changes.
+    pub fn constant(value: T) -> Track<T> {
+        Track::new(vec![(0.0, value)])
+    }
+
+    /// The times of each key, in order.
+    pub fn key_times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.keys.iter().map(|key| key.time)
+    }
+
+    /// The values at each key, along with any Bezier control values. For vectors, the track never
+    /// leaves the convex hull of these.
+    pub fn control_values(&self) -> impl Iterator<Item = T> + '_ {
+        self.keys.iter().flat_map(|key| {
+            let controls = match key.interpolation {
+                Interpolation::Linear => None,
+                Interpolation::Bezier(leaving, arriving) => Some(vec![leaving, arriving]),
+            };
+            std::iter::once(key.value).chain(controls.into_iter().flatten())
+        })
+    }
+
+    pub fn at(&self, time: f64) -> T {
+        // The first key after `time`.
+        let next = self.keys.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return self.keys[0].value;
+        }
+        if next == self.keys.len() {
+            return self.keys[next - 1].value;
+        }
+        let (from, to) = (&self.keys[next - 1], &self.keys[next]);
+        let s = (time - from.time) / (to.time - from.time);
+        match from.interpolation {
+            Interpolation::Linear => from.value.interpolate(&to.value, s),
+            Interpolation::Bezier(leaving, arriving) => {
+                // De Casteljau's algorithm, which only needs pairwise blending.
+                let a = from.value.interpolate(&leaving, s);
+                let b = leaving.interpolate(&arriving, s);
+                let c = arriving.interpolate(&to.value, s);
+                let (d, e) = (a.interpolate(&b, s), b.interpolate(&c, s));
+                d.interpolate(&e, s)
+            }
+        }
+    }
+}
+
+/// A sphere which moves over time, for motion blur.
+#[derive(Debug)]
+pub struct MovingSphere {
+    centre: Track<WorldVec>,
+    radius: f64,
+    /// The sphere, centred on the origin. Rays are moved to meet it.
+    shape: sphere::Sphere,
+}
+
+impl MovingSphere {
+    pub fn new(centre: Track<WorldVec>, radius: f64, material: std::rc::Rc<dyn material::Material>) -> MovingSphere {
+        MovingSphere {
+            centre,
+            radius,
+            shape: sphere::Sphere::new(WorldVec::new(0.0, 0.0, 0.0), radius, material),
+        }
+    }
+
+    /// A sphere moving in a straight line, from `start` at `start_time` to `end` at `end_time`.
+    pub fn linear(
+        (start, start_time): (WorldVec, f64),
+        (end, end_time): (WorldVec, f64),
+        radius: f64,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> MovingSphere {
+        MovingSphere::new(Track::new(vec![(start_time, start), (end_time, end)]), radius, material)
+    }
+}
+
+impl hit::Hit for MovingSphere {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        // Move the ray rather than the sphere. Only the hit point needs moving back.
+        let centre = self.centre.at(from.get_time());
+        let moved = ray::Ray::new(*from.get_origin() - centre, *from.get_direction())
+            .with_t_range(from.get_t_min(), from.get_t_max())
+            .with_time(from.get_time());
+        let mut hit = self.shape.hit(&moved)?;
+        let normal = hit.intersected_surface_normal;
+        hit.intersected_surface_normal = ray::Ray::new(*normal.get_origin() + centre, *normal.get_direction());
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // The centre never leaves the convex hull of the track's control values, so the boxes
+        // around those cover it.
+        let radius = WorldVec::new(self.radius, self.radius, self.radius);
+        self.centre
+            .control_values()
+            .map(|centre| aabb::Aabb::new(centre - radius, centre + radius))
+            .reduce(|a, b| a.union(&b))
+    }
+}
+
+/// An `Instance` whose transform changes over time: rays see the object wherever it is at the
+/// ray's time.
+#[derive(Debug)]
+pub struct AnimatedInstance {
+    object: std::rc::Rc<dyn hit::Hit>,
+    pose: Track<Pose>,
+}
+
+impl AnimatedInstance {
+    pub fn new(object: std::rc::Rc<dyn hit::Hit>, pose: Track<Pose>) -> AnimatedInstance {
+        AnimatedInstance { object, pose }
+    }
+}
+
+impl hit::Hit for AnimatedInstance {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let transform = self.pose.at(from.get_time()).to_transform();
+        crate::instance::hit_placed(self.object.as_ref(), &transform, &transform.normal_matrix(), from)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // Every pose's translation and scale stay within the convex hull of the track's control
+        // values, whatever the rotation does. If the rotation never changes, the boxes placed with
+        // each combination of those cover the object. Otherwise, it could be turned any which way,
+        // so cover the sphere it could reach, around each translation.
+        let bounds = self.object.bounding_box()?;
+        let poses: Vec<Pose> = self.pose.control_values().collect();
+        let rotation = poses[0].rotation;
+        let turns = poses.iter().any(|pose| pose.rotation.dot(&rotation).abs() < 1.0 - 1e-12);
+        let placed: Vec<aabb::Aabb> = if turns {
+            // The distance from the origin is convex in the scale, so the furthest any corner gets
+            // is at one of the control scales.
+            let reach = poses
+                .iter()
+                .flat_map(|pose| {
+                    bounds.corners().map(move |corner| {
+                        WorldVec::new(corner.0 * pose.scale.0, corner.1 * pose.scale.1, corner.2 * pose.scale.2).length()
+                    })
+                })
+                .fold(0.0, f64::max);
+            let reach = WorldVec::new(reach, reach, reach);
+            poses
+                .iter()
+                .map(|pose| aabb::Aabb::new(pose.translation - reach, pose.translation + reach))
+                .collect()
+        } else {
+            poses
+                .iter()
+                .flat_map(|moved| {
+                    poses.iter().map(move |scaled| Pose {
+                        translation: moved.translation,
+                        rotation,
+                        scale: scaled.scale,
+                        arc: transform::Arc::Short,
+                    })
+                })
+                .map(|pose| bounds.transformed(&pose.to_transform()))
+                .collect()
+        };
+        placed.into_iter().reduce(|a, b| a.union(&b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::grey;
+    use crate::hit::Hit;
+
+    #[test]
+    fn test_track() {
+        let track = Track::new(vec![(1.0, 10.0), (0.0, 0.0), (3.0, 0.0)]);
+        assert_eq!(track.at(-1.0), 0.0);
+        assert_eq!(track.at(0.5), 5.0);
+        assert_eq!(track.at(2.0), 5.0);
+        assert_eq!(track.at(5.0), 0.0);
+
+        // A Bezier segment passes through its ends, and eases in and out with these controls.
+        let track = Track::with_interpolation(vec![
+            (0.0, 0.0, Interpolation::Bezier(0.0, 1.0)),
+            (1.0, 1.0, Interpolation::Linear),
+        ]);
+        assert_eq!(track.at(0.0), 0.0);
+        assert_eq!(track.at(0.5), 0.5);
+        assert_eq!(track.at(1.0), 1.0);
+        assert!(track.at(0.1) < 0.1 && track.at(0.9) > 0.9);
+        assert_eq!(track.control_values().collect::<Vec<_>>(), vec![0.0, 0.0, 1.0, 1.0]);
+    }
+
+    #[test]
+    fn test_moving_sphere() {
+        let sphere = MovingSphere::linear(
+            (WorldVec::new(0.0, 0.0, 5.0), 0.0),
+            (WorldVec::new(4.0, 0.0, 5.0), 1.0),
+            1.0,
+            grey(),
+        );
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(sphere.hit(&ray.with_time(0.0)).is_some());
+        assert!(sphere.hit(&ray.with_time(1.0)).is_none());
+        let bounds = sphere.bounding_box().unwrap();
+        assert_eq!(bounds.min, WorldVec::new(-1.0, -1.0, 4.0));
+        assert_eq!(bounds.max, WorldVec::new(5.0, 1.0, 6.0));
+    }
+
+    #[test]
+    fn test_animated_instance() {
+        // A long, thin box spinning half a turn around the y axis.
+        let bar = std::rc::Rc::new(crate::cuboid::Cuboid::new(
+            WorldVec::new(-2.0, -0.1, -0.1),
+            WorldVec::new(2.0, 0.1, 0.1),
+            grey(),
+        ));
+        let axis = WorldVec::new(0.0, 1.0, 0.0);
+        let pose = |angle: f64| Pose {
+            rotation: transform::Quaternion::from_axis_angle(axis, angle),
+            ..Pose::identity()
+        };
+        let spinning = AnimatedInstance::new(
+            bar,
+            Track::new(vec![(0.0, pose(0.0)), (1.0, pose(std::f64::consts::FRAC_PI_2))]),
+        );
+
+        // A ray along z, offset in x, only hits the bar before it's turned.
+        let ray = ray::Ray::new(WorldVec::new(1.5, 0.0, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(spinning.hit(&ray.with_time(0.0)).is_some());
+        assert!(spinning.hit(&ray.with_time(1.0)).is_none());
+
+        // The bounds cover the bar at every angle in between.
+        let bounds = spinning.bounding_box().unwrap();
+        let corner = WorldVec::new(2.0, 0.0, 0.0);
+        for step in 0..=100 {
+            let angle = std::f64::consts::FRAC_PI_2 * step as f64 / 100.0;
+            let point = transform::Transform::rotation(axis, angle).transform_point(corner);
+            assert!(point.0 <= bounds.max.0 && point.2 >= bounds.min.2 && point.2 <= bounds.max.2);
+        }
+    }
+
+    #[test]
+    fn test_long_arc() {
+        // Three quarters of a turn, which would otherwise go back a quarter of a turn instead.
+        let axis = WorldVec::new(0.0, 1.0, 0.0);
+        let end = Pose {
+            rotation: transform::Quaternion::from_axis_angle(axis, 1.5 * std::f64::consts::PI),
+            arc: transform::Arc::Long,
+            ..Pose::identity()
+        };
+        let track = Track::new(vec![(0.0, Pose::identity()), (1.0, end)]);
+        let x = WorldVec::new(1.0, 0.0, 0.0);
+        let halfway = track.at(0.5).to_transform().transform_point(x);
+        let expected = transform::Transform::rotation(axis, 0.75 * std::f64::consts::PI).transform_point(x);
+        assert!((halfway - expected).length() < 1e-9);
+    }
+}
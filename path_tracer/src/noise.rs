@@ -0,0 +1,254 @@
+//! Coherent noise: random-looking values which vary smoothly through space, for procedural
+//! textures. Everything here is built from integer hashing and basic floating point arithmetic
+//! (no `sin`, `exp` and so on, whose results can differ between platforms), so the same seed
+//! gives exactly the same noise everywhere.
+
+use crate::sampler;
+use crate::WorldVec;
+
+/// The 12 gradients used by Perlin and simplex noise: the midpoints of a cube's edges.
+const GRADIENTS: [[f64; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+/// A source of noise. Noises with the same seed are identical.
+#[derive(Debug, Clone)]
+pub struct Noise {
+    seed: u64,
+    /// A shuffle of 0..256, repeated twice so that lookups can skip wrapping.
+    permutation: Vec<usize>,
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Noise {
+        use rand::RngCore;
+        let mut rng = sampler::Sampler::new(seed);
+        let mut permutation: Vec<usize> = (0..256).collect();
+        // Fisher-Yates. The tiny bias from the modulo doesn't matter here.
+        for i in (1..256).rev() {
+            permutation.swap(i, (rng.next_u64() % (i as u64 + 1)) as usize);
+        }
+        permutation.extend_from_within(..);
+        Noise { seed, permutation }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Hash some lattice point to one of the 256 entries in the permutation.
+    fn hash(&self, x: i64, y: i64, z: i64) -> usize {
+        let p = &self.permutation;
+        p[p[p[(x & 255) as usize] + (y & 255) as usize] + (z & 255) as usize]
+    }
+
+    fn gradient_dot(&self, cell: (i64, i64, i64), offset: (f64, f64, f64)) -> f64 {
+        let g = GRADIENTS[self.hash(cell.0, cell.1, cell.2) % 12];
+        g[0] * offset.0 + g[1] * offset.1 + g[2] * offset.2
+    }
+
+    /// Ken Perlin's improved gradient noise, roughly between -1 and 1. It is zero at every
+    /// integer lattice point.
+    pub fn perlin(&self, point: WorldVec) -> f64 {
+        let (x0, y0, z0) = (point.0.floor(), point.1.floor(), point.2.floor());
+        let (fx, fy, fz) = (point.0 - x0, point.1 - y0, point.2 - z0);
+        let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let (u, v, w) = (fade(fx), fade(fy), fade(fz));
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+
+        let corner = |dx: i64, dy: i64, dz: i64| {
+            self.gradient_dot(
+                (x0 + dx, y0 + dy, z0 + dz),
+                (fx - dx as f64, fy - dy as f64, fz - dz as f64),
+            )
+        };
+        lerp(
+            lerp(
+                lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+                lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+                v,
+            ),
+            lerp(
+                lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+                lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+                v,
+            ),
+            w,
+        )
+    }
+
+    /// Simplex noise, roughly between -1 and 1. It has fewer directional artifacts than Perlin
+    /// noise, and is cheaper, as it blends four corners of a tetrahedron rather than eight of a
+    /// cube.
+    pub fn simplex(&self, point: WorldVec) -> f64 {
+        const SKEW: f64 = 1.0 / 3.0;
+        const UNSKEW: f64 = 1.0 / 6.0;
+
+        // Find which cell of the skewed (cubic) lattice we're in, and the offset from its origin.
+        let s = (point.0 + point.1 + point.2) * SKEW;
+        let (i, j, k) = ((point.0 + s).floor(), (point.1 + s).floor(), (point.2 + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let offset = (point.0 - (i - t), point.1 - (j - t), point.2 - (k - t));
+
+        // Each cube is split into six tetrahedra; work out which one we're in from the order of
+        // the offset's components.
+        let (x, y, z) = offset;
+        let (second, third) = if x >= y {
+            if y >= z {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x >= z {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else if y < z {
+            ((0, 0, 1), (0, 1, 1))
+        } else if x < z {
+            ((0, 1, 0), (0, 1, 1))
+        } else {
+            ((0, 1, 0), (1, 1, 0))
+        };
+
+        let cell = (i as i64, j as i64, k as i64);
+        let mut total = 0.0;
+        for (n, &(di, dj, dk)) in [(0, 0, 0), second, third, (1, 1, 1)].iter().enumerate() {
+            let unskew = n as f64 * UNSKEW;
+            let corner_offset = (
+                x - di as f64 + unskew,
+                y - dj as f64 + unskew,
+                z - dk as f64 + unskew,
+            );
+            let falloff = 0.6
+                - corner_offset.0 * corner_offset.0
+                - corner_offset.1 * corner_offset.1
+                - corner_offset.2 * corner_offset.2;
+            if falloff > 0.0 {
+                let falloff = falloff * falloff;
+                total += falloff
+                    * falloff
+                    * self.gradient_dot((cell.0 + di, cell.1 + dj, cell.2 + dk), corner_offset);
+            }
+        }
+        // Scale the result to roughly fill [-1, 1].
+        32.0 * total
+    }
+
+    /// Fractal Brownian motion: `octaves` layers of Perlin noise, each at twice the frequency and
+    /// half the amplitude of the last. This gives detail at every scale, like clouds or rock.
+    pub fn fbm(&self, point: WorldVec, octaves: usize) -> f64 {
+        self.sum_octaves(point, octaves, |value| value)
+    }
+
+    /// Like `fbm`, but summing the absolute value of each octave. The creases where the noise
+    /// crosses zero make it look like flames or veins. Always at least 0.
+    pub fn turbulence(&self, point: WorldVec, octaves: usize) -> f64 {
+        self.sum_octaves(point, octaves, f64::abs)
+    }
+
+    fn sum_octaves(&self, point: WorldVec, octaves: usize, shape: impl Fn(f64) -> f64) -> f64 {
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        for _ in 0..octaves {
+            total += amplitude * shape(self.perlin(point * frequency));
+            frequency *= 2.0;
+            amplitude *= 0.5;
+        }
+        total
+    }
+
+    /// Worley (cellular) noise: space is split into unit cells, each with one randomly placed
+    /// feature point. Returns the distances to the closest and second closest feature points.
+    pub fn worley(&self, point: WorldVec) -> (f64, f64) {
+        let cell = (point.0.floor() as i64, point.1.floor() as i64, point.2.floor() as i64);
+        let mut closest = (f64::INFINITY, f64::INFINITY);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let neighbour = (cell.0 + dx, cell.1 + dy, cell.2 + dz);
+                    let distance = (self.feature_point(neighbour) - point).length();
+                    if distance < closest.0 {
+                        closest = (distance, closest.0);
+                    } else if distance < closest.1 {
+                        closest.1 = distance;
+                    }
+                }
+            }
+        }
+        closest
+    }
+
+    /// Where the feature point in some cell is.
+    fn feature_point(&self, (x, y, z): (i64, i64, i64)) -> WorldVec {
+        // Mix the coordinates into a single hash, then draw each component from it in turn.
+        let mut hash = self.seed;
+        for &coordinate in &[x, y, z] {
+            hash = mix(hash ^ coordinate as u64);
+        }
+        let mut component = || {
+            hash = mix(hash);
+            (hash >> 11) as f64 / (1u64 << 53) as f64
+        };
+        WorldVec::new(
+            x as f64 + component(),
+            y as f64 + component(),
+            z as f64 + component(),
+        )
+    }
+}
+
+/// The SplitMix64 step and finaliser, which scrambles every input bit into every output bit.
+fn mix(state: u64) -> u64 {
+    let mut z = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise() {
+        let noise = Noise::new(7);
+        let other = Noise::new(8);
+        let point = WorldVec::new(1.3, -2.7, 0.45);
+
+        assert_eq!(noise.perlin(WorldVec::new(3.0, -1.0, 2.0)), 0.0);
+        assert_eq!(noise.perlin(point), Noise::new(7).perlin(point));
+        assert_ne!(noise.perlin(point), other.perlin(point));
+
+        for i in 0..1000 {
+            let point = WorldVec::new(i as f64 * 0.173, i as f64 * -0.311, i as f64 * 0.057);
+            assert!(noise.perlin(point).abs() <= 1.5);
+            assert!(noise.simplex(point).abs() <= 1.5);
+            assert!(noise.turbulence(point, 4) >= 0.0);
+            let (f1, f2) = noise.worley(point);
+            assert!(f1 <= f2 && f2 < 3.0);
+        }
+
+        // These must never change, on any platform, or saved scenes would render differently.
+        assert_eq!(noise.perlin(point), -0.03938080760426829);
+        assert_eq!(noise.simplex(point), -0.5590267537718626);
+        assert_eq!(noise.fbm(point, 4), -0.2568685159031227);
+        assert_eq!(noise.worley(point), (0.6843340457870205, 0.7136133249982185));
+
+        // Nearby points have nearby values.
+        let nudge = WorldVec::new(1e-6, 0.0, 0.0);
+        assert!((noise.simplex(point) - noise.simplex(point + nudge)).abs() < 1e-4);
+        assert!((noise.fbm(point, 5) - noise.fbm(point + nudge, 5)).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,374 @@
+//! Materials which add surface detail by bending the shading normal, without changing the
+//! geometry: `NormalMapped` reads the normal from a tangent-space normal map, and `BumpMapped`
+//! works it out from a height map. Both wrap any other material.
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::subsurface;
+use crate::texture;
+use crate::WorldVec;
+
+/// How far above the geometric surface `facing_shading_normal` keeps reflections.
+const MIN_ELEVATION: f64 = 1e-3;
+
+/// Bend `shading` (a normal on the same side of the surface as `geometric`) so that the viewer can
+/// see it. A bent normal can face away from the viewer, or reflect them into the surface, which
+/// lets light leak through it; in that case, the reflection is lifted back just above the
+/// geometric surface, and the normal rebuilt as the halfway vector.
+pub fn facing_shading_normal(shading: WorldVec, geometric: WorldVec, towards_viewer: WorldVec) -> WorldVec {
+    // Work on the viewer's side of the surface.
+    let side = if geometric.dot(towards_viewer) < 0.0 { -1.0 } else { 1.0 };
+    let (shading, geometric) = (shading * side, geometric * side);
+    let reflected = shading * (2.0 * shading.dot(towards_viewer)) - towards_viewer;
+    let elevation = reflected.dot(geometric);
+    if elevation >= MIN_ELEVATION {
+        return shading * side;
+    }
+    let lifted = (reflected + geometric * (MIN_ELEVATION - elevation)).normalised();
+    (towards_viewer + lifted).normalised() * side
+}
+
+/// Rebuild `hit` with a new shading normal, bent as needed to stop light leaking. Returns the hit
+/// unchanged if `normal` is degenerate.
+fn with_bent_normal(hit: &hit::MaterialHit, reflected_ray: &ray::Ray, normal: WorldVec) -> hit::MaterialHit {
+    if normal.length() <= 1e-12 {
+        return hit.clone();
+    }
+    let geometric = *hit.intersected_surface_normal.get_direction();
+    let towards_viewer = -reflected_ray.get_direction().normalised();
+    hit.clone()
+        .with_shading_normal(facing_shading_normal(normal.normalised(), geometric, towards_viewer))
+}
+
+/// Fix up a ray sampled with a bent normal: if the bent normal sent it out into the reflecting
+/// hemisphere but it actually passes through the geometric surface, mirror it back out.
+fn keep_above_surface(ray: ray::Ray, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> ray::Ray {
+    let geometric = *hit.intersected_surface_normal.get_direction();
+    let side = if geometric.dot(towards_viewer) < 0.0 { -1.0 } else { 1.0 };
+    let (geometric, shading) = (geometric * side, hit.shading_normal * side);
+    let direction = *ray.get_direction();
+    if direction.dot(shading) > 0.0 && direction.dot(geometric) < 0.0 {
+        ray::Ray::new(*ray.get_origin(), direction - geometric * (2.0 * direction.dot(geometric)))
+    } else {
+        ray
+    }
+}
+
+/// The colouring pass doesn't know where the viewer was, so pretend they looked straight at the
+/// surface.
+fn head_on(hit: &hit::MaterialHit) -> ray::Ray {
+    let normal = *hit.intersected_surface_normal.get_direction();
+    let towards_viewer = if hit.front_face { normal } else { -normal };
+    ray::Ray::new(*hit.intersected_surface_normal.get_origin(), -towards_viewer)
+}
+
+/// Some material with a tangent-space normal map. Each texel's red, green and blue give the
+/// normal along the tangent, bitangent and normal, each mapped from [-1, 1] to [0, 1], so the
+/// usual lilac (0.5, 0.5, 1) leaves the surface flat.
+#[derive(Debug)]
+pub struct NormalMapped {
+    base: std::rc::Rc<dyn material::Material>,
+    map: std::rc::Rc<dyn texture::Texture>,
+    /// How strongly the map bends the normal. 1 is as authored; 0 is flat.
+    strength: f64,
+}
+
+impl NormalMapped {
+    pub fn new(
+        base: std::rc::Rc<dyn material::Material>,
+        map: std::rc::Rc<dyn texture::Texture>,
+        strength: f64,
+    ) -> NormalMapped {
+        NormalMapped {
+            base,
+            map,
+            strength,
+        }
+    }
+
+    fn shade(&self, hit: &hit::MaterialHit, reflected_ray: &ray::Ray) -> hit::MaterialHit {
+        let texel = self.map.colour(hit);
+        let x = (texel.get_red() * 2.0 - 1.0) * self.strength;
+        let y = (texel.get_green() * 2.0 - 1.0) * self.strength;
+        let z = texel.get_blue() * 2.0 - 1.0;
+        let normal = hit.tangent * x + hit.bitangent() * y + hit.shading_normal * z;
+        with_bent_normal(hit, reflected_ray, normal)
+    }
+}
+
+impl material::Material for NormalMapped {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let shaded = self.shade(hit, reflected_ray);
+        let ray = self.base.sample_gathering_ray(reflected_ray, &shaded);
+        keep_above_surface(ray, &shaded, -*reflected_ray.get_direction())
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        self.base.colour(start_colour, &self.shade(hit, &head_on(hit)), angle_of_incidence)
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let viewed_from = ray::Ray::new(*hit.intersected_surface_normal.get_origin(), -towards_viewer);
+        let shaded = self.shade(hit, &viewed_from);
+        self.base.colour_between(start_colour, &shaded, towards_light, towards_viewer)
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        // Choose with the bent normal, which the chosen part is shaded with, and keep the map over
+        // whichever part it is.
+        let viewed_from = ray::Ray::new(*hit.intersected_surface_normal.get_origin(), -towards_viewer);
+        self.base.choose(&self.shade(hit, &viewed_from), towards_viewer).map(|base| {
+            let chosen: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(NormalMapped {
+                base,
+                map: self.map.clone(),
+                strength: self.strength,
+            });
+            chosen
+        })
+    }
+
+    fn interior(&self) -> Option<subsurface::Medium> {
+        self.base.interior()
+    }
+}
+
+/// Some material with a bump map: a height texture (read as a scalar) which the surface appears
+/// to be raised by. The slope of the height bends the normal. The slope is measured by stepping
+/// both the UVs and the hit position, so solid and procedural textures bump as well as images.
+#[derive(Debug)]
+pub struct BumpMapped {
+    base: std::rc::Rc<dyn material::Material>,
+    height: std::rc::Rc<dyn texture::Texture>,
+    /// How many units the surface rises for each unit of height, relative to one unit of UV (or of
+    /// distance along the tangent, for solid textures).
+    scale: f64,
+}
+
+/// The step in UV, and in distance along the tangent and bitangent, used to measure the slope of a
+/// bump map.
+const BUMP_DELTA: f64 = 1e-3;
+
+impl BumpMapped {
+    pub fn new(
+        base: std::rc::Rc<dyn material::Material>,
+        height: std::rc::Rc<dyn texture::Texture>,
+        scale: f64,
+    ) -> BumpMapped {
+        BumpMapped { base, height, scale }
+    }
+
+    fn shade(&self, hit: &hit::MaterialHit, reflected_ray: &ray::Ray) -> hit::MaterialHit {
+        let (u, v) = hit.uv;
+        let surface = hit.intersected_surface_normal;
+        let height_at = |du: f64, dv: f64| {
+            let mut moved = hit.clone();
+            moved.uv = (u + du, v + dv);
+            moved.intersected_surface_normal = ray::Ray::new(
+                *surface.get_origin() + hit.tangent * du + hit.bitangent() * dv,
+                *surface.get_direction(),
+            );
+            self.height.scalar(&moved)
+        };
+        let d_du = (height_at(BUMP_DELTA, 0.0) - height_at(-BUMP_DELTA, 0.0)) / (2.0 * BUMP_DELTA);
+        let d_dv = (height_at(0.0, BUMP_DELTA) - height_at(0.0, -BUMP_DELTA)) / (2.0 * BUMP_DELTA);
+        let normal = hit.shading_normal - (hit.tangent * d_du + hit.bitangent() * d_dv) * self.scale;
+        with_bent_normal(hit, reflected_ray, normal)
+    }
+}
+
+impl material::Material for BumpMapped {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let shaded = self.shade(hit, reflected_ray);
+        let ray = self.base.sample_gathering_ray(reflected_ray, &shaded);
+        keep_above_surface(ray, &shaded, -*reflected_ray.get_direction())
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, angle_of_incidence: f64) -> colour::Colour {
+        self.base.colour(start_colour, &self.shade(hit, &head_on(hit)), angle_of_incidence)
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let viewed_from = ray::Ray::new(*hit.intersected_surface_normal.get_origin(), -towards_viewer);
+        let shaded = self.shade(hit, &viewed_from);
+        self.base.colour_between(start_colour, &shaded, towards_light, towards_viewer)
+    }
+
+    fn choose(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Option<std::rc::Rc<dyn material::Material>> {
+        // Choose with the bent normal, which the chosen part is shaded with, and keep the map over
+        // whichever part it is.
+        let viewed_from = ray::Ray::new(*hit.intersected_surface_normal.get_origin(), -towards_viewer);
+        self.base.choose(&self.shade(hit, &viewed_from), towards_viewer).map(|base| {
+            let chosen: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(BumpMapped {
+                base,
+                height: self.height.clone(),
+                scale: self.scale,
+            });
+            chosen
+        })
+    }
+
+    fn interior(&self) -> Option<subsurface::Medium> {
+        self.base.interior()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::hit_on_plane;
+    use crate::combinator;
+    use crate::lambertian;
+    use crate::metal;
+    use crate::sampler;
+    use material::Material;
+
+    #[test]
+    fn test_facing_shading_normal() {
+        let geometric = WorldVec::new(0.0, 0.0, 1.0);
+        // A normal which is fine as it is.
+        let shading = WorldVec::new(0.1, 0.0, 1.0).normalised();
+        let viewer = WorldVec::new(0.0, 0.0, 1.0);
+        assert_eq!(facing_shading_normal(shading, geometric, viewer), shading);
+
+        // A grazing viewer whose reflection about a steep normal goes into the surface.
+        let shading = WorldVec::new(1.0, 0.0, 0.5).normalised();
+        let viewer = WorldVec::new(-1.0, 0.0, 0.1).normalised();
+        let bent = facing_shading_normal(shading, geometric, viewer);
+        let reflected = bent * (2.0 * bent.dot(viewer)) - viewer;
+        assert!(reflected.dot(geometric) > 0.0);
+        assert!(bent.dot(viewer) > 0.0);
+    }
+
+    #[test]
+    fn test_normal_map() {
+        let mirror: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(metal::Metal::new(colour::Colour::new(1.0, 1.0, 1.0), 0.0));
+        // A flat map leaves a mirror alone.
+        let flat = NormalMapped::new(
+            mirror.clone(),
+            std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.5, 0.5, 1.0))),
+            1.0,
+        );
+        let direction = WorldVec::new(0.0, 0.6, -0.8);
+        let (from, hit) = hit_on_plane(mirror.clone(), direction);
+        let reflected = *flat.sample_gathering_ray(&from, &hit).get_direction();
+        assert!((reflected - WorldVec::new(0.0, 0.6, 0.8)).length() < 1e-9);
+
+        // Tilting the normal along the bitangent tilts the reflection.
+        let tilted = NormalMapped::new(
+            mirror.clone(),
+            std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.5, 0.6, 1.0))),
+            1.0,
+        );
+        let reflected = *tilted.sample_gathering_ray(&from, &hit).get_direction();
+        assert!((reflected - WorldVec::new(0.0, 0.6, 0.8)).length() > 1e-3);
+
+        // However steep the map, reflections never go through the surface.
+        let steep = NormalMapped::new(
+            mirror.clone(),
+            std::rc::Rc::new(texture::Constant::new(colour::Colour::new(1.0, 0.5, 0.05))),
+            1.0,
+        );
+        for &x in &[-0.99, -0.5, 0.0, 0.5, 0.99] {
+            let direction = WorldVec::new(x, 0.0, -1.0).normalised();
+            let (from, hit) = hit_on_plane(mirror.clone(), direction);
+            let reflected = *steep.sample_gathering_ray(&from, &hit).get_direction();
+            assert!(reflected.2 > 0.0, "{:?} leaked through the surface", reflected);
+        }
+    }
+
+    #[test]
+    fn test_bump_map() {
+        let mirror: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(metal::Metal::new(colour::Colour::new(1.0, 1.0, 1.0), 0.0));
+        // Height increasing along u, so the surface slopes up along the tangent.
+        let ramp = BumpMapped::new(mirror.clone(), std::rc::Rc::new(UHeight), 0.5);
+        let (from, hit) = hit_on_plane(mirror.clone(), WorldVec::new(0.0, 0.0, -1.0));
+        let shaded = ramp.shade(&hit, &from);
+        assert!((shaded.shading_normal - WorldVec::new(-0.5, 0.0, 1.0).normalised()).length() < 1e-6);
+
+        // The same slope from a solid texture, which ignores the UVs.
+        let ramp = BumpMapped::new(mirror.clone(), std::rc::Rc::new(XHeight), 0.5);
+        let (from, hit) = hit_on_plane(mirror, WorldVec::new(0.0, 0.0, -1.0));
+        let shaded = ramp.shade(&hit, &from);
+        assert!((shaded.shading_normal - WorldVec::new(-0.5, 0.0, 1.0).normalised()).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_wrapped_interior() {
+        // Bending the normal of a translucent surface leaves it translucent inside.
+        let marble: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(subsurface::Subsurface::marble());
+        let lilac = std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.5, 0.5, 1.0)));
+        let medium = marble.interior();
+        assert!(medium.is_some());
+        assert_eq!(NormalMapped::new(marble.clone(), lilac, 1.0).interior(), medium);
+        assert_eq!(BumpMapped::new(marble, std::rc::Rc::new(UHeight), 0.5).interior(), medium);
+    }
+
+    #[test]
+    fn test_wrapped_choice() {
+        sampler::replace_current(sampler::Sampler::new(16));
+        // A mapped blend is resolved into one of its parts, still mapped, and coloured as that part
+        // alone rather than as the blend.
+        let red: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(1.0, 0.0, 0.0)));
+        let blue: std::rc::Rc<dyn material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.0, 0.0, 1.0)));
+        let mix: std::rc::Rc<dyn material::Material> = std::rc::Rc::new(combinator::Mix::new(red, blue, 0.5));
+        let lilac = std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.5, 0.5, 1.0)));
+        let wrapped: [std::rc::Rc<dyn material::Material>; 2] = [
+            std::rc::Rc::new(NormalMapped::new(mix.clone(), lilac, 1.0)),
+            std::rc::Rc::new(BumpMapped::new(mix, std::rc::Rc::new(UHeight), 0.5)),
+        ];
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        for material in wrapped.iter() {
+            let direction = WorldVec::new(0.0, 0.0, -1.0);
+            let (_, hit) = hit_on_plane(material.clone(), direction);
+            let mut reds = 0;
+            for _ in 0..200 {
+                let resolved = material::resolve(hit.clone(), -direction);
+                assert!(resolved.material.choose(&resolved, -direction).is_none());
+                let colour = resolved.material.colour_between(white.clone(), &resolved, -direction, -direction);
+                assert!(colour.get_red() == 0.0 || colour.get_blue() == 0.0, "{:?}", colour);
+                if colour.get_red() > 0.0 {
+                    reds += 1;
+                }
+            }
+            assert!((60..140).contains(&reds), "{}", reds);
+        }
+    }
+
+    #[derive(Debug)]
+    struct UHeight;
+
+    impl texture::Texture for UHeight {
+        fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+            colour::Colour::new(hit.uv.0, hit.uv.0, hit.uv.0)
+        }
+    }
+
+    /// Height increasing along x in space, whatever the UVs.
+    #[derive(Debug)]
+    struct XHeight;
+
+    impl texture::Texture for XHeight {
+        fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+            let x = hit.intersected_surface_normal.get_origin().0 + 0.5;
+            colour::Colour::new(x, x, x)
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Rough diffuse surfaces, such as clay, plaster and concrete, using the qualitative Oren-Nayar
+//! model. The surface is a field of tiny Lambertian V-shaped grooves, so it throws more light
+//! back towards the light than a Lambertian surface does, and looks flatter when lit from behind
+//! the viewer (like the full moon).
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::texture;
+use crate::WorldVec;
+
+#[derive(Debug)]
+pub struct OrenNayar {
+    albedo: std::rc::Rc<dyn texture::Texture>,
+    /// The factors of the model, from the roughness.
+    a: f64,
+    b: f64,
+}
+
+impl OrenNayar {
+    /// `sigma` is the standard deviation of the grooves' slopes, in radians. 0 is Lambertian, and
+    /// real materials are mostly below about 0.6.
+    pub fn new(colour: colour::Colour, sigma: f64) -> OrenNayar {
+        OrenNayar::textured(std::rc::Rc::new(texture::Constant::new(colour)), sigma)
+    }
+
+    /// A rough diffuse surface whose colour varies across it.
+    pub fn textured(albedo: std::rc::Rc<dyn texture::Texture>, sigma: f64) -> OrenNayar {
+        let sigma2 = sigma * sigma;
+        OrenNayar {
+            albedo,
+            a: 1.0 - 0.5 * sigma2 / (sigma2 + 0.33),
+            b: 0.45 * sigma2 / (sigma2 + 0.09),
+        }
+    }
+
+    /// The reflectance relative to a Lambertian surface of the same albedo, between the local
+    /// directions `wi` and `wo`.
+    fn relative_reflectance(&self, wi: WorldVec, wo: WorldVec) -> f64 {
+        // cos(phi_i - phi_o) sin(alpha) tan(beta), where alpha is the larger of the angles to the
+        // normal and beta the smaller, simplifies to this.
+        let azimuthal = (wi.0 * wo.0 + wi.1 * wo.1).max(0.0) / wi.2.max(wo.2);
+        self.a + self.b * azimuthal
+    }
+}
+
+impl material::Material for OrenNayar {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        // Sampled like a Lambertian surface, in proportion to the cosine, which leaves only the
+        // relative reflectance as the weight.
+        let frame = microfacet::Frame::new(hit, -*reflected_ray.get_direction());
+        ray::Ray::new(
+            *hit.intersected_surface_normal.get_origin(),
+            frame.to_world(material::random_cosine_weighted_in_hemisphere()),
+        )
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        // Without the viewing direction, this is as close as we can get.
+        material::attenuate(&start_colour, &self.albedo.colour(hit))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wi.2 <= 0.0 || wo.2 <= 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let albedo = self.albedo.colour(hit);
+        let s = self.relative_reflectance(wi, wo);
+        material::attenuate(
+            &start_colour,
+            &colour::Colour::unclamped(albedo.get_red() * s, albedo.get_green() * s, albedo.get_blue() * s),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler;
+    use material::Material;
+
+    #[test]
+    fn test_oren_nayar() {
+        sampler::replace_current(sampler::Sampler::new(4));
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let direction = WorldVec::new(0.5, 0.0, -1.0).normalised();
+        let from = ray::Ray::new(-direction, direction);
+        let normal = WorldVec::new(0.0, 0.0, 1.0);
+        let clay = std::rc::Rc::new(OrenNayar::new(white.clone(), 0.5));
+        let hit = hit::MaterialHit::new(clay.clone(), &from, 1.0, normal, (0.0, 0.0));
+        let towards_viewer = -direction;
+
+        // Rays are scattered into the hemisphere above the surface.
+        let samples = 2000;
+        let mut total = 0.0;
+        for _ in 0..samples {
+            let towards_light = clay.sample_gathering_ray(&from, &hit).get_direction().normalised();
+            assert!(towards_light.2 >= 0.0);
+            total += clay.colour_between(white.clone(), &hit, towards_light, towards_viewer).get_red();
+        }
+        // Some light is lost between the grooves.
+        let mean = total / samples as f64;
+        assert!(mean > 0.7 && mean < 1.0, "{}", mean);
+
+        // More light is thrown back towards a light behind the viewer than on past the normal.
+        let grazing_light = WorldVec::new(0.9, 0.0, 0.3).normalised();
+        let retro = clay.colour_between(white.clone(), &hit, towards_viewer, towards_viewer).get_red();
+        let forward = clay.colour_between(white.clone(), &hit, grazing_light, towards_viewer).get_red();
+        assert!(retro > forward, "{} {}", retro, forward);
+
+        // With no roughness, it's Lambertian.
+        let smooth = OrenNayar::new(white.clone(), 0.0);
+        assert_eq!(smooth.colour_between(white, &hit, towards_viewer, towards_viewer).get_red(), 1.0);
+    }
+}
@@ -0,0 +1,156 @@
+//! Procedural textures built on `noise`. Each is solid -- it fills space, and is looked up from
+//! the hit's position -- so objects look carved out of a block of the material.
+
+use crate::colour;
+use crate::hit;
+use crate::noise;
+use crate::texture;
+use crate::WorldVec;
+
+/// A smooth wave, repeating every 1, which goes from 0 up to 1 and back. This stands in for `sin`,
+/// which isn't guaranteed to give the same results on every platform.
+fn wave(x: f64) -> f64 {
+    let triangle = 1.0 - (2.0 * (x - x.floor()) - 1.0).abs();
+    smoothstep(triangle)
+}
+
+fn smoothstep(t: f64) -> f64 {
+    let t = t.clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn point(hit: &hit::MaterialHit) -> WorldVec {
+    *hit.intersected_surface_normal.get_origin()
+}
+
+/// Marble: bands of `vein` through `base`, running along x, warped by turbulence.
+#[derive(Debug)]
+pub struct Marble {
+    noise: noise::Noise,
+    /// How many bands there are per unit.
+    pub frequency: f64,
+    /// How much the bands are warped.
+    pub turbulence: f64,
+    pub base: colour::Colour,
+    pub vein: colour::Colour,
+}
+
+impl Marble {
+    pub fn new(seed: u64, base: colour::Colour, vein: colour::Colour) -> Marble {
+        Marble {
+            noise: noise::Noise::new(seed),
+            frequency: 1.0,
+            turbulence: 4.0,
+            base,
+            vein,
+        }
+    }
+}
+
+impl texture::Texture for Marble {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let p = point(hit) * self.frequency;
+        let warped = p.0 + self.turbulence * self.noise.turbulence(p, 6);
+        // Sharpen the bands into thin veins.
+        let band = wave(warped * 0.5);
+        let band = band * band * band;
+        self.base.mix(&self.vein, band)
+    }
+}
+
+/// Wood: growth rings around the y axis, with the rings wobbled by noise and streaked by grain.
+#[derive(Debug)]
+pub struct Wood {
+    noise: noise::Noise,
+    /// How many rings there are per unit of radius.
+    pub rings: f64,
+    /// How much the rings wobble.
+    pub distortion: f64,
+    pub light: colour::Colour,
+    pub dark: colour::Colour,
+}
+
+impl Wood {
+    pub fn new(seed: u64, light: colour::Colour, dark: colour::Colour) -> Wood {
+        Wood {
+            noise: noise::Noise::new(seed),
+            rings: 8.0,
+            distortion: 0.1,
+            light,
+            dark,
+        }
+    }
+}
+
+impl texture::Texture for Wood {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let p = point(hit);
+        let wobble = self.noise.fbm(p * 2.0, 3) * self.distortion;
+        let radius = (p.0 * p.0 + p.2 * p.2).sqrt() + wobble;
+        let ring = wave(radius * self.rings);
+        // Fine grain running along the trunk.
+        let grain = self.noise.perlin(WorldVec::new(p.0 * 40.0, p.1 * 2.0, p.2 * 40.0)) * 0.15;
+        self.light.mix(&self.dark, (ring * 0.8 + grain).clamp(0.0, 1.0))
+    }
+}
+
+/// Granite: mottled cells of `light` and `dark`, with `speck` flecks where cells meet.
+#[derive(Debug)]
+pub struct Granite {
+    noise: noise::Noise,
+    /// How many grains there are per unit.
+    pub frequency: f64,
+    pub light: colour::Colour,
+    pub dark: colour::Colour,
+    pub speck: colour::Colour,
+}
+
+impl Granite {
+    pub fn new(seed: u64, light: colour::Colour, dark: colour::Colour, speck: colour::Colour) -> Granite {
+        Granite {
+            noise: noise::Noise::new(seed),
+            frequency: 20.0,
+            light,
+            dark,
+            speck,
+        }
+    }
+}
+
+impl texture::Texture for Granite {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let p = point(hit) * self.frequency;
+        let mottle = smoothstep(0.5 + self.noise.fbm(p * 0.25, 4));
+        let (closest, second) = self.noise.worley(p);
+        // Close to the border between two cells, the two distances are nearly equal.
+        let border = 1.0 - smoothstep((second - closest) * 8.0);
+        self.light.mix(&self.dark, mottle).mix(&self.speck, border)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::hit_at;
+    use crate::texture::Texture;
+
+    #[test]
+    fn test_patterns() {
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let black = colour::Colour::new(0.0, 0.0, 0.0);
+        let textures: Vec<Box<dyn Texture>> = vec![
+            Box::new(Marble::new(1, white.clone(), black.clone())),
+            Box::new(Wood::new(1, white.clone(), black.clone())),
+            Box::new(Granite::new(1, white.clone(), black.clone(), black)),
+        ];
+        for texture in &textures {
+            // Each pattern should actually vary, and stay between its colours.
+            let values: Vec<f64> = (0..200)
+                .map(|i| texture.scalar(&hit_at(WorldVec::new(i as f64 * 0.037, 0.3, i as f64 * 0.021), (0.0, 0.0))))
+                .collect();
+            let (min, max) = values.iter().fold((1.0f64, 0.0f64), |(min, max), &v| (min.min(v), max.max(v)));
+            assert!(min >= 0.0 && max <= 1.0);
+            assert!(max - min > 0.2, "{:?} barely varies", texture);
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::WorldVec;
+
+/// An infinite plane.
+#[derive(Debug)]
+pub struct Plane {
+    point: WorldVec,
+    normal: WorldVec,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Plane {
+    /// A plane through `point`, facing in the direction of `normal`.
+    pub fn new(point: WorldVec, normal: WorldVec, material: std::rc::Rc<dyn material::Material>) -> Plane {
+        Plane {
+            point,
+            normal: normal.normalised(),
+            material,
+        }
+    }
+}
+
+/// Find the `t` at which `from` passes through the plane through `point` with normal `normal`, if
+/// that's within the ray's range.
+pub(crate) fn intersect_plane(from: &ray::Ray, point: WorldVec, normal: WorldVec) -> Option<f64> {
+    let denominator = normal.dot(*from.get_direction());
+    if denominator.abs() < 1e-12 {
+        // The ray is parallel to the plane.
+        return None;
+    }
+    let t = (point - *from.get_origin()).dot(normal) / denominator;
+    if !from.in_range(t) {
+        return None;
+    }
+    Some(t)
+}
+
+impl hit::Hit for Plane {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let t = intersect_plane(from, self.point, self.normal)?;
+        // The plane is infinite, so the UVs are just distances along the plane from `point`.
+        let (tangent, bitangent) = self.normal.tangent_basis();
+        let offset = from.at(t) - self.point;
+        Some(hit::MaterialHit::new(
+            std::rc::Rc::clone(&self.material),
+            from,
+            t,
+            self.normal,
+            (offset.dot(tangent), offset.dot(bitangent)),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_plane() {
+        let plane = Plane::new(
+            WorldVec::new(0.0, -1.0, 0.0),
+            WorldVec::new(0.0, 2.0, 0.0),
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        );
+        let ray = ray::Ray::new(WorldVec::new(3.0, 1.0, 0.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = plane.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert_eq!(*normal.get_origin(), WorldVec::new(3.0, -1.0, 0.0));
+        assert_eq!(*normal.get_direction(), WorldVec::new(0.0, 1.0, 0.0));
+
+        // Parallel to, and pointing away from, the plane.
+        let ray = ray::Ray::new(WorldVec::new(3.0, 1.0, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(plane.hit(&ray).is_none());
+        let ray = ray::Ray::new(WorldVec::new(3.0, 1.0, 0.0), WorldVec::new(0.0, 1.0, 0.0));
+        assert!(plane.hit(&ray).is_none());
+    }
+}
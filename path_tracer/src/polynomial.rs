@@ -0,0 +1,152 @@
+/// Evaluate a polynomial, given its coefficients from the lowest order up.
+pub fn evaluate(coefficients: &[f64], x: f64) -> f64 {
+    coefficients.iter().rev().fold(0.0, |acc, c| acc * x + c)
+}
+
+/// Find the real roots of a polynomial, given its coefficients from the lowest order up. The roots
+/// are returned in ascending order, with repeated roots only given once.
+///
+/// Rather than using the closed forms for cubics and quartics, which lose a lot of precision in
+/// the cases we care about (such as rays grazing a torus), we find the turning points of the
+/// polynomial from its derivative, and then bisect each monotonic section between them.
+pub fn real_roots(coefficients: &[f64]) -> Vec<f64> {
+    let degree = match coefficients.iter().rposition(|&c| c != 0.0) {
+        Some(degree) => degree,
+        None => return Vec::new(),
+    };
+    let coefficients = &coefficients[..=degree];
+    match degree {
+        0 => Vec::new(),
+        1 => vec![-coefficients[0] / coefficients[1]],
+        2 => quadratic_roots(coefficients[2], coefficients[1], coefficients[0]),
+        _ => {
+            let derivative: Vec<f64> = coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(i, c)| c * i as f64)
+                .collect();
+            // Cauchy's bound: every root is smaller than this in magnitude.
+            let bound = 1.0
+                + coefficients[..degree]
+                    .iter()
+                    .map(|c| (c / coefficients[degree]).abs())
+                    .fold(0.0, f64::max);
+            let mut points = vec![-bound];
+            points.extend(real_roots(&derivative).into_iter().filter(|x| x.abs() < bound));
+            points.push(bound);
+
+            let found: Vec<Option<f64>> = points
+                .windows(2)
+                .map(|section| bisect(coefficients, section[0], section[1]))
+                .collect();
+            let mut roots: Vec<f64> = Vec::new();
+            for (i, root) in found.iter().enumerate() {
+                // A turning point which only just misses zero, through rounding, is a repeated
+                // root, such as where a ray grazes a surface. Unless the sections either side
+                // already have a root near it.
+                let turning_point = points[i];
+                if i > 0 && found[i - 1].is_none() && root.is_none() && touches_zero(coefficients, turning_point) {
+                    roots.push(turning_point);
+                }
+                if let Some(root) = *root {
+                    // A root on a turning point is found in the sections either side of it.
+                    if roots.last() != Some(&root) {
+                        roots.push(root);
+                    }
+                }
+            }
+            roots
+        }
+    }
+}
+
+/// Whether a polynomial is zero at `x`, to within the rounding error of evaluating it there.
+fn touches_zero(coefficients: &[f64], x: f64) -> bool {
+    let magnitude = coefficients.iter().rev().fold(0.0, |acc, c| acc * x.abs() + c.abs());
+    evaluate(coefficients, x).abs() <= 1e-12 * magnitude
+}
+
+/// The real roots of `ax^2 + bx + c`, in ascending order. `a` must not be zero.
+pub fn quadratic_roots(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return Vec::new();
+    }
+    // Avoid subtracting two similar numbers, which loses precision.
+    let q = -0.5 * (b + b.signum() * discriminant.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    let (x0, x1) = (q / a, c / q);
+    if x0 == x1 {
+        vec![x0]
+    } else if x0 < x1 {
+        vec![x0, x1]
+    } else {
+        vec![x1, x0]
+    }
+}
+
+/// Find the root of a polynomial which is monotonic between `low` and `high`, if there is one.
+fn bisect(coefficients: &[f64], mut low: f64, mut high: f64) -> Option<f64> {
+    let mut f_low = evaluate(coefficients, low);
+    let f_high = evaluate(coefficients, high);
+    if f_low == 0.0 {
+        return Some(low);
+    }
+    if f_high == 0.0 {
+        return Some(high);
+    }
+    if f_low.signum() == f_high.signum() {
+        return None;
+    }
+    loop {
+        let middle = 0.5 * (low + high);
+        if middle <= low || middle >= high {
+            // We've run out of precision.
+            return Some(middle);
+        }
+        let f_middle = evaluate(coefficients, middle);
+        if f_middle == 0.0 {
+            return Some(middle);
+        }
+        if f_middle.signum() == f_low.signum() {
+            low = middle;
+            f_low = f_middle;
+        } else {
+            high = middle;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_real_roots() {
+        // (x - 1)(x - 2)(x + 3)(x - 4) = x^4 - 4x^3 - 7x^2 + 34x - 24
+        let roots = real_roots(&[-24.0, 34.0, -7.0, -4.0, 1.0]);
+        let expected = [-3.0, 1.0, 2.0, 4.0];
+        assert_eq!(roots.len(), expected.len());
+        for (root, expected) in roots.iter().zip(expected.iter()) {
+            assert!((root - expected).abs() < 1e-9, "{:?}", roots);
+        }
+
+        // x^4 + 1 has no real roots.
+        assert!(real_roots(&[1.0, 0.0, 0.0, 0.0, 1.0]).is_empty());
+
+        // (x - 1)^2 (x + 1) = x^3 - x^2 - x + 1 has a repeated root.
+        let roots = real_roots(&[1.0, -1.0, -1.0, 1.0]);
+        assert_eq!(roots, vec![-1.0, 1.0]);
+
+        // (x - 1)^2 (x + 1)^2, nudged up by less than its rounding error, still touches zero twice.
+        let roots = real_roots(&[1.0 + 1e-15, 0.0, -2.0, 0.0, 1.0]);
+        assert_eq!(roots.len(), 2, "{:?}", roots);
+        assert!((roots[0] + 1.0).abs() < 1e-9 && (roots[1] - 1.0).abs() < 1e-9);
+
+        // Leading zeros are ignored.
+        assert_eq!(real_roots(&[-2.0, 1.0, 0.0, 0.0]), vec![2.0]);
+    }
+}
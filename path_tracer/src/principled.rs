@@ -0,0 +1,493 @@
+//! A principled material in the style of Disney's BSDF: one material whose handful of intuitive
+//! parameters covers plastic, metal, glass, cloth and lacquered surfaces, and anything in between.
+//! It is the natural target when converting the material models of formats such as MTL and glTF.
+
+use crate::colour;
+use crate::combinator;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::texture;
+use crate::WorldVec;
+
+const PI: f64 = std::f64::consts::PI;
+
+/// The least probability of sampling the specular lobe of an opaque surface, so that the
+/// highlights of dull dielectrics (which reflect only a few percent) aren't left to chance.
+const MIN_SPECULAR_PROBABILITY: f64 = 0.25;
+
+/// The ways light can leave a principled surface, each of which is sampled separately.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Lobe {
+    Diffuse,
+    Specular,
+    Clearcoat,
+    Transmission,
+}
+
+const LOBES: [Lobe; 4] = [Lobe::Diffuse, Lobe::Specular, Lobe::Clearcoat, Lobe::Transmission];
+
+/// Every parameter is a texture. All but `base_colour` are read as scalars, and all but `ior` run
+/// from 0 to 1.
+#[derive(Debug)]
+pub struct Principled {
+    /// The diffuse colour of dielectrics, and the reflectance of metals.
+    pub base_colour: std::rc::Rc<dyn texture::Texture>,
+    /// From 0 for a dielectric to 1 for a metal.
+    pub metallic: std::rc::Rc<dyn texture::Texture>,
+    /// From 0 for a mirror to 1 for a very rough surface.
+    pub roughness: std::rc::Rc<dyn texture::Texture>,
+    /// Stretches highlights along the surface's tangent, from 0 (not at all) towards 1.
+    pub anisotropy: std::rc::Rc<dyn texture::Texture>,
+    /// The strength of an opaque dielectric's reflections: 0.5 reflects 4% head on, like most
+    /// materials, and 1 reflects 8%.
+    pub specular: std::rc::Rc<dyn texture::Texture>,
+    /// How much dielectric reflections take on the hue of the base colour.
+    pub specular_tint: std::rc::Rc<dyn texture::Texture>,
+    /// A soft glow at grazing angles, as on cloth.
+    pub sheen: std::rc::Rc<dyn texture::Texture>,
+    /// How much the sheen takes on the hue of the base colour.
+    pub sheen_tint: std::rc::Rc<dyn texture::Texture>,
+    /// The strength of a clear, colourless layer on top, like varnish.
+    pub clearcoat: std::rc::Rc<dyn texture::Texture>,
+    /// From 0 for a satin clearcoat to 1 for a glossy one.
+    pub clearcoat_gloss: std::rc::Rc<dyn texture::Texture>,
+    /// From 0 for an opaque dielectric to 1 for a clear one, like glass. Transmitted light is
+    /// tinted by the base colour.
+    pub transmission: std::rc::Rc<dyn texture::Texture>,
+    /// The refractive index inside the material, relative to outside, for transmission.
+    pub ior: std::rc::Rc<dyn texture::Texture>,
+}
+
+/// The parameters at some point on the surface, and what follows from them.
+#[derive(Debug)]
+struct Lobes {
+    base: [f64; 3],
+    roughness: f64,
+    /// The reflectance of the specular lobe, head on.
+    specular_f0: [f64; 3],
+    sheen: [f64; 3],
+    ggx: microfacet::Ggx,
+    clearcoat_ggx: microfacet::Ggx,
+    /// How much each lobe contributes, in the order of `LOBES`.
+    weights: [f64; 4],
+    /// How likely each lobe is to be sampled, in the order of `LOBES`.
+    probabilities: [f64; 4],
+    /// The ratio of the refractive index across the surface to the one on the viewer's side.
+    eta: f64,
+}
+
+impl Lobes {
+    fn choose(&self, u: f64) -> Lobe {
+        let mut total = 0.0;
+        for (&lobe, &probability) in LOBES.iter().zip(&self.probabilities) {
+            total += probability;
+            if u < total {
+                return lobe;
+            }
+        }
+        // Only reachable through rounding, so pick the last lobe which can be chosen at all.
+        LOBES
+            .iter()
+            .zip(&self.probabilities)
+            .rev()
+            .find(|(_, &probability)| probability > 0.0)
+            .map_or(Lobe::Specular, |(&lobe, _)| lobe)
+    }
+}
+
+/// Schlick's approximation of how reflectance rises from `f0` head on to 1 at grazing angles.
+fn schlick(f0: f64, cos: f64) -> f64 {
+    f0 + (1.0 - f0) * schlick_weight(cos)
+}
+
+fn schlick_weight(cos: f64) -> f64 {
+    (1.0 - cos.clamp(0.0, 1.0)).powi(5)
+}
+
+fn mix(a: f64, b: f64, s: f64) -> f64 {
+    a + (b - a) * s
+}
+
+fn max_channel(c: [f64; 3]) -> f64 {
+    c[0].max(c[1]).max(c[2])
+}
+
+/// A cosine-weighted direction in the hemisphere around z.
+fn sample_cosine_hemisphere((u1, u2): (f64, f64)) -> WorldVec {
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    WorldVec::new(r * phi.cos(), r * phi.sin(), (1.0 - u1).max(0.0).sqrt())
+}
+
+impl Principled {
+    /// A dielectric with the given base colour, and the other parameters at Disney's defaults:
+    /// medium roughness, 4% reflectance, and no sheen, clearcoat or transmission.
+    pub fn new(base_colour: std::rc::Rc<dyn texture::Texture>) -> Principled {
+        let scalar = |value: f64| -> std::rc::Rc<dyn texture::Texture> { std::rc::Rc::new(texture::Constant::scalar(value)) };
+        Principled {
+            base_colour,
+            metallic: scalar(0.0),
+            roughness: scalar(0.5),
+            anisotropy: scalar(0.0),
+            specular: scalar(0.5),
+            specular_tint: scalar(0.0),
+            sheen: scalar(0.0),
+            sheen_tint: scalar(0.5),
+            clearcoat: scalar(0.0),
+            clearcoat_gloss: scalar(1.0),
+            transmission: scalar(0.0),
+            ior: scalar(1.5),
+        }
+    }
+
+    /// Convert the parameters of a Wavefront MTL material, once they've been read from the file:
+    /// its diffuse colour (`Kd`), specular colour (`Ks`), Phong exponent (`Ns`), refractive index
+    /// (`Ni`) and opacity (`d`). The Phong exponent is matched to the roughness with the same
+    /// highlight width. An opacity below 1 cuts the surface out, through `combinator::Opacity`,
+    /// rather than making it transmissive: `d` is how much of the surface is there, not how clear
+    /// it is.
+    pub fn from_mtl(
+        diffuse: colour::Colour,
+        specular: colour::Colour,
+        shininess: f64,
+        ior: f64,
+        dissolve: f64,
+    ) -> std::rc::Rc<dyn material::Material> {
+        let surface = std::rc::Rc::new(Principled::mtl_surface(diffuse, specular, shininess, ior));
+        if dissolve >= 1.0 {
+            return surface;
+        }
+        let opacity = std::rc::Rc::new(texture::Constant::scalar(dissolve.clamp(0.0, 1.0)));
+        std::rc::Rc::new(combinator::Opacity::new(surface, opacity))
+    }
+
+    /// Everything about an MTL material but its opacity.
+    fn mtl_surface(diffuse: colour::Colour, specular: colour::Colour, shininess: f64, ior: f64) -> Principled {
+        let scalar = |value: f64| -> std::rc::Rc<dyn texture::Texture> { std::rc::Rc::new(texture::Constant::scalar(value)) };
+        let mut material = Principled::new(std::rc::Rc::new(texture::Constant::new(diffuse)));
+        // A Phong lobe with exponent n is about as wide as a microfacet one with alpha^2 = 2/(n+2).
+        let alpha = (2.0 / (shininess.max(0.0) + 2.0)).sqrt();
+        material.roughness = scalar(alpha.sqrt());
+        material.specular = scalar(specular.luminance().clamp(0.0, 1.0));
+        // Many exporters write an index of 1 to mean "unset".
+        if ior > 1.0 {
+            material.ior = scalar(ior);
+        }
+        material
+    }
+
+    /// Convert a glTF metallic-roughness material. glTF packs metallic into the blue channel and
+    /// roughness into the green channel of one texture, `metallic_roughness`, and they're picked
+    /// back out with `texture::Channel`. For a material with factors but no texture, pass a
+    /// constant of (0, roughness, metallic). Only the core material is converted; extensions such
+    /// as clearcoat, sheen and transmission aren't, but the fields of the same names can be set
+    /// afterwards.
+    pub fn from_gltf(
+        base_colour: std::rc::Rc<dyn texture::Texture>,
+        metallic_roughness: std::rc::Rc<dyn texture::Texture>,
+    ) -> Principled {
+        let mut material = Principled::new(base_colour);
+        material.metallic = std::rc::Rc::new(texture::Channel::blue(metallic_roughness.clone()));
+        material.roughness = std::rc::Rc::new(texture::Channel::green(metallic_roughness));
+        material
+    }
+
+    fn lobes(&self, hit: &hit::MaterialHit, towards_viewer: WorldVec) -> Lobes {
+        let scalar = |texture: &std::rc::Rc<dyn texture::Texture>| texture.scalar(hit).clamp(0.0, 1.0);
+        let colour = self.base_colour.colour(hit);
+        let base = [colour.get_red(), colour.get_green(), colour.get_blue()];
+        // The hue of the base colour, without its brightness.
+        let luminance = colour.luminance();
+        let hue = if luminance > 0.0 {
+            [base[0] / luminance, base[1] / luminance, base[2] / luminance]
+        } else {
+            [1.0; 3]
+        };
+
+        let metallic = scalar(&self.metallic);
+        let roughness = scalar(&self.roughness);
+        let (specular, specular_tint) = (scalar(&self.specular), scalar(&self.specular_tint));
+        let (sheen, sheen_tint) = (scalar(&self.sheen), scalar(&self.sheen_tint));
+        let mut specular_f0 = [0.0; 3];
+        let mut sheen_colour = [0.0; 3];
+        for channel in 0..3 {
+            let dielectric = specular * 0.08 * mix(1.0, hue[channel], specular_tint);
+            specular_f0[channel] = mix(dielectric, base[channel], metallic);
+            sheen_colour[channel] = sheen * mix(1.0, hue[channel], sheen_tint);
+        }
+
+        let transmission = (1.0 - metallic) * scalar(&self.transmission);
+        let weights = [
+            (1.0 - metallic) * (1.0 - scalar(&self.transmission)),
+            1.0 - transmission,
+            0.25 * scalar(&self.clearcoat),
+            transmission,
+        ];
+        let mut probabilities = [
+            weights[0] * max_channel(base),
+            weights[1] * max_channel(specular_f0).max(MIN_SPECULAR_PROBABILITY),
+            weights[2],
+            weights[3],
+        ];
+        let total: f64 = probabilities.iter().sum();
+        for probability in &mut probabilities {
+            *probability /= total;
+        }
+
+        let clearcoat_alpha = mix(0.1, 0.001, scalar(&self.clearcoat_gloss));
+        let ior = self.ior.scalar(hit).max(1e-3);
+        let outside = hit.intersected_surface_normal.get_direction().dot(towards_viewer) >= 0.0;
+        Lobes {
+            base,
+            roughness,
+            specular_f0,
+            sheen: sheen_colour,
+            ggx: microfacet::Ggx::from_roughness(roughness, scalar(&self.anisotropy)),
+            clearcoat_ggx: microfacet::Ggx {
+                alpha_x: clearcoat_alpha,
+                alpha_y: clearcoat_alpha,
+            },
+            weights,
+            probabilities,
+            eta: if outside { ior } else { 1.0 / ior },
+        }
+    }
+
+    /// The weight of light arriving from `wi` and leaving towards `wo` (both in local space, with
+    /// `wo` above the surface), for a direction chosen by `sample_gathering_ray`. This is the sum of
+    /// every lobe's contribution over the combined probability of any lobe choosing `wi`, so it
+    /// doesn't matter which lobe actually did.
+    fn weight(&self, lobes: &Lobes, wi: WorldVec, wo: WorldVec) -> [f64; 3] {
+        let [diffuse, specular, clearcoat, transmission] = lobes.weights;
+        let [p_diffuse, p_specular, p_clearcoat, p_transmission] = lobes.probabilities;
+        let ggx = &lobes.ggx;
+        let mut value = [0.0; 3];
+        let mut pdf = 0.0;
+        if wi.2 > 0.0 {
+            let h = (wi + wo).normalised();
+            let cos_d = wi.dot(h);
+
+            // Diffuse, brightened or darkened at grazing angles depending on roughness, plus sheen.
+            let fd90 = 0.5 + 2.0 * lobes.roughness * cos_d * cos_d;
+            let fd = (1.0 + (fd90 - 1.0) * schlick_weight(wi.2)) * (1.0 + (fd90 - 1.0) * schlick_weight(wo.2));
+            // Specular, with visible normals sampled so that only the masking from wo cancels.
+            let d = ggx.distribution(h);
+            let specular_term = d * ggx.masking_shadowing(wi, wo) / (4.0 * wo.2);
+            let specular_pdf = d * ggx.masking(wo) / (4.0 * wo.2);
+            // A colourless clearcoat, always with a 4% reflectance head on.
+            let clearcoat_ggx = &lobes.clearcoat_ggx;
+            let d_clearcoat = clearcoat_ggx.distribution(h);
+            let clearcoat_term =
+                schlick(0.04, cos_d) * d_clearcoat * clearcoat_ggx.masking_shadowing(wi, wo) / (4.0 * wo.2);
+            // The reflections of the transparent part of the surface.
+            let reflectance = microfacet::fresnel_dielectric(wo.dot(h), lobes.eta);
+
+            for (channel, value) in value.iter_mut().enumerate() {
+                *value = diffuse
+                    * (lobes.base[channel] / PI * fd + lobes.sheen[channel] * schlick_weight(cos_d))
+                    * wi.2
+                    + specular * schlick(lobes.specular_f0[channel], cos_d) * specular_term
+                    + clearcoat * clearcoat_term
+                    + transmission * reflectance * specular_term;
+            }
+            pdf = p_diffuse * wi.2 / PI
+                + p_specular * specular_pdf
+                + p_clearcoat * d_clearcoat * clearcoat_ggx.masking(wo) / (4.0 * wo.2)
+                + p_transmission * reflectance * specular_pdf;
+        } else if transmission > 0.0 {
+            let eta = lobes.eta;
+            let h = (wo + wi * eta).normalised();
+            let h = if h.2 < 0.0 { -h } else { h };
+            let (cos_o, cos_i) = (wo.dot(h), wi.dot(h));
+            if cos_o > 0.0 && cos_i < 0.0 {
+                // The change from the density of half vectors to that of refracted directions.
+                let denominator = cos_o + eta * cos_i;
+                let jacobian = eta * eta * -cos_i / (denominator * denominator);
+                let wi_above = WorldVec::new(wi.0, wi.1, -wi.2);
+                let common =
+                    (1.0 - microfacet::fresnel_dielectric(cos_o, eta)) * ggx.distribution(h) * cos_o * jacobian / wo.2;
+                let shadowing = ggx.masking_shadowing(wi_above, wo);
+                for (channel, value) in value.iter_mut().enumerate() {
+                    *value = transmission * lobes.base[channel] * common * shadowing;
+                }
+                pdf = p_transmission * common * ggx.masking(wo);
+            }
+        }
+
+        if !(pdf.is_finite() && pdf > 0.0) {
+            return [0.0; 3];
+        }
+        [value[0] / pdf, value[1] / pdf, value[2] / pdf]
+    }
+}
+
+impl material::Material for Principled {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        let up = WorldVec::new(0.0, 0.0, 1.0);
+        let origin = *hit.intersected_surface_normal.get_origin();
+        // Seen exactly edge on (or from behind the shading normal), just mirror the ray.
+        if wo.2 <= 0.0 {
+            return ray::Ray::new(origin, frame.to_world(microfacet::reflect(wo, up)));
+        }
+
+        let lobes = self.lobes(hit, towards_viewer);
+        let u = (microfacet::random(), microfacet::random());
+        let wi = match lobes.choose(microfacet::random()) {
+            Lobe::Diffuse => sample_cosine_hemisphere(u),
+            Lobe::Specular => microfacet::reflect(wo, lobes.ggx.sample_visible_normal(wo, u)),
+            Lobe::Clearcoat => microfacet::reflect(wo, lobes.clearcoat_ggx.sample_visible_normal(wo, u)),
+            Lobe::Transmission => {
+                let h = lobes.ggx.sample_visible_normal(wo, u);
+                if microfacet::random() < microfacet::fresnel_dielectric(wo.dot(h), lobes.eta) {
+                    microfacet::reflect(wo, h)
+                } else {
+                    microfacet::refract(wo, h, lobes.eta).unwrap_or_else(|| microfacet::reflect(wo, h))
+                }
+            }
+        };
+        ray::Ray::new(origin, frame.to_world(wi))
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        material::attenuate(&start_colour, &self.base_colour.colour(hit))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wo.2 <= 0.0 || wi.2 == 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let [r, g, b] = self.weight(&self.lobes(hit, towards_viewer), wi, wo);
+        material::attenuate(&start_colour, &colour::Colour::unclamped(r, g, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{hit_on_plane, mean_weight};
+    use crate::sampler;
+    use material::Material;
+
+    fn scalar(value: f64) -> std::rc::Rc<dyn texture::Texture> {
+        std::rc::Rc::new(texture::Constant::scalar(value))
+    }
+
+    #[test]
+    fn test_principled() {
+        sampler::replace_current(sampler::Sampler::new(3));
+        let direction = WorldVec::new(0.4, 0.0, -1.0).normalised();
+
+        // A white plastic reflects nearly everything, without creating energy.
+        let plastic = Principled::new(scalar(1.0));
+        let ([r, g, b], transmitted) = mean_weight(std::rc::Rc::new(plastic), direction, 4000);
+        assert!(r > 0.85 && r < 1.05, "{}", r);
+        assert_eq!((r, g), (g, b));
+        // Only reflections off steep facets end up below the surface, and they carry no light.
+        assert!(transmitted < 0.05, "{}", transmitted);
+        // Some samples weigh more than 1 (where a lobe reflects more than it's sampled), and keep
+        // their weight, or the average would come out too dark.
+        let plastic = std::rc::Rc::new(Principled::new(scalar(1.0)));
+        let (from, hit) = hit_on_plane(plastic.clone(), direction);
+        let heaviest = (0..1000)
+            .map(|_| {
+                let towards_light = plastic.sample_gathering_ray(&from, &hit).get_direction().normalised();
+                plastic.colour_between(colour::Colour::new(1.0, 1.0, 1.0), &hit, towards_light, -direction).get_red()
+            })
+            .fold(0.0, f64::max);
+        assert!(heaviest > 1.0, "{}", heaviest);
+
+        // A red metal reflects red, and nothing gets through it even if it's marked transmissive.
+        let mut metal = Principled::new(std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.9, 0.1, 0.1))));
+        metal.metallic = scalar(1.0);
+        metal.roughness = scalar(0.3);
+        metal.transmission = scalar(1.0);
+        let ([r, _, b], transmitted) = mean_weight(std::rc::Rc::new(metal), direction, 2000);
+        assert!(r > 0.7 && b < 0.3, "{} {}", r, b);
+        assert!(transmitted < 0.05, "{}", transmitted);
+
+        // Glass lets most light through, and loses little.
+        let mut glass = Principled::new(scalar(1.0));
+        glass.transmission = scalar(1.0);
+        glass.roughness = scalar(0.2);
+        let ([r, _, _], transmitted) = mean_weight(std::rc::Rc::new(glass), direction, 2000);
+        assert!(r > 0.9 && r <= 1.0, "{}", r);
+        assert!(transmitted > 0.85, "{}", transmitted);
+
+        // A smooth metal reflects at the mirror angle.
+        let mut mirror = Principled::new(scalar(1.0));
+        mirror.metallic = scalar(1.0);
+        mirror.roughness = scalar(0.0);
+        let mirror = std::rc::Rc::new(mirror);
+        let (from, hit) = hit_on_plane(mirror.clone(), direction);
+        let reflected = mirror.sample_gathering_ray(&from, &hit).get_direction().normalised();
+        assert!((reflected - WorldVec::new(direction.0, 0.0, -direction.2)).length() < 1e-3);
+    }
+
+    #[test]
+    fn test_from_mtl() {
+        sampler::replace_current(sampler::Sampler::new(17));
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let (_, hit) = hit_on_plane(std::rc::Rc::new(Principled::new(scalar(1.0))), WorldVec::new(0.0, 0.0, -1.0));
+        // A very high exponent is nearly a mirror, and a low one is rough.
+        let shiny = Principled::mtl_surface(white.clone(), white.clone(), 10000.0, 1.0);
+        let dull = Principled::mtl_surface(white.clone(), white.clone(), 1.0, 1.0);
+        assert!(shiny.roughness.scalar(&hit) < 0.15);
+        assert!(dull.roughness.scalar(&hit) > 0.9);
+        // Unset indices stay at the default.
+        assert_eq!(dull.ior.scalar(&hit), 1.5);
+        let clear = Principled::mtl_surface(white.clone(), white.clone(), 100.0, 1.33);
+        assert_eq!(clear.ior.scalar(&hit), 1.33);
+        assert_eq!(clear.transmission.scalar(&hit), 0.0);
+
+        // An opaque material is just the surface, and a dissolved one is cut out in proportion.
+        let direction = WorldVec::new(0.3, 0.0, -1.0).normalised();
+        let opaque = Principled::from_mtl(white.clone(), white.clone(), 100.0, 1.33, 1.0);
+        let (_, hit) = hit_on_plane(opaque.clone(), direction);
+        assert!(opaque.choose(&hit, -direction).is_none());
+        let dissolved = Principled::from_mtl(white.clone(), white, 100.0, 1.33, 0.25);
+        let (from, hit) = hit_on_plane(dissolved, direction);
+        let passed = (0..2000)
+            .filter(|_| {
+                let resolved = material::resolve(hit.clone(), -direction);
+                *resolved.material.sample_gathering_ray(&from, &resolved).get_direction() == direction
+            })
+            .count();
+        assert!((1400..1600).contains(&passed), "{}", passed);
+    }
+
+    #[test]
+    fn test_from_gltf() {
+        sampler::replace_current(sampler::Sampler::new(18));
+        let direction = WorldVec::new(0.4, 0.0, -1.0).normalised();
+        let gold = std::rc::Rc::new(texture::Constant::new(colour::Colour::new(1.0, 0.78, 0.34)));
+        // Metallic and roughness are read from the blue and green channels.
+        let packed = std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.0, 0.2, 1.0)));
+        let metal = Principled::from_gltf(gold.clone(), packed);
+        let (_, hit) = hit_on_plane(std::rc::Rc::new(Principled::new(scalar(1.0))), direction);
+        assert_eq!(metal.metallic.scalar(&hit), 1.0);
+        assert_eq!(metal.roughness.scalar(&hit), 0.2);
+        // So this reflects the colour of gold.
+        let ([r, g, b], _) = mean_weight(std::rc::Rc::new(metal), direction, 2000);
+        assert!(r > 0.8 && r > g && g > b, "{} {} {}", r, g, b);
+
+        // And this is a rough, gold-coloured plastic, whose highlights aren't tinted.
+        let packed = std::rc::Rc::new(texture::Constant::new(colour::Colour::new(0.0, 0.9, 0.0)));
+        let plastic = Principled::from_gltf(gold, packed);
+        assert_eq!(plastic.metallic.scalar(&hit), 0.0);
+        let lobes = plastic.lobes(&hit, -direction);
+        assert_eq!(lobes.specular_f0, [0.04; 3]);
+    }
+}
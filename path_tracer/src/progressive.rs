@@ -0,0 +1,200 @@
+use crate::camera;
+use crate::film;
+use crate::hit::Hit;
+use crate::image;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How much work a progressive render may do. The render stops as soon as any of the limits which
+/// are set is reached. If none are set, the render only stops when it is cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct RenderBudget {
+    /// The maximum number of passes (samples per pixel) to render.
+    pub max_passes: Option<usize>,
+    /// The maximum wall-clock time to spend rendering.
+    pub time: Option<Duration>,
+    /// Stop once the standard error of the pixels, averaged over the image, is at most this.
+    pub target_noise: Option<f64>,
+}
+
+/// A handle which can stop a progressive render, possibly from another thread. Clones share the
+/// same underlying flag.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> CancelToken {
+        CancelToken(Arc::new(AtomicBool::new(false)))
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+/// An in-progress render, created by `Camera::render_progressive`. Each call to `next` renders
+/// another pass over the image and returns the image so far.
+pub struct ProgressiveRender<'camera, T: Hit> {
+    camera: &'camera camera::Camera<T>,
+    projection: camera::Projection,
+    film: film::Film,
+    bounces: usize,
+    budget: RenderBudget,
+    cancel: CancelToken,
+    started: Instant,
+    passes: usize,
+    finished: bool,
+}
+
+impl<'camera, T: Hit> ProgressiveRender<'camera, T> {
+    pub(crate) fn new(
+        camera: &'camera camera::Camera<T>,
+        x_size: usize,
+        y_size: usize,
+        fov: f64,
+        bounces: usize,
+        budget: RenderBudget,
+        cancel: CancelToken,
+    ) -> ProgressiveRender<'camera, T> {
+        ProgressiveRender {
+            camera,
+            projection: camera.projection(x_size, y_size, fov),
+            film: film::Film::seeded(x_size, y_size, camera.seed(), (0, 0)),
+            bounces,
+            budget,
+            cancel,
+            started: Instant::now(),
+            passes: 0,
+            finished: false,
+        }
+    }
+
+    /// The accumulated samples so far.
+    pub fn film(&self) -> &film::Film {
+        &self.film
+    }
+
+    /// The number of passes which have been started so far. The last of these may not have
+    /// covered the whole image if the render was stopped part-way through.
+    pub fn passes(&self) -> usize {
+        self.passes
+    }
+
+    /// Our current estimate of the noise in the image. See `Film::mean_standard_error`.
+    pub fn noise(&self) -> f64 {
+        self.film.mean_standard_error()
+    }
+
+    fn out_of_time(&self) -> bool {
+        self.cancel.is_cancelled()
+            || self
+                .budget
+                .time
+                .is_some_and(|time| self.started.elapsed() >= time)
+    }
+
+    fn budget_spent(&self) -> bool {
+        self.out_of_time()
+            || self
+                .budget
+                .max_passes
+                .is_some_and(|max_passes| self.passes >= max_passes)
+            || self
+                .budget
+                .target_noise
+                .is_some_and(|target_noise| self.noise() <= target_noise)
+    }
+}
+
+impl<T: Hit> Iterator for ProgressiveRender<'_, T> {
+    type Item = image::Image;
+
+    fn next(&mut self) -> Option<image::Image> {
+        if self.finished || self.budget_spent() {
+            self.finished = true;
+            return None;
+        }
+        self.passes += 1;
+        for j in 0..self.film.y_len() {
+            // Check between rows so that we can stop promptly, even part-way through a pass. The
+            // film tracks the samples per pixel, so a partial pass still averages correctly.
+            if self.out_of_time() {
+                self.finished = true;
+                break;
+            }
+            for i in 0..self.film.x_len() {
+                let pixel = &mut self.film[(i, j)];
+                let colour = self.camera.sample_pixel(
+                    &self.projection,
+                    (i, j),
+                    self.bounces,
+                    &mut pixel.sampler,
+                );
+                pixel.add_sample(&colour);
+            }
+        }
+        Some(self.film.to_image())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene;
+    use crate::vec3;
+
+    fn camera() -> camera::Camera<scene::Scene<'static>> {
+        camera::Camera::new_looking_at(
+            std::rc::Rc::new(scene::Scene::new()),
+            vec3::Vec3::new(0.0, 0.0, -3.0),
+            vec3::Vec3::new(0.0, 1.0, 0.0),
+            vec3::Vec3::new(0.0, 0.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn test_pass_budget() {
+        let camera = camera();
+        let budget = RenderBudget {
+            max_passes: Some(3),
+            ..Default::default()
+        };
+        let mut render = camera.render_progressive(8, 4, 60.0, 10, budget, CancelToken::new());
+        assert_eq!(render.by_ref().count(), 3);
+        assert!(render.film().pixels().iter().all(|p| p.samples == 3));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let camera = camera();
+        let cancel = CancelToken::new();
+        let mut render =
+            camera.render_progressive(8, 4, 60.0, 10, RenderBudget::default(), cancel.clone());
+        assert!(render.next().is_some());
+
+        // Cancel from another thread. The render should stop at the next opportunity.
+        std::thread::spawn(move || cancel.cancel()).join().unwrap();
+        assert!(render.next().is_none());
+        assert_eq!(render.passes(), 1);
+    }
+
+    #[test]
+    fn test_noise_budget() {
+        let camera = camera();
+        let budget = RenderBudget {
+            max_passes: Some(1000),
+            target_noise: Some(0.05),
+            ..Default::default()
+        };
+        let mut render = camera.render_progressive(8, 4, 60.0, 10, budget, CancelToken::new());
+        let passes = render.by_ref().count();
+        assert!(passes < 1000);
+        assert!(render.noise() <= 0.05);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::plane;
+use crate::ray;
+use crate::sampler;
+use crate::WorldVec;
+
+/// A parallelogram, given by one corner and the two edges leaving it. Quads can be used as area
+/// lights by giving them an `Emissive` material, and can have points on them sampled uniformly,
+/// so that `Camera::add_light` can light surfaces from them directly.
+#[derive(Debug)]
+pub struct Quad {
+    corner: WorldVec,
+    edge_u: WorldVec,
+    edge_v: WorldVec,
+    /// `edge_u` cross `edge_v`, scaled so that it can be used to find the UVs of a point.
+    w: WorldVec,
+    normal: WorldVec,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+/// A point chosen on a light, and the probability density of choosing it.
+#[derive(Debug, Copy, Clone)]
+pub struct LightSample {
+    pub point: WorldVec,
+    pub normal: WorldVec,
+    /// The density of this sample per unit solid angle, as seen from the point being lit.
+    pub pdf: f64,
+}
+
+impl Quad {
+    /// A quad with corners `corner`, `corner + edge_u`, `corner + edge_u + edge_v` and
+    /// `corner + edge_v`. The normal faces along `edge_u` cross `edge_v`.
+    pub fn new(
+        corner: WorldVec,
+        edge_u: WorldVec,
+        edge_v: WorldVec,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Quad {
+        let n = edge_u.cross(edge_v);
+        Quad {
+            corner,
+            edge_u,
+            edge_v,
+            w: n * (1.0 / n.dot(n)),
+            normal: n.normalised(),
+            material,
+        }
+    }
+
+    /// An axis-aligned rectangle between two opposite corners, which must share one coordinate.
+    /// The normal faces along the positive direction of that axis. Panics if the corners don't
+    /// share exactly one coordinate.
+    pub fn axis_aligned(
+        min: WorldVec,
+        max: WorldVec,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Quad {
+        let size = max - min;
+        let (edge_u, edge_v) = match (size.0 == 0.0, size.1 == 0.0, size.2 == 0.0) {
+            (true, false, false) => (WorldVec::new(0.0, size.1, 0.0), WorldVec::new(0.0, 0.0, size.2)),
+            (false, true, false) => (WorldVec::new(0.0, 0.0, size.2), WorldVec::new(size.0, 0.0, 0.0)),
+            (false, false, true) => (WorldVec::new(size.0, 0.0, 0.0), WorldVec::new(0.0, size.1, 0.0)),
+            _ => panic!("An axis-aligned rectangle must be flat along exactly one axis."),
+        };
+        // Keep the normal on the positive side, whichever way round the corners were given.
+        let normal = edge_u.cross(edge_v);
+        if normal.0 + normal.1 + normal.2 < 0.0 {
+            Quad::new(min + edge_v, edge_u, -edge_v, material)
+        } else {
+            Quad::new(min, edge_u, edge_v, material)
+        }
+    }
+
+    pub fn area(&self) -> f64 {
+        self.edge_u.cross(self.edge_v).length()
+    }
+
+    pub fn normal(&self) -> WorldVec {
+        self.normal
+    }
+
+    /// The UV coordinates of a point on the quad's plane. The point is on the quad if both are
+    /// between 0 and 1.
+    fn uv(&self, point: WorldVec) -> (f64, f64) {
+        let offset = point - self.corner;
+        (
+            self.w.dot(offset.cross(self.edge_v)),
+            self.w.dot(self.edge_u.cross(offset)),
+        )
+    }
+
+    /// Choose a point uniformly over the quad's area.
+    pub fn sample_point(&self) -> WorldVec {
+        use rand::distributions::{Distribution, Uniform};
+        let between = Uniform::from(0.0..1.0);
+        let (u, v) = sampler::with_current(|rng| (between.sample(rng), between.sample(rng)));
+        self.corner + self.edge_u * u + self.edge_v * v
+    }
+
+    /// Choose a point on the quad to light `from` with.
+    pub fn sample(&self, from: WorldVec) -> LightSample {
+        let point = self.sample_point();
+        LightSample {
+            point,
+            normal: self.normal,
+            pdf: self.solid_angle_pdf(from, point),
+        }
+    }
+
+    /// The density (per unit solid angle) with which `sample` would have chosen the point where
+    /// `from` hits the quad, or 0 if it misses.
+    pub fn pdf(&self, from: &ray::Ray) -> f64 {
+        match self.intersect(from) {
+            Some((t, _)) => self.solid_angle_pdf(*from.get_origin(), from.at(t)),
+            None => 0.0,
+        }
+    }
+
+    /// Convert the uniform density over the quad's area into a density over the solid angle it
+    /// covers as seen from `from`.
+    fn solid_angle_pdf(&self, from: WorldVec, point: WorldVec) -> f64 {
+        let to_point = point - from;
+        let distance_squared = to_point.dot(to_point);
+        let cosine = self.normal.dot(to_point).abs() / distance_squared.sqrt();
+        if cosine == 0.0 {
+            return 0.0;
+        }
+        distance_squared / (cosine * self.area())
+    }
+
+    /// Returns the `t` and UVs of where `from` hits the quad.
+    fn intersect(&self, from: &ray::Ray) -> Option<(f64, (f64, f64))> {
+        let t = plane::intersect_plane(from, self.corner, self.normal)?;
+        let uv = self.uv(from.at(t));
+        if (0.0..=1.0).contains(&uv.0) && (0.0..=1.0).contains(&uv.1) {
+            Some((t, uv))
+        } else {
+            None
+        }
+    }
+}
+
+impl hit::Hit for Quad {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let (t, uv) = self.intersect(from)?;
+        Some(
+            hit::MaterialHit::new(std::rc::Rc::clone(&self.material), from, t, self.normal, uv)
+                .with_tangent(self.edge_u),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::from_points(&[
+            self.corner,
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v,
+        ]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::emissive;
+    use crate::hit::Hit;
+
+    fn light() -> Quad {
+        Quad::axis_aligned(
+            WorldVec::new(-1.0, 4.0, -2.0),
+            WorldVec::new(1.0, 4.0, 2.0),
+            std::rc::Rc::new(emissive::Emissive::new(colour::Colour::new(1.0, 1.0, 1.0))),
+        )
+    }
+
+    #[test]
+    fn test_hit_quad() {
+        let quad = light();
+        assert_eq!(quad.normal(), WorldVec::new(0.0, 1.0, 0.0));
+        assert_eq!(quad.area(), 8.0);
+
+        let ray = ray::Ray::new(WorldVec::new(0.5, 0.0, 1.0), WorldVec::new(0.0, 1.0, 0.0));
+        let hit = quad.hit(&ray).expect("The ray should hit.");
+        assert_eq!(*hit.intersected_surface_normal.get_origin(), WorldVec::new(0.5, 4.0, 1.0));
+        assert!((hit.uv.0 - 0.75).abs() < 1e-12 && (hit.uv.1 - 0.75).abs() < 1e-12);
+
+        let ray = ray::Ray::new(WorldVec::new(1.5, 0.0, 1.0), WorldVec::new(0.0, 1.0, 0.0));
+        assert!(quad.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_sample_quad() {
+        let quad = light();
+        let from = WorldVec::new(0.0, 0.0, 0.0);
+        let mut sum = WorldVec::new(0.0, 0.0, 0.0);
+        let samples = 10000;
+        for _ in 0..samples {
+            let sample = quad.sample(from);
+            let (u, v) = quad.uv(sample.point);
+            assert!((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v));
+            assert!((sample.point.1 - 4.0).abs() < 1e-12);
+
+            // The density of a sample should match the density of a ray towards it.
+            let towards = ray::Ray::new(from, (sample.point - from).normalised());
+            assert!((quad.pdf(&towards) - sample.pdf).abs() < 1e-9 * sample.pdf);
+            sum = sum + sample.point;
+        }
+        let mean = sum * (1.0 / samples as f64);
+        assert!((mean - WorldVec::new(0.0, 4.0, 0.0)).length() < 0.05);
+
+        let away = ray::Ray::new(from, WorldVec::new(0.0, -1.0, 0.0));
+        assert_eq!(quad.pdf(&away), 0.0);
+    }
+}
@@ -0,0 +1,310 @@
+/// An image loaded from a file, with each channel as a float. 8 and 16 bit images are scaled to
+/// lie between 0 and 1, while HDR images keep their full range; no colour space conversion is
+/// done.
+#[derive(Debug, Clone)]
+pub struct Raster {
+    x_size: usize,
+    y_size: usize,
+    pixels: Vec<[f64; 3]>,
+}
+
+impl Raster {
+    /// Panics if `pixels` isn't `x_size * y_size` long.
+    pub fn new(x_size: usize, y_size: usize, pixels: Vec<[f64; 3]>) -> Raster {
+        assert_eq!(pixels.len(), x_size * y_size, "Wrong number of pixels for the raster size.");
+        Raster {
+            x_size,
+            y_size,
+            pixels,
+        }
+    }
+
+    /// Load a PNG, a Radiance HDR file, or a binary or plain PGM or PPM file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> std::io::Result<Raster> {
+        Raster::decode(&std::fs::read(path)?)
+    }
+
+    /// Decode an image file held in memory. The format is worked out from the data itself.
+    pub fn decode(bytes: &[u8]) -> std::io::Result<Raster> {
+        if bytes.starts_with(b"\x89PNG") {
+            decode_png(bytes)
+        } else if bytes.starts_with(b"#?") {
+            decode_hdr(bytes)
+        } else if bytes.starts_with(b"P") {
+            decode_netpbm(bytes)
+        } else {
+            Err(invalid_data("Unrecognised image format"))
+        }
+    }
+
+    pub fn x_len(&self) -> usize {
+        self.x_size
+    }
+
+    pub fn y_len(&self) -> usize {
+        self.y_size
+    }
+}
+
+impl std::ops::Index<(usize, usize)> for Raster {
+    type Output = [f64; 3];
+    fn index(&self, (x, y): (usize, usize)) -> &[f64; 3] {
+        &self.pixels[y * self.x_size + x]
+    }
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+fn decode_png(bytes: &[u8]) -> std::io::Result<Raster> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Expand palettes and low bit depths out to 8 bits per channel.
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder
+        .read_info()
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+    let channels = info.color_type.samples();
+    let samples: Vec<f64> = match info.bit_depth {
+        png::BitDepth::Sixteen => buffer[..info.buffer_size()]
+            .chunks(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]) as f64 / u16::MAX as f64)
+            .collect(),
+        _ => buffer[..info.buffer_size()]
+            .iter()
+            .map(|&value| value as f64 / u8::MAX as f64)
+            .collect(),
+    };
+    let pixels = samples
+        .chunks(channels)
+        .map(|pixel| match pixel.len() {
+            // Greyscale, possibly with alpha, which we ignore.
+            1 | 2 => [pixel[0], pixel[0], pixel[0]],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .collect();
+    Ok(Raster::new(info.width as usize, info.height as usize, pixels))
+}
+
+/// Decode the Radiance RGBE format, flat or with per-scanline run length encoding. Only the
+/// usual `-Y <height> +X <width>` orientation is supported.
+fn decode_hdr(bytes: &[u8]) -> std::io::Result<Raster> {
+    // The header is lines of text, ending with a blank line and then the resolution.
+    let mut lines = bytes.split(|&b| b == b'\n');
+    let mut position = 0;
+    let mut next_line = || {
+        let line = lines.next()?;
+        position += line.len() + 1;
+        Some(line)
+    };
+    while !next_line()
+        .ok_or_else(|| invalid_data("HDR header is truncated"))?
+        .is_empty()
+    {}
+    let resolution = next_line().ok_or_else(|| invalid_data("HDR header is truncated"))?;
+    let resolution = std::str::from_utf8(resolution).unwrap_or("");
+    let (y_size, x_size) = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+        ["-Y", height, "+X", width] => (height.parse().ok(), width.parse().ok()),
+        _ => (None, None),
+    };
+    let (x_size, y_size): (usize, usize) = x_size
+        .zip(y_size)
+        .ok_or_else(|| invalid_data("Unsupported HDR resolution"))?;
+
+    let mut data = &bytes[position.min(bytes.len())..];
+    // A run can repeat a byte at most 127 times, so each pixel takes at least 8/127 bytes. Check
+    // that before allocating anything.
+    let count = x_size
+        .checked_mul(y_size)
+        .filter(|&count| count.max(x_size) / 127 * 8 <= data.len())
+        .ok_or_else(|| invalid_data("Image data is truncated"))?;
+    let mut take = |count: usize| -> std::io::Result<&[u8]> {
+        if data.len() < count {
+            return Err(invalid_data("Image data is truncated"));
+        }
+        let (taken, rest) = data.split_at(count);
+        data = rest;
+        Ok(taken)
+    };
+    let mut pixels = Vec::with_capacity(count);
+    let mut scanline = vec![[0u8; 4]; x_size];
+    for _ in 0..y_size {
+        let start = take(4)?;
+        let encoded = (8..0x8000).contains(&x_size)
+            && start[0] == 2
+            && start[1] == 2
+            && ((start[2] as usize) << 8 | start[3] as usize) == x_size;
+        if encoded {
+            // Each component is stored separately, as runs (a count over 128, then a byte to
+            // repeat) or literal strings (a count, then that many bytes).
+            for component in 0..4 {
+                let mut x = 0;
+                while x < x_size {
+                    let count = take(1)?[0] as usize;
+                    let (run, length) = if count > 128 { (true, count - 128) } else { (false, count) };
+                    if length == 0 || x + length > x_size {
+                        return Err(invalid_data("Invalid HDR run length"));
+                    }
+                    let values = take(if run { 1 } else { length })?;
+                    for (i, pixel) in scanline[x..x + length].iter_mut().enumerate() {
+                        pixel[component] = values[if run { 0 } else { i }];
+                    }
+                    x += length;
+                }
+            }
+        } else {
+            scanline[0].copy_from_slice(start);
+            for pixel in scanline.iter_mut().skip(1) {
+                pixel.copy_from_slice(take(4)?);
+            }
+        }
+        pixels.extend(scanline.iter().map(|&[r, g, b, e]| {
+            if e == 0 {
+                [0.0; 3]
+            } else {
+                let scale = 2f64.powi(e as i32 - 136);
+                [r as f64 * scale, g as f64 * scale, b as f64 * scale]
+            }
+        }));
+    }
+    Ok(Raster::new(x_size, y_size, pixels))
+}
+
+/// Decode the PGM (P2 and P5) and PPM (P3 and P6) formats.
+fn decode_netpbm(bytes: &[u8]) -> std::io::Result<Raster> {
+    let (channels, binary) = match &bytes[..2.min(bytes.len())] {
+        b"P2" => (1, false),
+        b"P3" => (3, false),
+        b"P5" => (1, true),
+        b"P6" => (3, true),
+        _ => return Err(invalid_data("Unsupported PNM format")),
+    };
+
+    // The header is whitespace-separated numbers, with comments starting with '#'.
+    let mut position = 2;
+    let next_number = |position: &mut usize| -> std::io::Result<usize> {
+        loop {
+            match bytes.get(*position) {
+                Some(b'#') => {
+                    while bytes.get(*position).is_some_and(|&b| b != b'\n') {
+                        *position += 1;
+                    }
+                }
+                Some(b) if b.is_ascii_whitespace() => *position += 1,
+                _ => break,
+            }
+        }
+        let start = *position;
+        while bytes.get(*position).is_some_and(|b| b.is_ascii_digit()) {
+            *position += 1;
+        }
+        std::str::from_utf8(&bytes[start..*position])
+            .ok()
+            .and_then(|digits| digits.parse().ok())
+            .ok_or_else(|| invalid_data("Expected a number"))
+    };
+    let x_size = next_number(&mut position)?;
+    let y_size = next_number(&mut position)?;
+    let max_value = next_number(&mut position)?;
+    if max_value == 0 || max_value > u16::MAX as usize {
+        return Err(invalid_data("Invalid maximum value"));
+    }
+
+    let truncated = || invalid_data("Image data is truncated");
+    // Each sample takes at least a byte, so the data must be at least that long before we allocate
+    // anything for it.
+    let count = x_size
+        .checked_mul(y_size)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .filter(|&count| count <= bytes.len().saturating_sub(position))
+        .ok_or_else(truncated)?;
+    let samples: Vec<usize> = if binary {
+        // Exactly one whitespace character separates the header from the data.
+        position += 1;
+        let width = if max_value < 256 { 1 } else { 2 };
+        let data = count
+            .checked_mul(width)
+            .and_then(|length| bytes.get(position..position.checked_add(length)?))
+            .ok_or_else(truncated)?;
+        data.chunks(width)
+            .map(|sample| sample.iter().fold(0, |acc, &b| acc << 8 | b as usize))
+            .collect()
+    } else {
+        (0..count)
+            .map(|_| next_number(&mut position))
+            .collect::<std::io::Result<_>>()?
+    };
+    let scale = 1.0 / max_value as f64;
+    let pixels = samples
+        .chunks(channels)
+        .map(|pixel| {
+            let channel = |i: usize| pixel[i.min(channels - 1)] as f64 * scale;
+            [channel(0), channel(1), channel(2)]
+        })
+        .collect();
+    Ok(Raster::new(x_size, y_size, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let plain = Raster::decode(b"P2\n# A comment.\n3 2\n4\n0 1 2\n3 4 4\n").unwrap();
+        assert_eq!((plain.x_len(), plain.y_len()), (3, 2));
+        assert_eq!(plain[(1, 1)], [1.0, 1.0, 1.0]);
+        assert_eq!(plain[(2, 0)], [0.5, 0.5, 0.5]);
+
+        let mut binary = b"P6 1 1 255\n".to_vec();
+        binary.extend_from_slice(&[255, 0, 51]);
+        assert_eq!(Raster::decode(&binary).unwrap()[(0, 0)], [1.0, 0.0, 0.2]);
+
+        // Round trip through the png encoder.
+        let mut png_bytes = Vec::new();
+        {
+            let mut encoder = png::Encoder::new(&mut png_bytes, 2, 1);
+            encoder.set_color(png::ColorType::Grayscale);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().unwrap();
+            writer.write_image_data(&[0, 255]).unwrap();
+        }
+        let png = Raster::decode(&png_bytes).unwrap();
+        assert_eq!(png[(0, 0)], [0.0, 0.0, 0.0]);
+        assert_eq!(png[(1, 0)], [1.0, 1.0, 1.0]);
+
+        // A flat HDR scanline, then one run length encoded.
+        let mut hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        for _ in 0..8 {
+            hdr.extend_from_slice(&[128, 64, 0, 130]);
+        }
+        hdr.extend_from_slice(&[2, 2, 0, 8]);
+        for &value in &[128, 64, 0, 129] {
+            hdr.extend_from_slice(&[128 + 8, value]);
+        }
+        let hdr = Raster::decode(&hdr).unwrap();
+        assert_eq!((hdr.x_len(), hdr.y_len()), (8, 2));
+        assert_eq!(hdr[(7, 0)], [2.0, 1.0, 0.0]);
+        assert_eq!(hdr[(3, 1)], [1.0, 0.5, 0.0]);
+
+        assert!(Raster::decode(b"P5 2 2 255\n\x00").is_err());
+        assert!(Raster::decode(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn test_oversized() {
+        // Headers claiming far more pixels than there is data for (or than fit in memory) are
+        // rejected before anything is allocated for them.
+        assert!(Raster::decode(b"P6 4294967296 4294967296 255\n\x00").is_err());
+        assert!(Raster::decode(b"P2 100000 100000 255\n0 0 0").is_err());
+        assert!(Raster::decode(b"P5 100000 100000 255\n\x00").is_err());
+        let hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 4294967296 +X 4294967296\n\x02\x02\x00\x08";
+        assert!(Raster::decode(hdr).is_err());
+        let hdr = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 1 +X 100000000\n\x02\x02\x00\x08";
+        assert!(Raster::decode(hdr).is_err());
+    }
+}
@@ -1,16 +1,43 @@
-use crate::vec3;
-use crate::colour;
 use crate::WorldVec;
 
 #[derive(Debug, Copy, Clone)]
 pub struct Ray {
     origin: WorldVec,    // A point.
     direction: WorldVec, // A unit vector pointing in the ray's direction.
+    // Only hits between these distances along the ray (in units of `direction`) count.
+    t_min: f64,
+    t_max: f64,
+    // When the ray was cast, for motion blur.
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: WorldVec, direction: WorldVec) -> Ray {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            t_min: 0.0,
+            t_max: f64::INFINITY,
+            time: 0.0,
+        }
+    }
+
+    /// A ray leaving a surface at `point`, with geometric normal `normal`. The origin is nudged
+    /// off the surface, to whichever side `direction` points to, so that the ray doesn't hit the
+    /// surface it started from.
+    pub fn leaving_surface(point: WorldVec, normal: WorldVec, direction: WorldVec) -> Ray {
+        let normal = if normal.dot(direction) < 0.0 { -normal } else { normal };
+        Ray::new(offset_origin(point, normal), direction)
+    }
+
+    /// The same ray, only counting hits between `t_min` and `t_max`.
+    pub fn with_t_range(self, t_min: f64, t_max: f64) -> Ray {
+        Ray { t_min, t_max, ..self }
+    }
+
+    /// The same ray, cast at `time`.
+    pub fn with_time(self, time: f64) -> Ray {
+        Ray { time, ..self }
     }
 
     pub fn get_origin(&self) -> &WorldVec {
@@ -19,4 +46,73 @@ impl Ray {
     pub fn get_direction(&self) -> &WorldVec {
         &self.direction
     }
+    pub fn get_t_min(&self) -> f64 {
+        self.t_min
+    }
+    pub fn get_t_max(&self) -> f64 {
+        self.t_max
+    }
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// Whether a hit at `t` is within the ray's range.
+    pub fn in_range(&self, t: f64) -> bool {
+        t >= self.t_min && t <= self.t_max
+    }
+
+    /// The point `t` units of `direction` along the ray.
+    pub fn at(&self, t: f64) -> WorldVec {
+        self.direction * t + self.origin
+    }
+}
+
+/// Move `point` a tiny distance along `normal`, by enough to get clear of any rounding error in
+/// where we think it is. Following "A Fast and Robust Method for Avoiding Self-Intersection"
+/// (Wächter and Binder, Ray Tracing Gems), the offset is a fixed number of ULPs, so it scales with
+/// the size of the coordinates, except very close to zero where it's a fixed distance.
+pub fn offset_origin(point: WorldVec, normal: WorldVec) -> WorldVec {
+    // These are larger than the paper's, which are for 32 bit floats and triangles: our quadric
+    // and quartic intersections are much less precise than the floats they're stored in.
+    const ORIGIN: f64 = 1.0 / 32.0;
+    const FLOAT_SCALE: f64 = 1.0 / (1u64 << 30) as f64;
+    const INT_SCALE: f64 = (1u64 << 24) as f64;
+    let offset = |p: f64, n: f64| {
+        if p.abs() < ORIGIN {
+            return p + FLOAT_SCALE * n;
+        }
+        let ulps = (INT_SCALE * n) as i64;
+        // Moving away from zero means increasing the magnitude, whatever the sign.
+        let ulps = if p < 0.0 { -ulps } else { ulps };
+        f64::from_bits((p.to_bits() as i64 + ulps) as u64)
+    };
+    WorldVec::new(
+        offset(point.0, normal.0),
+        offset(point.1, normal.1),
+        offset(point.2, normal.2),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_origin() {
+        let normal = WorldVec::new(0.0, 1.0, -1.0).normalised();
+        for &point in &[
+            WorldVec::new(0.0, 0.0, 0.0),
+            WorldVec::new(1000.0, -250.0, 3.5),
+            WorldVec::new(-0.01, 1e6, -1e-6),
+        ] {
+            let offset = offset_origin(point, normal);
+            let moved = offset - point;
+            // Moved a little, in the direction of the normal.
+            assert!(moved.dot(normal) > 0.0);
+            assert!(moved.length() < 1e-6 * (1.0 + point.length()));
+        }
+
+        let ray = Ray::leaving_surface(WorldVec::new(5.0, 0.0, 0.0), normal, -normal);
+        assert!((*ray.get_origin() - WorldVec::new(5.0, 0.0, 0.0)).dot(normal) < 0.0);
+    }
 }
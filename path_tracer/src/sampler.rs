@@ -0,0 +1,152 @@
+//! Random numbers for rendering. Each pixel carries its own `Sampler`, which the camera installs
+//! as the current sampler for this thread while it traces that pixel. Anything which needs random
+//! numbers while tracing (materials, for example) should draw them through `with_current`, so that
+//! a pixel's samples only depend on its own sampler state. This is what lets us render regions
+//! independently and resume renders from checkpoints while getting exactly the same result.
+
+use std::cell::Cell;
+
+/// A small, fast PRNG (SplitMix64) whose entire state is a single `u64`, so that it can be
+/// serialised easily.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct Sampler {
+    state: u64,
+}
+
+impl Sampler {
+    pub fn new(state: u64) -> Sampler {
+        Sampler { state }
+    }
+
+    /// Create the sampler for pixel `(i, j)` of an image rendered with the given seed. The pixel
+    /// coordinates are those in the full image, not any region of it.
+    pub fn for_pixel(seed: u64, (i, j): (usize, usize)) -> Sampler {
+        let mut sampler = Sampler::new(seed);
+        let state = sampler.next() ^ (i as u64);
+        let mut sampler = Sampler::new(state);
+        Sampler::new(sampler.next() ^ (j as u64).rotate_left(32))
+    }
+
+    pub fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl rand::RngCore for Sampler {
+    fn next_u32(&mut self) -> u32 {
+        (self.next() >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+thread_local! {
+    static CURRENT: Cell<Sampler> = Cell::new(Sampler::new(rand::random()));
+}
+
+/// Run `f` with this thread's current sampler.
+pub fn with_current<R>(f: impl FnOnce(&mut Sampler) -> R) -> R {
+    CURRENT.with(|current| {
+        let mut sampler = current.get();
+        let result = f(&mut sampler);
+        current.set(sampler);
+        result
+    })
+}
+
+/// Make `sampler` the current sampler for this thread, returning the previous one.
+pub fn replace_current(sampler: Sampler) -> Sampler {
+    CURRENT.with(|current| current.replace(sampler))
+}
+
+/// Make `sampler` the current sampler while `f` runs, leaving `sampler` wherever `f` left it. The
+/// previous sampler is put back afterwards, even if `f` panics.
+pub fn using<R>(sampler: &mut Sampler, f: impl FnOnce() -> R) -> R {
+    struct Restore<'a> {
+        sampler: &'a mut Sampler,
+        previous: Sampler,
+    }
+
+    impl Drop for Restore<'_> {
+        fn drop(&mut self) {
+            *self.sampler = replace_current(self.previous);
+        }
+    }
+
+    let _restore = Restore {
+        previous: replace_current(*sampler),
+        sampler,
+    };
+    f()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngCore;
+
+    #[test]
+    fn test_deterministic() {
+        let mut a = Sampler::for_pixel(42, (3, 4));
+        let mut b = Sampler::for_pixel(42, (3, 4));
+        let mut c = Sampler::for_pixel(42, (4, 3));
+        for _ in 0..16 {
+            let value = a.next_u64();
+            assert_eq!(value, b.next_u64());
+            assert_ne!(value, c.next_u64());
+        }
+    }
+
+    #[test]
+    fn test_current() {
+        let previous = replace_current(Sampler::new(7));
+        let first = with_current(|sampler| sampler.next_u64());
+        let second = with_current(|sampler| sampler.next_u64());
+        assert_ne!(first, second);
+
+        // Putting the old state back gives us the same numbers again.
+        let used = replace_current(Sampler::new(7));
+        assert_eq!(with_current(|sampler| sampler.next_u64()), first);
+        assert_ne!(used, Sampler::new(7));
+        replace_current(previous);
+    }
+
+    #[test]
+    fn test_using() {
+        replace_current(Sampler::new(7));
+        let mut sampler = Sampler::new(8);
+        let first = using(&mut sampler, || with_current(|sampler| sampler.next_u64()));
+        assert_eq!(first, Sampler::new(8).next_u64());
+        assert_ne!(sampler, Sampler::new(8));
+        assert_eq!(replace_current(Sampler::new(7)), Sampler::new(7));
+
+        // A panic part-way through still puts the previous sampler back.
+        let panicked = std::panic::catch_unwind(|| {
+            using(&mut Sampler::new(8), || -> () { panic!("Interrupted") })
+        });
+        assert!(panicked.is_err());
+        assert_eq!(replace_current(Sampler::new(7)), Sampler::new(7));
+    }
+}
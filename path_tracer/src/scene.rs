@@ -1,48 +1,218 @@
+use crate::aabb;
+use crate::bvh;
 use crate::hit;
+use crate::hit::Hit;
+use crate::instance;
 use crate::ray;
-use crate::material;
+use crate::transform;
+
+/// Identifies an instance added to a scene with `Scene::add_instance`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InstanceId(usize);
 
 #[derive(Debug)]
 pub struct Scene<'scene> {
     objects: Vec<std::rc::Rc<dyn hit::Hit + 'scene>>,
+    /// Instances are kept in a two-level hierarchy: the objects they refer to are expected to
+    /// carry their own acceleration structure (a `Mesh` or a `Bvh`), shared between instances, and
+    /// the scene keeps a top-level BVH over the instances' bounds. This way, moving an instance
+    /// only means updating the top level.
+    instances: Vec<instance::Instance>,
+    /// Instances without bounds can't go in the top level, so they're checked separately.
+    unbounded_instances: Vec<usize>,
+    /// Built the first time the scene is hit after instances are added.
+    top_level: std::cell::OnceCell<bvh::BvhTree>,
 }
 
 impl<'scene> Scene<'scene> {
     pub fn new() -> Scene<'scene> {
         Scene {
             objects: Vec::new(),
+            instances: Vec::new(),
+            unbounded_instances: Vec::new(),
+            top_level: std::cell::OnceCell::new(),
         }
     }
 
     pub fn add_object(&mut self, hit: std::rc::Rc<dyn hit::Hit>) {
         self.objects.push(hit);
     }
+
+    pub fn add_instance(&mut self, instance: instance::Instance) -> InstanceId {
+        let id = InstanceId(self.instances.len());
+        if bounds_of(&instance).is_none() {
+            self.unbounded_instances.push(id.0);
+        }
+        self.instances.push(instance);
+        // The top level will need rebuilding to include the new instance.
+        self.top_level = std::cell::OnceCell::new();
+        id
+    }
+
+    pub fn instance(&self, id: InstanceId) -> &instance::Instance {
+        &self.instances[id.0]
+    }
+
+    /// Move an instance. Only the top level of the hierarchy is updated (by refitting it), so this
+    /// is cheap no matter how complicated the instanced object is.
+    pub fn set_instance_transform(&mut self, id: InstanceId, transform: transform::Transform) {
+        self.instances[id.0].set_transform(transform);
+        // Moving an instance far enough can take its bounds out of range, or bring them back.
+        let bounded = bounds_of(&self.instances[id.0]).is_some();
+        let listed = self.unbounded_instances.iter().position(|&index| index == id.0);
+        match (bounded, listed) {
+            (true, Some(position)) => {
+                self.unbounded_instances.remove(position);
+            }
+            (false, None) => self.unbounded_instances.push(id.0),
+            _ => {}
+        }
+        let bounds = self.instance_bounds();
+        if let Some(top_level) = self.top_level.get_mut() {
+            top_level.refit(&bounds);
+        }
+    }
+
+    /// Rebuild the top level from scratch. Worth doing after moving instances a long way, as
+    /// refitting makes the hierarchy less efficient over time.
+    pub fn rebuild_top_level(&mut self) {
+        self.top_level = std::cell::OnceCell::new();
+    }
+
+    /// The bounds of each instance. Unbounded instances are given an empty box, so that they're
+    /// never found in the top level.
+    fn instance_bounds(&self) -> Vec<aabb::Aabb> {
+        self.instances
+            .iter()
+            .map(|instance| bounds_of(instance).unwrap_or(aabb::Aabb::from_points(&[])))
+            .collect()
+    }
+}
+
+/// The bounds of an instance, if it has any which the top level can use. Boxes reaching off to
+/// infinity can't be split up, so they count as unbounded.
+fn bounds_of(instance: &instance::Instance) -> Option<aabb::Aabb> {
+    instance.bounding_box().filter(|bounds| {
+        [bounds.min, bounds.max]
+            .iter()
+            .all(|corner| corner.0.is_finite() && corner.1.is_finite() && corner.2.is_finite())
+    })
+}
+
+impl Default for Scene<'_> {
+    fn default() -> Self {
+        Scene::new()
+    }
 }
 
-impl hit::Hit for Scene<'_> {
+impl Hit for Scene<'_> {
     fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
-        // Linearly search through the objects and see if they can be hit.
+        // Each hit we find shortens the ray, so that only closer hits count after it.
         let mut current_hit_candidate: Option<hit::MaterialHit> = None;
+        let shortened = |candidate: &Option<hit::MaterialHit>| {
+            let t_max = candidate.as_ref().map_or(from.get_t_max(), |hit| hit.t);
+            from.with_t_range(from.get_t_min(), t_max)
+        };
+
+        // Linearly search through the objects and see if they can be hit.
         for hittable in self.objects.iter() {
-            if let Some(new_hit) = hittable.hit(from) {
-                // We have a hit.
-                match &current_hit_candidate {
-                    Some(hit_candidate) => {
-                        // Get the distance between the current hit and the previous hit.
-                        let origin = from.get_origin();
-                        let hit_candidate_distance = (*hit_candidate.intersected_surface_normal.get_origin() - *origin).length();
-                        let new_distance = (*new_hit.intersected_surface_normal.get_origin() - *origin).length();
-                        if new_distance < hit_candidate_distance {
-                            current_hit_candidate = Some(new_hit);
-                        }
-                    }
-                    None => {
-                        current_hit_candidate = Some(new_hit);
-                    }
-
-                }
+            if let Some(new_hit) = hittable.hit(&shortened(&current_hit_candidate)) {
+                current_hit_candidate = Some(new_hit);
+            }
+        }
+        for &index in &self.unbounded_instances {
+            if let Some(new_hit) = self.instances[index].hit(&shortened(&current_hit_candidate)) {
+                current_hit_candidate = Some(new_hit);
             }
         }
+
+        let top_level = self
+            .top_level
+            .get_or_init(|| bvh::BvhTree::build(&self.instance_bounds()));
+        let instance_hit = top_level.closest_hit(&shortened(&current_hit_candidate), |index, t_max| {
+            let hit = self.instances[index].hit(&from.with_t_range(from.get_t_min(), t_max))?;
+            Some((hit.t, hit))
+        });
+        if let Some((_, new_hit)) = instance_hit {
+            current_hit_candidate = Some(new_hit);
+        }
         current_hit_candidate
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::lambertian;
+    use crate::sphere;
+    use crate::WorldVec;
+
+    #[test]
+    fn test_move_instance() {
+        let shared_sphere: std::rc::Rc<dyn hit::Hit> = std::rc::Rc::new(sphere::Sphere::new(
+            WorldVec::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5))),
+        ));
+        let mut scene = Scene::new();
+        let mut ids = Vec::new();
+        for i in 0..20 {
+            ids.push(scene.add_instance(instance::Instance::new(
+                std::rc::Rc::clone(&shared_sphere),
+                transform::Transform::translation(WorldVec::new(i as f64 * 3.0, 0.0, 10.0)),
+            )));
+        }
+
+        let ray = ray::Ray::new(WorldVec::new(30.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = scene.hit(&ray).expect("The ray should hit instance 10.");
+        assert_eq!(*hit.intersected_surface_normal.get_origin(), WorldVec::new(30.0, 0.0, 9.0));
+
+        // Move instance 10 closer, and another instance in front of the ray, behind it.
+        scene.set_instance_transform(
+            ids[10],
+            transform::Transform::translation(WorldVec::new(30.0, 0.0, 5.0)),
+        );
+        scene.set_instance_transform(
+            ids[3],
+            transform::Transform::translation(WorldVec::new(30.0, 0.0, 7.0)),
+        );
+        let hit = scene.hit(&ray).expect("The ray should hit instance 10.");
+        assert_eq!(*hit.intersected_surface_normal.get_origin(), WorldVec::new(30.0, 0.0, 4.0));
+
+        // Move instance 10 out of the way, so instance 3 is hit instead.
+        scene.set_instance_transform(
+            ids[10],
+            transform::Transform::translation(WorldVec::new(-30.0, 0.0, 5.0)),
+        );
+        let hit = scene.hit(&ray).expect("The ray should hit instance 3.");
+        assert_eq!(*hit.intersected_surface_normal.get_origin(), WorldVec::new(30.0, 0.0, 6.0));
+    }
+
+    #[test]
+    fn test_move_unbounded_instance() {
+        let grey: std::rc::Rc<dyn crate::material::Material> =
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)));
+        let floor: std::rc::Rc<dyn hit::Hit> = std::rc::Rc::new(crate::plane::Plane::new(
+            WorldVec::new(0.0, 0.0, 0.0),
+            WorldVec::new(0.0, 1.0, 0.0),
+            grey.clone(),
+        ));
+        let ball: std::rc::Rc<dyn hit::Hit> = std::rc::Rc::new(sphere::Sphere::new(WorldVec::new(0.0, 0.0, 0.0), 1.0, grey));
+        let mut scene = Scene::new();
+        let floor = scene.add_instance(instance::Instance::new(floor, transform::Transform::identity()));
+        let ball = scene.add_instance(instance::Instance::new(ball, transform::Transform::translation(WorldVec::new(0.0, 5.0, 0.0))));
+        let down = ray::Ray::new(WorldVec::new(0.0, 10.0, 0.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert_eq!(scene.hit(&down).unwrap().intersected_surface_normal.get_origin().1, 6.0);
+
+        // The floor has no bounds wherever it goes. The ball loses them off at infinity, and gets
+        // them back when it returns.
+        scene.set_instance_transform(floor, transform::Transform::translation(WorldVec::new(0.0, 2.0, 0.0)));
+        scene.set_instance_transform(ball, transform::Transform::translation(WorldVec::new(f64::INFINITY, 0.0, 0.0)));
+        assert_eq!(scene.unbounded_instances, vec![floor.0, ball.0]);
+        assert_eq!(scene.hit(&down).unwrap().intersected_surface_normal.get_origin().1, 2.0);
+        scene.set_instance_transform(ball, transform::Transform::translation(WorldVec::new(0.0, 5.0, 0.0)));
+        assert_eq!(scene.unbounded_instances, vec![floor.0]);
+        assert_eq!(scene.hit(&down).unwrap().intersected_surface_normal.get_origin().1, 6.0);
+    }
+}
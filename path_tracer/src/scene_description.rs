@@ -0,0 +1,186 @@
+//! A small text format for describing a camera and the scene it looks at, so that scenes can be
+//! stored in files or sent to other processes. Each line is one of:
+//!
+//! ```text
+//! camera origin <x> <y> <z> up <x> <y> <z> looking_at <x> <y> <z>
+//! material <name> lambertian <r> <g> <b>
+//! material <name> metal <r> <g> <b> <fuzziness>
+//! material <name> debugon
+//! sphere <x> <y> <z> <radius> <material name>
+//! ```
+//!
+//! Blank lines and anything following a `#` are ignored. Materials must be declared before they
+//! are used, and there must be exactly one camera.
+
+use crate::camera;
+use crate::colour;
+use crate::debugon;
+use crate::lambertian;
+use crate::material;
+use crate::metal;
+use crate::scene;
+use crate::sphere;
+use crate::WorldVec;
+
+pub type DescribedCamera = camera::Camera<scene::Scene<'static>>;
+
+#[derive(Debug)]
+pub struct ParseError {
+    /// The (1-based) line the error was found on, or 0 if it isn't specific to a line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Parse a scene description, returning a camera looking at the described scene.
+pub fn parse(description: &str) -> Result<DescribedCamera, ParseError> {
+    let mut scene = scene::Scene::new();
+    let mut materials: std::collections::HashMap<&str, std::rc::Rc<dyn material::Material>> =
+        std::collections::HashMap::new();
+    let mut camera = None;
+
+    for (index, line) in description.lines().enumerate() {
+        let line_number = index + 1;
+        let error = |message: String| ParseError {
+            line: line_number,
+            message,
+        };
+        let line = line.split('#').next().unwrap();
+        let mut tokens = Tokens {
+            tokens: line.split_whitespace(),
+        };
+        let keyword = match tokens.tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        match keyword {
+            "camera" => {
+                if camera.is_some() {
+                    return Err(error("there is more than one camera".to_string()));
+                }
+                tokens.keyword("origin").map_err(error)?;
+                let origin = tokens.vector().map_err(error)?;
+                tokens.keyword("up").map_err(error)?;
+                let up = tokens.vector().map_err(error)?;
+                tokens.keyword("looking_at").map_err(error)?;
+                let looking_at = tokens.vector().map_err(error)?;
+                camera = Some((origin, up, looking_at));
+            }
+            "material" => {
+                let name = tokens.word().map_err(error)?;
+                let material: std::rc::Rc<dyn material::Material> =
+                    match tokens.word().map_err(error)? {
+                        "lambertian" => std::rc::Rc::new(lambertian::Lambertian::new(
+                            tokens.colour().map_err(error)?,
+                        )),
+                        "metal" => std::rc::Rc::new(metal::Metal::new(
+                            tokens.colour().map_err(error)?,
+                            tokens.number().map_err(error)? as f32,
+                        )),
+                        "debugon" => std::rc::Rc::new(debugon::Debugon::new()),
+                        other => return Err(error(format!("unknown material type `{}`", other))),
+                    };
+                materials.insert(name, material);
+            }
+            "sphere" => {
+                let origin = tokens.vector().map_err(error)?;
+                let radius = tokens.number().map_err(error)?;
+                let name = tokens.word().map_err(error)?;
+                let material = materials
+                    .get(name)
+                    .ok_or_else(|| error(format!("unknown material `{}`", name)))?;
+                scene.add_object(std::rc::Rc::new(sphere::Sphere::new(
+                    origin,
+                    radius,
+                    material.clone(),
+                )));
+            }
+            other => return Err(error(format!("unknown keyword `{}`", other))),
+        }
+        if let Some(extra) = tokens.tokens.next() {
+            return Err(error(format!("unexpected `{}`", extra)));
+        }
+    }
+
+    let (origin, up, looking_at) = camera.ok_or(ParseError {
+        line: 0,
+        message: "there is no camera".to_string(),
+    })?;
+    Ok(camera::Camera::new_looking_at(
+        std::rc::Rc::new(scene),
+        origin,
+        up,
+        looking_at,
+    ))
+}
+
+struct Tokens<'a> {
+    tokens: std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> Tokens<'a> {
+    fn word(&mut self) -> Result<&'a str, String> {
+        self.tokens
+            .next()
+            .ok_or_else(|| "unexpected end of line".to_string())
+    }
+
+    fn keyword(&mut self, expected: &str) -> Result<(), String> {
+        match self.word()? {
+            word if word == expected => Ok(()),
+            word => Err(format!("expected `{}`, found `{}`", expected, word)),
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, String> {
+        let word = self.word()?;
+        word.parse()
+            .map_err(|_| format!("expected a number, found `{}`", word))
+    }
+
+    fn vector(&mut self) -> Result<WorldVec, String> {
+        Ok(WorldVec::new(self.number()?, self.number()?, self.number()?))
+    }
+
+    fn colour(&mut self) -> Result<colour::Colour, String> {
+        Ok(colour::Colour::new(
+            self.number()?,
+            self.number()?,
+            self.number()?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let camera = parse(
+            r#"
+            # Two spheres.
+            camera origin 0 0 -3 up 0 1 0 looking_at 0 0 0
+            material grey lambertian 0.5 0.5 0.5
+            material shiny metal 0.8 0.8 0.8 0.1 # A comment.
+            sphere 0 0 0 1 grey
+            sphere 0 -101 0 100 shiny
+            "#,
+        )
+        .unwrap();
+        let image = camera.render(4, 2, 60.0, 2, 1);
+        assert_eq!(image.x_len(), 4);
+
+        let error = parse("camera origin 0 0 0 up 0 1 0 looking_at 0 0 1\nsphere 0 0 0 1 missing")
+            .err()
+            .unwrap();
+        assert_eq!(error.line, 2);
+        assert!(parse("material grey lambertian 0.5 0.5 0.5").is_err());
+        assert!(parse("camera origin 0 0 0 up 0 1 0 looking_at 0 0 1 extra").is_err());
+    }
+}
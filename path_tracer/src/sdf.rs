@@ -0,0 +1,403 @@
+use crate::aabb;
+use crate::hit;
+use crate::material;
+use crate::ray;
+use crate::WorldVec;
+
+/// A signed distance function: how far a point is from the nearest surface of a shape, negative
+/// inside it. Functions should never overestimate the distance, or `Sdf` may step through the
+/// surface.
+pub trait DistanceFunction: std::fmt::Debug {
+    fn distance(&self, point: WorldVec) -> f64;
+
+    /// A box containing the whole shape, or `None` if it's unbounded.
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        None
+    }
+}
+
+/// The most steps `Sdf` will take along a ray before giving up.
+const MAX_STEPS: usize = 512;
+
+/// How close to the surface counts as hitting it.
+const SURFACE_EPSILON: f64 = 1e-6;
+
+/// How far a ray has to go before it can hit anything, so that rays leaving the surface don't
+/// immediately hit it again.
+const MIN_DISTANCE: f64 = 1e-4;
+
+/// How far along a ray to look for a surface, when the shape is unbounded.
+const MAX_DISTANCE: f64 = 1e4;
+
+/// Geometry defined by a distance function, found by sphere tracing: stepping along the ray by
+/// the distance to the nearest surface until we're close enough to call it a hit.
+#[derive(Debug)]
+pub struct Sdf {
+    function: std::rc::Rc<dyn DistanceFunction>,
+    material: std::rc::Rc<dyn material::Material>,
+    step_scale: f64,
+}
+
+impl Sdf {
+    pub fn new(
+        function: std::rc::Rc<dyn DistanceFunction>,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Sdf {
+        Sdf {
+            function,
+            material,
+            step_scale: 1.0,
+        }
+    }
+
+    /// Scale each step by this much (1 by default). Some operators, like `Twist`, distort the
+    /// distance so that it overestimates; taking shorter steps makes up for it.
+    pub fn set_step_scale(&mut self, step_scale: f64) {
+        self.step_scale = step_scale;
+    }
+
+    /// The normal at a point, from the gradient of the distance function (by central differences).
+    fn normal(&self, point: WorldVec) -> WorldVec {
+        let h = SURFACE_EPSILON;
+        let d = |offset: WorldVec| {
+            self.function.distance(point + offset) - self.function.distance(point - offset)
+        };
+        WorldVec::new(
+            d(WorldVec::new(h, 0.0, 0.0)),
+            d(WorldVec::new(0.0, h, 0.0)),
+            d(WorldVec::new(0.0, 0.0, h)),
+        )
+        .normalised()
+    }
+}
+
+impl hit::Hit for Sdf {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let length = from.get_direction().length();
+        let direction = *from.get_direction() * (1.0 / length);
+        let unit_ray = ray::Ray::new(*from.get_origin(), direction);
+
+        // Only march through the part of the ray inside the bounds, if there are any.
+        let (mut t, t_max) = match self.function.bounding_box() {
+            Some(bounds) => {
+                let (t_enter, t_exit) = bounds.hit_interval(&unit_ray, f64::INFINITY)?;
+                (t_enter.max(MIN_DISTANCE), t_exit + SURFACE_EPSILON)
+            }
+            None => (MIN_DISTANCE, MAX_DISTANCE),
+        };
+        // The ray's own range is in units of its (unnormalised) direction.
+        t = t.max(from.get_t_min() * length);
+        let t_max = t_max.min(from.get_t_max() * length);
+
+        for _ in 0..MAX_STEPS {
+            if t > t_max {
+                return None;
+            }
+            let point = direction * t + *from.get_origin();
+            // Rays can start inside the shape, so look for the surface from either side.
+            let distance = self.function.distance(point).abs();
+            if distance < SURFACE_EPSILON {
+                return Some(hit::MaterialHit::new(
+                    std::rc::Rc::clone(&self.material),
+                    from,
+                    t / length,
+                    self.normal(point),
+                    // There's no natural way to unwrap an arbitrary distance field.
+                    (0.0, 0.0),
+                ));
+            }
+            t += distance * self.step_scale;
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        self.function.bounding_box()
+    }
+}
+
+#[derive(Debug)]
+pub struct Sphere {
+    pub centre: WorldVec,
+    pub radius: f64,
+}
+
+impl DistanceFunction for Sphere {
+    fn distance(&self, point: WorldVec) -> f64 {
+        (point - self.centre).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let radius = WorldVec::new(self.radius, self.radius, self.radius);
+        Some(aabb::Aabb::new(self.centre - radius, self.centre + radius))
+    }
+}
+
+/// An axis-aligned box, centred on `centre`.
+#[derive(Debug)]
+pub struct Cuboid {
+    pub centre: WorldVec,
+    pub half_size: WorldVec,
+}
+
+impl DistanceFunction for Cuboid {
+    fn distance(&self, point: WorldVec) -> f64 {
+        let p = point - self.centre;
+        let q = WorldVec::new(
+            p.0.abs() - self.half_size.0,
+            p.1.abs() - self.half_size.1,
+            p.2.abs() - self.half_size.2,
+        );
+        let outside = WorldVec::new(q.0.max(0.0), q.1.max(0.0), q.2.max(0.0)).length();
+        let inside = q.0.max(q.1).max(q.2).min(0.0);
+        outside + inside
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::new(self.centre - self.half_size, self.centre + self.half_size))
+    }
+}
+
+/// A torus lying flat around the y axis.
+#[derive(Debug)]
+pub struct Torus {
+    pub centre: WorldVec,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl DistanceFunction for Torus {
+    fn distance(&self, point: WorldVec) -> f64 {
+        let p = point - self.centre;
+        let from_ring = (p.0 * p.0 + p.2 * p.2).sqrt() - self.major_radius;
+        (from_ring * from_ring + p.1 * p.1).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let outer = self.major_radius + self.minor_radius;
+        let extent = WorldVec::new(outer, self.minor_radius, outer);
+        Some(aabb::Aabb::new(self.centre - extent, self.centre + extent))
+    }
+}
+
+/// The Mandelbulb fractal, centred on the origin. It fits within a sphere of radius 1.2 or so.
+#[derive(Debug)]
+pub struct Mandelbulb {
+    /// 8 gives the classic shape.
+    pub power: f64,
+    /// More iterations give finer detail, at the cost of speed.
+    pub iterations: usize,
+}
+
+impl DistanceFunction for Mandelbulb {
+    fn distance(&self, point: WorldVec) -> f64 {
+        // The standard distance estimate, from the running derivative of the iteration.
+        let mut z = point;
+        let mut dr = 1.0;
+        let mut r = z.length();
+        for _ in 0..self.iterations {
+            if r > 2.0 || r == 0.0 {
+                break;
+            }
+            let theta = (z.2 / r).acos() * self.power;
+            let phi = z.1.atan2(z.0) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z = WorldVec::new(theta.sin() * phi.cos(), phi.sin() * theta.sin(), theta.cos()) * zr + point;
+            r = z.length();
+        }
+        if r == 0.0 {
+            return 0.0;
+        }
+        0.5 * r.ln() * r / dr
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        Some(aabb::Aabb::new(
+            WorldVec::new(-1.5, -1.5, -1.5),
+            WorldVec::new(1.5, 1.5, 1.5),
+        ))
+    }
+}
+
+/// Two shapes blended together, with a fillet of roughly `smoothness` where they meet.
+#[derive(Debug)]
+pub struct SmoothUnion {
+    pub a: std::rc::Rc<dyn DistanceFunction>,
+    pub b: std::rc::Rc<dyn DistanceFunction>,
+    pub smoothness: f64,
+}
+
+impl DistanceFunction for SmoothUnion {
+    fn distance(&self, point: WorldVec) -> f64 {
+        let (a, b) = (self.a.distance(point), self.b.distance(point));
+        let k = self.smoothness;
+        if k <= 0.0 {
+            return a.min(b);
+        }
+        // Polynomial smooth minimum.
+        let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+        b * (1.0 - h) + a * h - k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // The blend can bulge out by up to a quarter of the smoothness.
+        let grow = WorldVec::new(self.smoothness, self.smoothness, self.smoothness) * 0.25;
+        let bounds = self.a.bounding_box()?.union(&self.b.bounding_box()?);
+        Some(aabb::Aabb::new(bounds.min - grow, bounds.max + grow))
+    }
+}
+
+/// A shape twisted around the y axis by `rate` radians per unit of height. Twisting stretches
+/// distances, so use `Sdf::set_step_scale` to take smaller steps through it.
+#[derive(Debug)]
+pub struct Twist {
+    pub inner: std::rc::Rc<dyn DistanceFunction>,
+    pub rate: f64,
+}
+
+impl DistanceFunction for Twist {
+    fn distance(&self, point: WorldVec) -> f64 {
+        let (sin, cos) = (-self.rate * point.1).sin_cos();
+        self.inner.distance(WorldVec::new(
+            cos * point.0 - sin * point.2,
+            point.1,
+            sin * point.0 + cos * point.2,
+        ))
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        // Twisting spins the shape around the y axis, so it fits in the cylinder it sweeps out.
+        let bounds = self.inner.bounding_box()?;
+        let x = bounds.min.0.abs().max(bounds.max.0.abs());
+        let z = bounds.min.2.abs().max(bounds.max.2.abs());
+        let reach = x.hypot(z);
+        Some(aabb::Aabb::new(
+            WorldVec::new(-reach, bounds.min.1, -reach),
+            WorldVec::new(reach, bounds.max.1, reach),
+        ))
+    }
+}
+
+/// Infinitely many copies of a shape, repeated every `period` along each axis. The shape should
+/// fit within one period, centred on the origin.
+#[derive(Debug)]
+pub struct Repeat {
+    pub inner: std::rc::Rc<dyn DistanceFunction>,
+    pub period: WorldVec,
+}
+
+impl DistanceFunction for Repeat {
+    fn distance(&self, point: WorldVec) -> f64 {
+        let wrap = |x: f64, period: f64| x - period * (x / period).round();
+        self.inner.distance(WorldVec::new(
+            wrap(point.0, self.period.0),
+            wrap(point.1, self.period.1),
+            wrap(point.2, self.period.2),
+        ))
+    }
+}
+
+/// A shape with its edges rounded off, growing it by `radius`.
+#[derive(Debug)]
+pub struct Round {
+    pub inner: std::rc::Rc<dyn DistanceFunction>,
+    pub radius: f64,
+}
+
+impl DistanceFunction for Round {
+    fn distance(&self, point: WorldVec) -> f64 {
+        self.inner.distance(point) - self.radius
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let grow = WorldVec::new(self.radius, self.radius, self.radius);
+        let bounds = self.inner.bounding_box()?;
+        Some(aabb::Aabb::new(bounds.min - grow, bounds.max + grow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::grey;
+    use crate::hit::Hit;
+
+    #[test]
+    fn test_hit_sdf() {
+        let sphere = Sdf::new(
+            std::rc::Rc::new(Sphere {
+                centre: WorldVec::new(5.0, 0.0, 0.0),
+                radius: 1.0,
+            }),
+            grey(),
+        );
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = sphere.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(4.0, 0.0, 0.0)).length() < 1e-5);
+        assert!((*normal.get_direction() - WorldVec::new(-1.0, 0.0, 0.0)).length() < 1e-5);
+
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 1.0, 0.0));
+        assert!(sphere.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_operators() {
+        let a: std::rc::Rc<dyn DistanceFunction> = std::rc::Rc::new(Sphere {
+            centre: WorldVec::new(-1.0, 0.0, 0.0),
+            radius: 1.0,
+        });
+        let b: std::rc::Rc<dyn DistanceFunction> = std::rc::Rc::new(Sphere {
+            centre: WorldVec::new(1.0, 0.0, 0.0),
+            radius: 1.0,
+        });
+        // Where the spheres touch, the blend fills in the gap.
+        let blended = SmoothUnion { a, b, smoothness: 0.5 };
+        let gap = WorldVec::new(0.0, 0.1, 0.0);
+        assert!(blended.distance(gap) < 0.0);
+
+        // A repeated sphere can be hit far from the original.
+        let repeated = Sdf::new(
+            std::rc::Rc::new(Repeat {
+                inner: std::rc::Rc::new(Sphere {
+                    centre: WorldVec::new(0.0, 0.0, 0.0),
+                    radius: 0.5,
+                }),
+                period: WorldVec::new(4.0, 4.0, 4.0),
+            }),
+            grey(),
+        );
+        let ray = ray::Ray::new(WorldVec::new(40.0, 40.0, 30.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = repeated.hit(&ray).expect("The ray should hit a copy.");
+        assert!((hit.intersected_surface_normal.get_origin().2 - 31.5).abs() < 1e-5);
+
+        let rounded = Round {
+            inner: std::rc::Rc::new(Cuboid {
+                centre: WorldVec::new(0.0, 0.0, 0.0),
+                half_size: WorldVec::new(1.0, 1.0, 1.0),
+            }),
+            radius: 0.25,
+        };
+        assert!((rounded.distance(WorldVec::new(2.0, 0.0, 0.0)) - 0.75).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_hit_mandelbulb() {
+        let mut bulb = Sdf::new(
+            std::rc::Rc::new(Mandelbulb {
+                power: 8.0,
+                iterations: 8,
+            }),
+            grey(),
+        );
+        bulb.set_step_scale(0.9);
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = bulb.hit(&ray).expect("The ray should hit the bulb.");
+        let point = *hit.intersected_surface_normal.get_origin();
+        assert!(point.2 > -1.5 && point.2 < 0.0);
+
+        let ray = ray::Ray::new(WorldVec::new(0.0, 3.0, -5.0), WorldVec::new(0.0, 0.0, 1.0));
+        assert!(bulb.hit(&ray).is_none());
+    }
+}
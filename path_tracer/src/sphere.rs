@@ -1,7 +1,7 @@
+use crate::aabb;
 use crate::hit;
 use crate::ray;
 use crate::material;
-use crate::lambertian;
 use crate::WorldVec;
 
 #[derive(Debug)]
@@ -17,39 +17,88 @@ impl Sphere {
     }
 }
 
-impl hit::Hit for Sphere {
-    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+/// Latitude and longitude, with v running from the bottom of the sphere to the top.
+fn uv(normal: WorldVec) -> (f64, f64) {
+    (
+        0.5 + (-normal.2).atan2(normal.0) / (2.0 * std::f64::consts::PI),
+        (-normal.1).clamp(-1.0, 1.0).acos() / std::f64::consts::PI,
+    )
+}
+
+impl Sphere {
+    /// Solve for where `from` meets the sphere, returning the near and far `t`s.
+    fn roots(&self, from: &ray::Ray) -> Option<(f64, f64)> {
         // Given a ray O + At, we can find the intersection of a sphere with center point C and
         // radius R by solving (P - C).^2 = R^2 for t, substituting P = O + At and solving for t.
         let oc = *from.get_origin() - self.origin;
         let a = from.get_direction().dot(*from.get_direction());
         let b = oc.dot(*from.get_direction()) * 2.0;
-        let c = oc.dot(oc) - self.radius.powi(2) as f64;
+        let c = oc.dot(oc) - self.radius.powi(2);
 
         // Check the descriminant.
         let descriminant = b.powf(2.0) - 4.0 * a * c;
         if descriminant < 0.0 {
             // No real solutions; ie, no intersection.
-            None
+            return None;
+        }
+        Some((
+            (-b - descriminant.sqrt()) / (2.0 * a),
+            (-b + descriminant.sqrt()) / (2.0 * a),
+        ))
+    }
+
+    fn material_hit(&self, from: &ray::Ray, t: f64) -> hit::MaterialHit {
+        let normal_direction = (from.at(t) - self.origin).normalised();
+        // u increases heading around the y axis (clockwise, looking down it).
+        let tangent = WorldVec::new(normal_direction.2, 0.0, -normal_direction.0);
+        hit::MaterialHit::new(
+            std::rc::Rc::clone(&self.material),
+            from,
+            t,
+            normal_direction,
+            uv(normal_direction),
+        )
+        .with_tangent(tangent)
+    }
+}
+
+impl hit::Hit for Sphere {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let (near, far) = self.roots(from)?;
+        // We take the nearest intersection within the ray's range. If the ray starts inside the
+        // sphere, that's the far side.
+        let t = if from.in_range(near) {
+            near
+        } else if from.in_range(far) {
+            far
         } else {
-            // There is at least one intersection.
-            let t = (-b - descriminant.sqrt()) / (2.0 * a);
-            if t < 0.0 {
-                // We take the one that gives us positive t (indicating that the intersection is
-                // ahead of the ray). Otherwise, we discard this ray.
-                return None;
-            }
-            let intersection_point = *from.get_direction() * t + *from.get_origin();
-
-            let normal_direction = (intersection_point - self.origin).normalised();
-            let intersected_surface_normal = ray::Ray::new(intersection_point, normal_direction);
-            Some(
-                hit::MaterialHit {
-                    material: std::rc::Rc::clone(&self.material),
-                    intersected_surface_normal,
-            }
-            )
+            return None;
+        };
+        Some(self.material_hit(from, t))
+    }
+
+    fn intervals(&self, from: &ray::Ray) -> Vec<hit::Interval> {
+        let (t_enter, t_exit) = match self.roots(from) {
+            Some(roots) => roots,
+            None => return Vec::new(),
+        };
+        if t_exit < 0.0 || t_enter == t_exit {
+            // The sphere is behind us, or we only graze it.
+            return Vec::new();
         }
+        let crossing = |t: f64| hit::Crossing {
+            t,
+            hit: self.material_hit(from, t),
+        };
+        vec![hit::Interval {
+            enter: if t_enter >= 0.0 { Some(crossing(t_enter)) } else { None },
+            exit: Some(crossing(t_exit)),
+        }]
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let radius = WorldVec::new(self.radius, self.radius, self.radius);
+        Some(aabb::Aabb::new(self.origin - radius, self.origin + radius))
     }
 }
 
@@ -57,6 +106,7 @@ impl hit::Hit for Sphere {
 mod tests {
     use super::*;
     use crate::colour;
+    use crate::lambertian;
     use crate::vec3;
     use crate::ray;
 
@@ -81,8 +131,31 @@ mod tests {
                 vec3::Vec3::new(0.0, 0.0, 0.0),
                 vec3::Vec3::new(0.0, 1.0, 0.0),
         );
-        if let Some(_) = unit_sphere.hit(&ray) {
+        if unit_sphere.hit(&ray).is_some() {
             panic!("Test failed.");
         }
     }
+
+    #[test]
+    fn test_t_range() {
+        let unit_sphere = Sphere::new(vec3::Vec3::new(5.0, 0.0, 0.0), 1.0, std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0))));
+        let ray = ray::Ray::new(vec3::Vec3::new(0.0, 0.0, 0.0), vec3::Vec3::new(1.0, 0.0, 0.0));
+        let hit = unit_sphere.hit(&ray).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert!(hit.front_face);
+
+        // Cut the ray short of the sphere, then start it past the near side.
+        assert!(unit_sphere.hit(&ray.with_t_range(0.0, 3.5)).is_none());
+        let hit = unit_sphere.hit(&ray.with_t_range(4.5, f64::INFINITY)).unwrap();
+        assert_eq!(hit.t, 6.0);
+        assert!(!hit.front_face);
+
+        // Rays leaving the surface shouldn't hit it again where they start.
+        let normal = hit.intersected_surface_normal;
+        let outwards = ray::Ray::leaving_surface(*normal.get_origin(), *normal.get_direction(), vec3::Vec3::new(1.0, 1.0, 0.0).normalised());
+        assert!(unit_sphere.hit(&outwards).is_none());
+        let inwards = ray::Ray::leaving_surface(*normal.get_origin(), *normal.get_direction(), vec3::Vec3::new(-1.0, 0.0, 0.0));
+        let hit = unit_sphere.hit(&inwards).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-6);
+    }
 }
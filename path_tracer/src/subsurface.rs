@@ -0,0 +1,354 @@
+//! Subsurface scattering, for translucent materials like skin, marble and wax. Light goes into the
+//! object, wanders around inside it (a random walk, scattering off the medium and being partly
+//! absorbed as it goes) and comes out again somewhere else. This softens shadows and details, and
+//! lets light bleed through thin parts.
+//!
+//! The walk is done by the camera, which asks each material for its `interior`; objects with one
+//! must be closed, with nothing else inside them.
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::WorldVec;
+
+/// What the inside of an object is made of.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Medium {
+    /// For red, green and blue, how far light travels on average before meeting a particle.
+    pub mean_free_path: [f64; 3],
+    /// For red, green and blue, the chance that light which meets a particle is scattered rather
+    /// than absorbed.
+    pub albedo: [f64; 3],
+    /// The Henyey-Greenstein asymmetry of scattering, from -1 (back the way it came) through 0 (in
+    /// any direction) to 1 (straight on).
+    pub anisotropy: f64,
+    /// The refractive index of the object's surface.
+    pub ior: f64,
+}
+
+impl Medium {
+    /// How much of the light is still travelling after `distance`, per channel.
+    fn transmittance(&self, distance: f64) -> [f64; 3] {
+        let mut transmittance = [0.0; 3];
+        for (value, path) in transmittance.iter_mut().zip(&self.mean_free_path) {
+            *value = (-distance / path).exp();
+        }
+        transmittance
+    }
+
+    /// A new direction of travel, after scattering while going along `direction`.
+    fn scatter(&self, direction: WorldVec) -> WorldVec {
+        let g = self.anisotropy.clamp(-0.99, 0.99);
+        let u = microfacet::random();
+        let cos_theta = if g.abs() < 1e-3 {
+            1.0 - 2.0 * u
+        } else {
+            let s = (1.0 - g * g) / (1.0 - g + 2.0 * g * u);
+            (1.0 + g * g - s * s) / (2.0 * g)
+        }
+        .clamp(-1.0, 1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * std::f64::consts::PI * microfacet::random();
+        let (tangent, bitangent) = direction.tangent_basis();
+        (tangent * (sin_theta * phi.cos()) + bitangent * (sin_theta * phi.sin()) + direction * cos_theta).normalised()
+    }
+}
+
+/// Where light came out of an object after a random walk.
+#[derive(Debug, Clone)]
+pub struct Exit {
+    /// The ray leaving the object.
+    pub ray: ray::Ray,
+    /// How much of each of red, green and blue made it through, relative to what went in. This
+    /// is an estimate from a single walk, so it can be more than 1.
+    pub throughput: [f64; 3],
+}
+
+/// Follow light which has just gone into an object (along `ray`, starting inside it) until it
+/// comes out again, or `None` if it never does. `scene` is searched for the object's surface.
+///
+/// Light in a thick, barely absorbing medium can scatter many thousands of times before it gets
+/// out, so rather than giving up after some number of steps, walks whose light has mostly been
+/// absorbed are ended at random (Russian roulette), and the survivors made brighter to make up.
+pub fn random_walk<H: hit::Hit + ?Sized>(scene: &H, medium: &Medium, ray: ray::Ray) -> Option<Exit> {
+    let time = ray.get_time();
+    let mut ray = ray::Ray::new(*ray.get_origin(), ray.get_direction().normalised()).with_time(time);
+    // Each channel has its own mean free path, so pick one to sample distances with for the whole
+    // walk, and weight by the average chance of any of them having made the same walk. For each
+    // channel, keep the chance of its making the walk relative to the chosen one's, and how much
+    // of its light has been scattered rather than absorbed.
+    let channel = ((microfacet::random() * 3.0) as usize).min(2);
+    let mut relative = [1.0f64; 3];
+    let mut albedo = [1.0f64; 3];
+    let mut survived = 1.0;
+    let throughput = |relative: &[f64; 3], albedo: &[f64; 3], survived: f64| {
+        let mean = relative.iter().sum::<f64>() / 3.0;
+        let mut throughput = [0.0; 3];
+        for (c, weight) in throughput.iter_mut().enumerate() {
+            *weight = relative[c] / mean * albedo[c] / survived;
+        }
+        throughput
+    };
+    loop {
+        let survival = throughput(&relative, &albedo, survived).iter().cloned().fold(0.0, f64::max);
+        if survival < 1.0 {
+            if microfacet::random() >= survival {
+                return None;
+            }
+            survived *= survival;
+        }
+
+        let boundary = scene.hit(&ray)?;
+        let distance = -(1.0 - microfacet::random()).ln() * medium.mean_free_path[channel];
+        let scattered = distance < boundary.t;
+        let distance = distance.min(boundary.t);
+        // The chance of each channel's sampling ending here (scattering, or reaching the surface).
+        let mut probabilities = medium.transmittance(distance);
+        if scattered {
+            for (p, path) in probabilities.iter_mut().zip(&medium.mean_free_path) {
+                *p /= path;
+            }
+        }
+        let chosen = probabilities[channel];
+        if chosen.is_nan() || chosen <= 0.0 {
+            return None;
+        }
+        for c in 0..3 {
+            relative[c] *= probabilities[c] / chosen;
+            if scattered {
+                albedo[c] *= medium.albedo[c];
+            }
+        }
+
+        let direction = *ray.get_direction();
+        if scattered {
+            ray = ray::Ray::new(ray.at(distance), medium.scatter(direction)).with_time(time);
+            continue;
+        }
+
+        // At the surface, light is either reflected back in or refracted out, by the Fresnel
+        // equations.
+        let point = *boundary.intersected_surface_normal.get_origin();
+        let outward = boundary.intersected_surface_normal.get_direction().normalised();
+        let outward = if outward.dot(direction) < 0.0 { -outward } else { outward };
+        let eta = 1.0 / medium.ior;
+        let reflectance = microfacet::fresnel_dielectric(direction.dot(outward), eta);
+        let refracted = if microfacet::random() < reflectance {
+            None
+        } else {
+            microfacet::refract(-direction, -outward, eta)
+        };
+        match refracted {
+            Some(out) => {
+                return Some(Exit {
+                    ray: ray::Ray::leaving_surface(point, outward, out).with_time(time),
+                    throughput: throughput(&relative, &albedo, survived),
+                })
+            }
+            None => {
+                let reflected = direction - outward * (2.0 * direction.dot(outward));
+                ray = ray::Ray::leaving_surface(point, outward, reflected).with_time(time);
+            }
+        }
+    }
+}
+
+/// A translucent material, whose inside scatters light. The surface itself is smooth, reflecting
+/// some light and letting the rest in, where the camera takes over with a random walk.
+#[derive(Debug)]
+pub struct Subsurface {
+    medium: Medium,
+}
+
+impl Subsurface {
+    /// `albedo` is the chance of light being scattered rather than absorbed each time it meets a
+    /// particle, and `mean_free_path` is how far it travels between particles, for each of red,
+    /// green and blue. Scattering is in any direction, and the surface has the refractive index
+    /// of skin, wax and most stone.
+    pub fn new(albedo: colour::Colour, mean_free_path: [f64; 3]) -> Subsurface {
+        Subsurface::with_medium(Medium {
+            mean_free_path,
+            albedo: [albedo.get_red(), albedo.get_green(), albedo.get_blue()],
+            anisotropy: 0.0,
+            ior: 1.4,
+        })
+    }
+
+    pub fn with_medium(medium: Medium) -> Subsurface {
+        Subsurface { medium }
+    }
+
+    /// Marble, from Jensen et al.'s measurements, for scenes in millimetres.
+    pub fn marble() -> Subsurface {
+        Subsurface::new(colour::Colour::new(0.999, 0.998, 0.998), [0.456, 0.381, 0.333])
+    }
+
+    /// Skin, from Jensen et al.'s measurements, for scenes in millimetres.
+    pub fn skin() -> Subsurface {
+        Subsurface::new(colour::Colour::new(0.959, 0.838, 0.678), [1.296, 0.953, 0.671])
+    }
+}
+
+impl material::Material for Subsurface {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let outside = hit.intersected_surface_normal.get_direction().dot(towards_viewer) >= 0.0;
+        let normal = hit.shading_normal.normalised();
+        let normal = if outside { normal } else { -normal };
+        let eta = if outside { self.medium.ior } else { 1.0 / self.medium.ior };
+        let reflectance = microfacet::fresnel_dielectric(towards_viewer.dot(normal), eta);
+        let mirrored = microfacet::reflect(towards_viewer, normal);
+        let direction = if microfacet::random() < reflectance {
+            mirrored
+        } else {
+            microfacet::refract(towards_viewer, normal, eta).unwrap_or(mirrored)
+        };
+        ray::Ray::new(*hit.intersected_surface_normal.get_origin(), direction)
+    }
+
+    fn colour(&self, start_colour: colour::Colour, _hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        // Reflecting and refracting were chosen by the Fresnel equations, so they cancel out. What
+        // happens inside is accounted for by the walk.
+        start_colour
+    }
+
+    fn interior(&self) -> Option<Medium> {
+        Some(self.medium)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh;
+    use crate::sampler;
+    use crate::sphere;
+
+    /// The light coming out of a unit sphere, after going in at the top.
+    fn walk(medium: Medium, walks: usize) -> (Vec<Exit>, usize) {
+        let sphere = sphere::Sphere::new(
+            WorldVec::new(0.0, 0.0, 0.0),
+            1.0,
+            std::rc::Rc::new(Subsurface::with_medium(medium)),
+        );
+        let entering = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.999), WorldVec::new(0.0, 0.0, -1.0));
+        let exits: Vec<Exit> = (0..walks).filter_map(|_| random_walk(&sphere, &medium, entering)).collect();
+        let lost = walks - exits.len();
+        (exits, lost)
+    }
+
+    #[test]
+    fn test_random_walk() {
+        sampler::replace_current(sampler::Sampler::new(10));
+        let medium = Medium {
+            mean_free_path: [0.1, 0.1, 0.1],
+            albedo: [1.0, 1.0, 1.0],
+            anisotropy: 0.0,
+            ior: 1.0,
+        };
+        // With no absorption, all the light gets out again, from all over the sphere.
+        let (exits, lost) = walk(medium, 500);
+        assert!(lost < 5, "{}", lost);
+        assert!(exits.iter().all(|exit| exit.throughput.iter().all(|&t| (t - 1.0).abs() < 1e-9)));
+        for exit in &exits {
+            let origin = *exit.ray.get_origin();
+            assert!((origin.length() - 1.0).abs() < 1e-3);
+            assert!(exit.ray.get_direction().dot(origin) > 0.0);
+        }
+        assert!(exits.iter().any(|exit| exit.ray.get_origin().2 < 0.0));
+
+        // Absorption dims the light which gets out, and more so for the colours which meet more
+        // particles on the way.
+        let medium = Medium {
+            mean_free_path: [0.3, 0.1, 0.05],
+            albedo: [0.9, 0.9, 0.9],
+            ..medium
+        };
+        let (exits, _) = walk(medium, 2000);
+        let mean = |c: usize| exits.iter().map(|exit| exit.throughput[c]).sum::<f64>() / 2000.0;
+        assert!(mean(0) < 1.0 && mean(2) > 0.0);
+        assert!(mean(2) < mean(0), "{} {}", mean(2), mean(0));
+    }
+
+    #[test]
+    fn test_thick_medium() {
+        sampler::replace_current(sampler::Sampler::new(14));
+        // Marble, in millimetres, but without absorption, lit from the middle of a ball four
+        // centimetres across. Light scatters thousands of times on its way out, but it all gets
+        // out: the weights of the channels vary from walk to walk, but each averages 1.
+        let medium = Medium {
+            albedo: [1.0; 3],
+            ..Subsurface::marble().medium
+        };
+        let sphere = sphere::Sphere::new(
+            WorldVec::new(0.0, 0.0, 0.0),
+            20.0,
+            std::rc::Rc::new(Subsurface::with_medium(medium)),
+        );
+        let centre = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, -1.0));
+        let walks = 500;
+        let exits: Vec<Exit> = (0..walks).filter_map(|_| random_walk(&sphere, &medium, centre)).collect();
+        assert_eq!(exits.len(), walks);
+        assert!(exits.iter().any(|exit| exit.throughput.iter().any(|&t| t > 1.0)));
+        for c in 0..3 {
+            let mean = exits.iter().map(|exit| exit.throughput[c]).sum::<f64>() / walks as f64;
+            assert!((mean - 1.0).abs() < 0.1, "{} {}", c, mean);
+        }
+    }
+
+    #[test]
+    fn test_closed_mesh() {
+        sampler::replace_current(sampler::Sampler::new(15));
+        let medium = Medium {
+            mean_free_path: [0.1; 3],
+            albedo: [1.0; 3],
+            anisotropy: 0.3,
+            ior: 1.4,
+        };
+        // An octahedron, with its corners one unit along each axis.
+        let vertices = vec![
+            WorldVec::new(1.0, 0.0, 0.0),
+            WorldVec::new(-1.0, 0.0, 0.0),
+            WorldVec::new(0.0, 1.0, 0.0),
+            WorldVec::new(0.0, -1.0, 0.0),
+            WorldVec::new(0.0, 0.0, 1.0),
+            WorldVec::new(0.0, 0.0, -1.0),
+        ];
+        let triangles = vec![
+            [0, 2, 4], [2, 1, 4], [1, 3, 4], [3, 0, 4],
+            [2, 0, 5], [1, 2, 5], [3, 1, 5], [0, 3, 5],
+        ];
+        let octahedron = mesh::Mesh::new(vertices, triangles, std::rc::Rc::new(Subsurface::with_medium(medium)));
+        let entering = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.999), WorldVec::new(0.0, 0.0, -1.0));
+
+        // Light gets out through every face, on the surface and heading away from it.
+        let exits: Vec<Exit> = (0..500).filter_map(|_| random_walk(&octahedron, &medium, entering)).collect();
+        assert_eq!(exits.len(), 500);
+        let mut faces = std::collections::HashSet::new();
+        for exit in &exits {
+            let origin = *exit.ray.get_origin();
+            assert!((origin.0.abs() + origin.1.abs() + origin.2.abs() - 1.0).abs() < 1e-3, "{:?}", origin);
+            let outward = WorldVec::new(origin.0.signum(), origin.1.signum(), origin.2.signum());
+            assert!(exit.ray.get_direction().dot(outward) > 0.0);
+            faces.insert((origin.0 > 0.0, origin.1 > 0.0, origin.2 > 0.0));
+        }
+        assert_eq!(faces.len(), 8);
+    }
+
+    #[test]
+    fn test_scatter() {
+        sampler::replace_current(sampler::Sampler::new(11));
+        let forward = Medium {
+            mean_free_path: [1.0; 3],
+            albedo: [1.0; 3],
+            anisotropy: 0.8,
+            ior: 1.0,
+        };
+        let direction = WorldVec::new(0.0, 1.0, 0.0);
+        let mean_cos: f64 = (0..4000).map(|_| forward.scatter(direction).dot(direction)).sum::<f64>() / 4000.0;
+        // The mean cosine of Henyey-Greenstein scattering is its asymmetry.
+        assert!((mean_cos - 0.8).abs() < 0.03, "{}", mean_cos);
+    }
+}
@@ -0,0 +1,53 @@
+//! Scenery shared between the tests of several modules.
+
+use crate::colour;
+use crate::debugon;
+use crate::hit;
+use crate::lambertian;
+use crate::material;
+use crate::ray;
+use crate::WorldVec;
+
+/// A plain grey diffuse material, for tests which only care about geometry.
+pub fn grey() -> std::rc::Rc<dyn material::Material> {
+    std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.5, 0.5, 0.5)))
+}
+
+/// A hit at `point`, facing -z, with the given UVs. For looking up textures.
+pub fn hit_at(point: WorldVec, uv: (f64, f64)) -> hit::MaterialHit {
+    let from = ray::Ray::new(point - WorldVec::new(0.0, 0.0, 1.0), WorldVec::new(0.0, 0.0, 1.0));
+    hit::MaterialHit::new(std::rc::Rc::new(debugon::Debugon::new()), &from, 1.0, WorldVec::new(0.0, 0.0, -1.0), uv)
+}
+
+/// A hit on the z = 0 plane, facing +z with its tangent along x, from a ray coming in along
+/// `direction`.
+pub fn hit_on_plane(material: std::rc::Rc<dyn material::Material>, direction: WorldVec) -> (ray::Ray, hit::MaterialHit) {
+    let from = ray::Ray::new(-direction, direction);
+    let hit = hit::MaterialHit::new(material, &from, 1.0, WorldVec::new(0.0, 0.0, 1.0), (0.5, 0.5))
+        .with_tangent(WorldVec::new(1.0, 0.0, 0.0));
+    (from, hit)
+}
+
+/// The average weight of `samples` samples of `material` on the plane of `hit_on_plane`, for
+/// white light, and the fraction of them which went through the surface.
+pub fn mean_weight(material: std::rc::Rc<dyn material::Material>, direction: WorldVec, samples: usize) -> ([f64; 3], f64) {
+    let (from, hit) = hit_on_plane(material.clone(), direction);
+    let mut total = [0.0; 3];
+    let mut transmitted = 0.0;
+    for _ in 0..samples {
+        let towards_light = material.sample_gathering_ray(&from, &hit).get_direction().normalised();
+        if towards_light.2 < 0.0 {
+            transmitted += 1.0 / samples as f64;
+        }
+        let weight = material.colour_between(
+            colour::Colour::new(1.0, 1.0, 1.0),
+            &hit,
+            towards_light,
+            -*from.get_direction(),
+        );
+        total[0] += weight.get_red() / samples as f64;
+        total[1] += weight.get_green() / samples as f64;
+        total[2] += weight.get_blue() / samples as f64;
+    }
+    (total, transmitted)
+}
@@ -0,0 +1,336 @@
+use crate::colour;
+use crate::hit;
+use crate::raster;
+use crate::WorldVec;
+
+/// Something which varies across a surface, such as a material's colour or roughness. Textures
+/// are looked up from the hit's UVs or its position, whichever suits them.
+pub trait Texture: std::fmt::Debug {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour;
+
+    /// The texture as a single number, for parameters like roughness. This is the luminance, so a
+    /// greyscale texture gives its grey level.
+    fn scalar(&self, hit: &hit::MaterialHit) -> f64 {
+        self.colour(hit).luminance()
+    }
+}
+
+/// The same colour everywhere.
+#[derive(Debug)]
+pub struct Constant {
+    colour: colour::Colour,
+    /// Kept separately, as colours can't go above 1 but scalars (such as a refractive index) can.
+    scalar: f64,
+}
+
+impl Constant {
+    pub fn new(colour: colour::Colour) -> Constant {
+        Constant {
+            scalar: colour.luminance(),
+            colour,
+        }
+    }
+
+    /// A grey level, for scalar parameters. Its `scalar` is exactly `value`, even outside [0, 1].
+    pub fn scalar(value: f64) -> Constant {
+        Constant {
+            colour: colour::Colour::new(value, value, value),
+            scalar: value,
+        }
+    }
+}
+
+impl Texture for Constant {
+    fn colour(&self, _hit: &hit::MaterialHit) -> colour::Colour {
+        self.colour.clone()
+    }
+
+    fn scalar(&self, _hit: &hit::MaterialHit) -> f64 {
+        self.scalar
+    }
+}
+
+/// Whether the cell at some integer coordinates is even, in a checkerboard.
+fn is_even(cells: &[f64]) -> bool {
+    cells.iter().map(|cell| cell.floor() as i64).sum::<i64>().rem_euclid(2) == 0
+}
+
+/// A checkerboard over the surface's UVs, alternating between two other textures.
+#[derive(Debug)]
+pub struct Checker {
+    even: std::rc::Rc<dyn Texture>,
+    odd: std::rc::Rc<dyn Texture>,
+    /// How many squares there are along each of u and v.
+    squares: f64,
+}
+
+impl Checker {
+    pub fn new(even: std::rc::Rc<dyn Texture>, odd: std::rc::Rc<dyn Texture>, squares: f64) -> Checker {
+        Checker { even, odd, squares }
+    }
+}
+
+impl Texture for Checker {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let (u, v) = hit.uv;
+        if is_even(&[u * self.squares, v * self.squares]) {
+            self.even.colour(hit)
+        } else {
+            self.odd.colour(hit)
+        }
+    }
+}
+
+/// A checkerboard of cubes filling space, so that it doesn't depend on how the surface is
+/// parameterised. Objects look carved out of it.
+#[derive(Debug)]
+pub struct SolidChecker {
+    even: std::rc::Rc<dyn Texture>,
+    odd: std::rc::Rc<dyn Texture>,
+    /// The length of each cube's sides.
+    size: f64,
+}
+
+impl SolidChecker {
+    pub fn new(even: std::rc::Rc<dyn Texture>, odd: std::rc::Rc<dyn Texture>, size: f64) -> SolidChecker {
+        SolidChecker { even, odd, size }
+    }
+}
+
+impl Texture for SolidChecker {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let point = *hit.intersected_surface_normal.get_origin() * (1.0 / self.size);
+        if is_even(&[point.0, point.1, point.2]) {
+            self.even.colour(hit)
+        } else {
+            self.odd.colour(hit)
+        }
+    }
+}
+
+/// What an `ImageTexture` does with UVs outside of [0, 1].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Wrap {
+    /// Tile the image.
+    Repeat,
+    /// Tile the image, flipping every other copy so that the edges meet.
+    Mirror,
+    /// Stretch the edge pixels outwards.
+    Clamp,
+}
+
+impl Wrap {
+    /// Map some pixel index onto one inside an image `size` pixels across.
+    fn apply(self, index: i64, size: usize) -> usize {
+        let size = size as i64;
+        let wrapped = match self {
+            Wrap::Repeat => index.rem_euclid(size),
+            Wrap::Mirror => {
+                let period = index.rem_euclid(2 * size);
+                if period < size {
+                    period
+                } else {
+                    2 * size - 1 - period
+                }
+            }
+            Wrap::Clamp => index.clamp(0, size - 1),
+        };
+        wrapped as usize
+    }
+}
+
+/// An image stretched over the surface's UVs, with u running left to right and v bottom to top.
+/// Pixels are blended bilinearly. Bright HDR pixels keep their full value, so an HDR image can
+/// light a scene.
+#[derive(Debug)]
+pub struct ImageTexture {
+    raster: std::rc::Rc<raster::Raster>,
+    wrap: Wrap,
+}
+
+impl ImageTexture {
+    /// Panics if the image is empty.
+    pub fn new(raster: std::rc::Rc<raster::Raster>, wrap: Wrap) -> ImageTexture {
+        assert!(raster.x_len() > 0 && raster.y_len() > 0, "An image texture needs pixels.");
+        ImageTexture { raster, wrap }
+    }
+
+    /// Load an image file, in any format `Raster` understands.
+    pub fn load(path: impl AsRef<std::path::Path>, wrap: Wrap) -> std::io::Result<ImageTexture> {
+        let raster = raster::Raster::load(path)?;
+        if raster.x_len() == 0 || raster.y_len() == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "The image is empty"));
+        }
+        Ok(ImageTexture::new(std::rc::Rc::new(raster), wrap))
+    }
+
+    /// The bilinearly filtered value at some UV.
+    pub fn sample(&self, (u, v): (f64, f64)) -> [f64; 3] {
+        // Pixel centres are at half-integer coordinates.
+        let x = u * self.raster.x_len() as f64 - 0.5;
+        let y = (1.0 - v) * self.raster.y_len() as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let pixel = |dx: i64, dy: i64| {
+            self.raster[(
+                self.wrap.apply(x0 as i64 + dx, self.raster.x_len()),
+                self.wrap.apply(y0 as i64 + dy, self.raster.y_len()),
+            )]
+        };
+        let (a, b, c, d) = (pixel(0, 0), pixel(1, 0), pixel(0, 1), pixel(1, 1));
+        let mut result = [0.0; 3];
+        for (channel, value) in result.iter_mut().enumerate() {
+            let top = a[channel] + (b[channel] - a[channel]) * fx;
+            let bottom = c[channel] + (d[channel] - c[channel]) * fx;
+            *value = top + (bottom - top) * fy;
+        }
+        result
+    }
+}
+
+impl Texture for ImageTexture {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let [r, g, b] = self.sample(hit.uv);
+        colour::Colour::unclamped(r, g, b)
+    }
+}
+
+/// One channel of another texture, as a grey level. Formats such as glTF pack several scalar maps
+/// into the channels of one image, and this picks one of them back out.
+#[derive(Debug)]
+pub struct Channel {
+    source: std::rc::Rc<dyn Texture>,
+    /// 0 for red, 1 for green and 2 for blue.
+    index: usize,
+}
+
+impl Channel {
+    pub fn red(source: std::rc::Rc<dyn Texture>) -> Channel {
+        Channel { source, index: 0 }
+    }
+
+    pub fn green(source: std::rc::Rc<dyn Texture>) -> Channel {
+        Channel { source, index: 1 }
+    }
+
+    pub fn blue(source: std::rc::Rc<dyn Texture>) -> Channel {
+        Channel { source, index: 2 }
+    }
+}
+
+impl Texture for Channel {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let value = self.scalar(hit);
+        colour::Colour::new(value, value, value)
+    }
+
+    fn scalar(&self, hit: &hit::MaterialHit) -> f64 {
+        let colour = self.source.colour(hit);
+        [colour.get_red(), colour.get_green(), colour.get_blue()][self.index]
+    }
+}
+
+/// A linear blend between two colours, from one point in space to another. Points before the
+/// start or past the end get the colour there.
+#[derive(Debug)]
+pub struct Gradient {
+    start: WorldVec,
+    end: WorldVec,
+    start_colour: colour::Colour,
+    end_colour: colour::Colour,
+}
+
+impl Gradient {
+    pub fn new(
+        (start, start_colour): (WorldVec, colour::Colour),
+        (end, end_colour): (WorldVec, colour::Colour),
+    ) -> Gradient {
+        Gradient {
+            start,
+            end,
+            start_colour,
+            end_colour,
+        }
+    }
+}
+
+impl Texture for Gradient {
+    fn colour(&self, hit: &hit::MaterialHit) -> colour::Colour {
+        let axis = self.end - self.start;
+        let along = (*hit.intersected_surface_normal.get_origin() - self.start).dot(axis) / axis.dot(axis);
+        let s = if along.is_finite() { along.clamp(0.0, 1.0) } else { 0.0 };
+        self.start_colour.mix(&self.end_colour, s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::hit_at;
+
+    #[test]
+    fn test_checker() {
+        let checker = Checker::new(
+            std::rc::Rc::new(Constant::scalar(1.0)),
+            std::rc::Rc::new(Constant::scalar(0.0)),
+            4.0,
+        );
+        let origin = WorldVec::new(0.0, 0.0, 0.0);
+        assert_eq!(checker.scalar(&hit_at(origin, (0.1, 0.1))), 1.0);
+        assert_eq!(checker.scalar(&hit_at(origin, (0.3, 0.1))), 0.0);
+        assert_eq!(checker.scalar(&hit_at(origin, (0.3, 0.3))), 1.0);
+
+        let solid = SolidChecker::new(
+            std::rc::Rc::new(Constant::scalar(1.0)),
+            std::rc::Rc::new(Constant::scalar(0.0)),
+            0.5,
+        );
+        assert_eq!(solid.scalar(&hit_at(WorldVec::new(0.25, 0.25, 0.25), (0.0, 0.0))), 1.0);
+        assert_eq!(solid.scalar(&hit_at(WorldVec::new(-0.25, 0.25, 0.25), (0.0, 0.0))), 0.0);
+        assert_eq!(solid.scalar(&hit_at(WorldVec::new(-0.25, -0.25, 0.25), (0.0, 0.0))), 1.0);
+    }
+
+    #[test]
+    fn test_image_texture() {
+        // Black on the left, white on the right.
+        let raster = std::rc::Rc::new(raster::Raster::new(2, 1, vec![[0.0; 3], [1.0; 3]]));
+        let clamped = ImageTexture::new(raster.clone(), Wrap::Clamp);
+        assert_eq!(clamped.sample((0.25, 0.5)), [0.0; 3]);
+        assert_eq!(clamped.sample((0.5, 0.5)), [0.5; 3]);
+        assert_eq!(clamped.sample((2.0, 0.5)), [1.0; 3]);
+
+        // Halfway between the last pixel and the first again.
+        let repeated = ImageTexture::new(raster.clone(), Wrap::Repeat);
+        assert_eq!(repeated.sample((1.0, 0.5)), [0.5; 3]);
+        // The mirrored copy carries on as white.
+        let mirrored = ImageTexture::new(raster, Wrap::Mirror);
+        assert_eq!(mirrored.sample((1.0, 0.5)), [1.0; 3]);
+
+        // HDR pixels aren't scaled back into range.
+        let bright = ImageTexture::new(std::rc::Rc::new(raster::Raster::new(1, 1, vec![[4.0, 2.0, 0.5]])), Wrap::Clamp);
+        let colour = bright.colour(&hit_at(WorldVec::new(0.0, 0.0, 0.0), (0.5, 0.5)));
+        assert_eq!((colour.get_red(), colour.get_green(), colour.get_blue()), (4.0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn test_channel() {
+        let packed: std::rc::Rc<dyn Texture> = std::rc::Rc::new(Constant::new(colour::Colour::new(0.1, 0.4, 0.9)));
+        let hit = hit_at(WorldVec::new(0.0, 0.0, 0.0), (0.0, 0.0));
+        assert_eq!(Channel::red(packed.clone()).scalar(&hit), 0.1);
+        assert_eq!(Channel::green(packed.clone()).scalar(&hit), 0.4);
+        let blue = Channel::blue(packed).colour(&hit);
+        assert_eq!((blue.get_red(), blue.get_green(), blue.get_blue()), (0.9, 0.9, 0.9));
+    }
+
+    #[test]
+    fn test_gradient() {
+        let gradient = Gradient::new(
+            (WorldVec::new(0.0, 0.0, 0.0), colour::Colour::new(0.0, 0.0, 1.0)),
+            (WorldVec::new(0.0, 2.0, 0.0), colour::Colour::new(1.0, 0.0, 0.0)),
+        );
+        let colour = gradient.colour(&hit_at(WorldVec::new(5.0, 1.0, 0.0), (0.0, 0.0)));
+        assert_eq!((colour.get_red(), colour.get_blue()), (0.5, 0.5));
+        let colour = gradient.colour(&hit_at(WorldVec::new(0.0, -1.0, 0.0), (0.0, 0.0)));
+        assert_eq!((colour.get_red(), colour.get_blue()), (0.0, 1.0));
+    }
+}
@@ -0,0 +1,350 @@
+//! Thin-film interference: the colours of soap bubbles, oil slicks and anodised metal. Light
+//! reflecting off the top of a film a few hundred nanometres thick interferes with light
+//! reflecting off the bottom, so some wavelengths are reflected strongly and others cancel out,
+//! depending on the film's thickness and the angle. Rendering is in RGB, so each channel averages
+//! the reflectance at a few wavelengths across its band.
+
+use crate::colour;
+use crate::hit;
+use crate::material;
+use crate::microfacet;
+use crate::ray;
+use crate::texture;
+use crate::WorldVec;
+
+/// The wavelengths (in nanometres) averaged for each of red, green and blue.
+const WAVELENGTHS: [[f64; 3]; 3] = [[610.0, 640.0, 670.0], [520.0, 550.0, 580.0], [430.0, 460.0, 490.0]];
+
+/// Just enough complex arithmetic for the Fresnel equations of absorbing materials.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Complex(f64, f64);
+
+impl Complex {
+    fn real(x: f64) -> Complex {
+        Complex(x, 0.0)
+    }
+
+    fn add(self, other: Complex) -> Complex {
+        Complex(self.0 + other.0, self.1 + other.1)
+    }
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex(self.0 - other.0, self.1 - other.1)
+    }
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex(self.0 * other.0 - self.1 * other.1, self.0 * other.1 + self.1 * other.0)
+    }
+
+    fn div(self, other: Complex) -> Complex {
+        let denominator = other.norm_squared();
+        Complex(
+            (self.0 * other.0 + self.1 * other.1) / denominator,
+            (self.1 * other.0 - self.0 * other.1) / denominator,
+        )
+    }
+
+    fn norm_squared(self) -> f64 {
+        self.0 * self.0 + self.1 * self.1
+    }
+
+    /// The principal square root, with a non-negative real part.
+    fn sqrt(self) -> Complex {
+        let modulus = self.norm_squared().sqrt();
+        let re = ((modulus + self.0) * 0.5).max(0.0).sqrt();
+        let im = ((modulus - self.0) * 0.5).max(0.0).sqrt();
+        Complex(re, if self.1 < 0.0 { -im } else { im })
+    }
+
+    /// e to the power of i times this.
+    fn exp_i(self) -> Complex {
+        let scale = (-self.1).exp();
+        Complex(scale * self.0.cos(), scale * self.0.sin())
+    }
+}
+
+/// The cosine of the angle to the normal in a medium of index `n`, for light arriving from a
+/// medium of index `from` at `cos_from`. It's complex when the light is evanescent or absorbed.
+fn snell_cos(from: Complex, cos_from: f64, n: Complex) -> Complex {
+    let ratio = from.div(n);
+    let sin2 = Complex::real(1.0 - cos_from * cos_from).mul(ratio).mul(ratio);
+    Complex::real(1.0).sub(sin2).sqrt()
+}
+
+/// The amplitudes reflected at the boundary between media of indices `a` and `b`, at angles with
+/// cosines `cos_a` and `cos_b`, for s and p polarised light.
+fn fresnel_amplitudes(a: Complex, cos_a: Complex, b: Complex, cos_b: Complex) -> (Complex, Complex) {
+    let (a_a, b_b) = (a.mul(cos_a), b.mul(cos_b));
+    let s = a_a.sub(b_b).div(a_a.add(b_b));
+    let (b_a, a_b) = (b.mul(cos_a), a.mul(cos_b));
+    let p = b_a.sub(a_b).div(b_a.add(a_b));
+    (s, p)
+}
+
+/// The fraction of light of some `wavelength` reflected by a film `thickness` thick (both in
+/// nanometres), averaged over both polarisations. The light arrives at `cos_i` to the normal from
+/// a medium with refractive index `outside`; the film's index is `film`; and below it is a medium
+/// with complex index `below.0 + i below.1` (for a dielectric, `below.1` is 0).
+pub fn film_reflectance(
+    cos_i: f64,
+    wavelength: f64,
+    thickness: f64,
+    outside: f64,
+    film: f64,
+    below: (f64, f64),
+) -> f64 {
+    let cos_i = cos_i.clamp(0.0, 1.0);
+    let (n1, n2, n3) = (Complex::real(outside), Complex::real(film), Complex(below.0, below.1));
+    let cos1 = Complex::real(cos_i);
+    let cos2 = snell_cos(n1, cos_i, n2);
+    let cos3 = snell_cos(n1, cos_i, n3);
+    let (top_s, top_p) = fresnel_amplitudes(n1, cos1, n2, cos2);
+    let (bottom_s, bottom_p) = fresnel_amplitudes(n2, cos2, n3, cos3);
+
+    // The extra phase of the light which went down through the film and back up again.
+    let phase = Complex::real(4.0 * std::f64::consts::PI * thickness / wavelength)
+        .mul(n2)
+        .mul(cos2)
+        .exp_i();
+    // The sum of every number of round trips, as a geometric series (Airy's formula).
+    let airy = |top: Complex, bottom: Complex| {
+        let bottom = bottom.mul(phase);
+        top.add(bottom).div(Complex::real(1.0).add(top.mul(bottom))).norm_squared()
+    };
+    ((airy(top_s, bottom_s) + airy(top_p, bottom_p)) * 0.5).clamp(0.0, 1.0)
+}
+
+/// What is underneath a thin film.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Substrate {
+    /// Nothing: the film is free-standing, like a soap bubble, and light which isn't reflected
+    /// carries straight on through.
+    Air,
+    /// A clear material with this refractive index, like the water under an oil slick. Light which
+    /// isn't reflected is refracted into it.
+    Dielectric(f64),
+    /// A metal, with complex refractive index `eta + ik` for red, green and blue. Light which isn't
+    /// reflected is absorbed.
+    Conductor { eta: [f64; 3], k: [f64; 3] },
+}
+
+/// A smooth surface with a thin film on top. The film's thickness can vary across the surface,
+/// which gives the swirling bands of colour on bubbles and oil.
+#[derive(Debug)]
+pub struct ThinFilm {
+    /// The refractive index of the film.
+    ior: f64,
+    /// Read as a scalar, in micrometres (so 0.5 is 500nm).
+    thickness: std::rc::Rc<dyn texture::Texture>,
+    substrate: Substrate,
+}
+
+impl ThinFilm {
+    /// `thickness` is read as a scalar, in micrometres. Interference colours are strongest from
+    /// about 0.1 to 1.
+    pub fn new(ior: f64, thickness: std::rc::Rc<dyn texture::Texture>, substrate: Substrate) -> ThinFilm {
+        ThinFilm {
+            ior,
+            thickness,
+            substrate,
+        }
+    }
+
+    pub fn soap_bubble(thickness: std::rc::Rc<dyn texture::Texture>) -> ThinFilm {
+        ThinFilm::new(1.33, thickness, Substrate::Air)
+    }
+
+    pub fn oil_slick(thickness: std::rc::Rc<dyn texture::Texture>) -> ThinFilm {
+        ThinFilm::new(1.47, thickness, Substrate::Dielectric(1.33))
+    }
+
+    /// Titanium with a film of its oxide grown on it, which is how anodised titanium gets its
+    /// colours.
+    pub fn anodised_titanium(thickness: std::rc::Rc<dyn texture::Texture>) -> ThinFilm {
+        ThinFilm::new(
+            2.2,
+            thickness,
+            Substrate::Conductor {
+                eta: [2.74, 2.54, 2.16],
+                k: [3.81, 3.43, 2.93],
+            },
+        )
+    }
+
+    /// The refractive indices on the viewer's side and beyond the film, for `channel`.
+    fn media(&self, channel: usize, outside: bool) -> (f64, (f64, f64)) {
+        match self.substrate {
+            Substrate::Air => (1.0, (1.0, 0.0)),
+            Substrate::Dielectric(ior) if outside => (1.0, (ior, 0.0)),
+            Substrate::Dielectric(ior) => (ior, (1.0, 0.0)),
+            Substrate::Conductor { eta, k } => (1.0, (eta[channel], k[channel])),
+        }
+    }
+
+    /// The reflectance in each channel, for light at `cos_i` to the normal.
+    fn reflectance(&self, hit: &hit::MaterialHit, cos_i: f64, outside: bool) -> [f64; 3] {
+        let thickness = self.thickness.scalar(hit).max(0.0) * 1000.0;
+        let mut reflectance = [0.0; 3];
+        for (channel, value) in reflectance.iter_mut().enumerate() {
+            let (above, below) = self.media(channel, outside);
+            let wavelengths = &WAVELENGTHS[channel];
+            *value = wavelengths
+                .iter()
+                .map(|&wavelength| film_reflectance(cos_i, wavelength, thickness, above, self.ior, below))
+                .sum::<f64>()
+                / wavelengths.len() as f64;
+        }
+        reflectance
+    }
+
+    /// How likely a ray is to be reflected rather than carry on through, given the reflectance.
+    fn reflection_probability(&self, reflectance: [f64; 3]) -> f64 {
+        match self.substrate {
+            Substrate::Conductor { .. } => 1.0,
+            _ => (reflectance[0] + reflectance[1] + reflectance[2]) / 3.0,
+        }
+    }
+}
+
+fn is_outside(hit: &hit::MaterialHit, towards_viewer: WorldVec) -> bool {
+    hit.intersected_surface_normal.get_direction().dot(towards_viewer) >= 0.0
+}
+
+impl material::Material for ThinFilm {
+    fn sample_gathering_ray(&self, reflected_ray: &ray::Ray, hit: &hit::MaterialHit) -> ray::Ray {
+        let towards_viewer = -reflected_ray.get_direction().normalised();
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let wo = frame.to_local(towards_viewer);
+        let up = WorldVec::new(0.0, 0.0, 1.0);
+        let outside = is_outside(hit, towards_viewer);
+        let probability = self.reflection_probability(self.reflectance(hit, wo.2, outside));
+        let wi = if wo.2 <= 0.0 || microfacet::random() < probability {
+            microfacet::reflect(wo, up)
+        } else {
+            match self.substrate {
+                Substrate::Dielectric(ior) => {
+                    let eta = if outside { ior } else { 1.0 / ior };
+                    microfacet::refract(wo, up, eta).unwrap_or_else(|| microfacet::reflect(wo, up))
+                }
+                _ => -wo,
+            }
+        };
+        ray::Ray::new(*hit.intersected_surface_normal.get_origin(), frame.to_world(wi))
+    }
+
+    fn colour(&self, start_colour: colour::Colour, hit: &hit::MaterialHit, _angle_of_incidence: f64) -> colour::Colour {
+        // Without the viewing direction, use the colour seen head on.
+        let [r, g, b] = self.reflectance(hit, 1.0, true);
+        material::attenuate(&start_colour, &colour::Colour::new(r, g, b))
+    }
+
+    fn colour_between(
+        &self,
+        start_colour: colour::Colour,
+        hit: &hit::MaterialHit,
+        towards_light: WorldVec,
+        towards_viewer: WorldVec,
+    ) -> colour::Colour {
+        let frame = microfacet::Frame::new(hit, towards_viewer);
+        let (wi, wo) = (frame.to_local(towards_light), frame.to_local(towards_viewer));
+        if wo.2 <= 0.0 {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        let reflectance = self.reflectance(hit, wo.2, is_outside(hit, towards_viewer));
+        let probability = self.reflection_probability(reflectance);
+        // Each way was chosen with some probability, which the weight divides out.
+        let weight = |channel: usize| {
+            if wi.2 > 0.0 {
+                reflectance[channel] / probability
+            } else {
+                (1.0 - reflectance[channel]) / (1.0 - probability)
+            }
+        };
+        let (r, g, b) = (weight(0), weight(1), weight(2));
+        if !(r.is_finite() && g.is_finite() && b.is_finite()) {
+            return colour::Colour::new(0.0, 0.0, 0.0);
+        }
+        material::attenuate(&start_colour, &colour::Colour::unclamped(r, g, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sampler;
+    use material::Material;
+
+    #[test]
+    fn test_film_reflectance() {
+        // A film with no thickness, or the same index as outside, isn't there.
+        for &cos_i in &[1.0, 0.7, 0.2] {
+            let bare = microfacet::fresnel_dielectric(cos_i, 1.5);
+            assert!((film_reflectance(cos_i, 550.0, 0.0, 1.0, 1.33, (1.5, 0.0)) - bare).abs() < 1e-9);
+            assert!((film_reflectance(cos_i, 550.0, 300.0, 1.0, 1.0, (1.5, 0.0)) - bare).abs() < 1e-9);
+            let metal = microfacet::fresnel_conductor(cos_i, 0.2, 3.9);
+            assert!((film_reflectance(cos_i, 550.0, 0.0, 1.0, 1.5, (0.2, 3.9)) - metal).abs() < 1e-9);
+        }
+
+        // A quarter-wave anti-reflective coating of the right index cancels reflections entirely.
+        let coating = 1.5f64.sqrt();
+        let quarter_wave = 550.0 / (4.0 * coating);
+        assert!(film_reflectance(1.0, 550.0, quarter_wave, 1.0, coating, (1.5, 0.0)) < 1e-9);
+        // While a half-wave one is as if it wasn't there.
+        let bare = microfacet::fresnel_dielectric(1.0, 1.5);
+        assert!((film_reflectance(1.0, 550.0, 2.0 * quarter_wave, 1.0, coating, (1.5, 0.0)) - bare).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_soap_bubble() {
+        let thin = ThinFilm::soap_bubble(std::rc::Rc::new(texture::Constant::scalar(0.3)));
+        let direction = WorldVec::new(0.0, 0.0, -1.0);
+        let from = ray::Ray::new(-direction, direction);
+        let hit = hit::MaterialHit::new(
+            std::rc::Rc::new(ThinFilm::soap_bubble(std::rc::Rc::new(texture::Constant::scalar(0.3)))),
+            &from,
+            1.0,
+            WorldVec::new(0.0, 0.0, 1.0),
+            (0.0, 0.0),
+        );
+
+        // The film is coloured, and mostly clear.
+        let [r, g, b] = thin.reflectance(&hit, 1.0, true);
+        assert!((r - g).abs() > 0.01 || (g - b).abs() > 0.01, "{} {} {}", r, g, b);
+        assert!(r.max(g).max(b) < 0.3);
+
+        // Reflected light takes on the film's colour, and light passing straight through its
+        // complement. Each way is weighted by how likely it was to be chosen, so between them
+        // all of the light is accounted for.
+        let white = colour::Colour::new(1.0, 1.0, 1.0);
+        let p = thin.reflection_probability([r, g, b]);
+        let reflected = thin.colour_between(white.clone(), &hit, -direction, -direction);
+        let through = thin.colour_between(white.clone(), &hit, direction, -direction);
+        for (channel, (reflected, through)) in [
+            (r, (reflected.get_red(), through.get_red())),
+            (g, (reflected.get_green(), through.get_green())),
+            (b, (reflected.get_blue(), through.get_blue())),
+        ]
+        .iter()
+        {
+            assert!((reflected * p - channel).abs() < 1e-9, "{} {}", reflected, channel);
+            assert!((through * (1.0 - p) - (1.0 - channel)).abs() < 1e-9, "{} {}", through, channel);
+            assert!((reflected * p + through * (1.0 - p) - 1.0).abs() < 1e-9);
+        }
+        // And so does sampling: the mean weight is 1 in every channel.
+        sampler::replace_current(sampler::Sampler::new(13));
+        let samples = 4000;
+        let mut total = [0.0; 3];
+        for _ in 0..samples {
+            let towards_light = thin.sample_gathering_ray(&from, &hit).get_direction().normalised();
+            let weight = thin.colour_between(white.clone(), &hit, towards_light, -direction);
+            total[0] += weight.get_red();
+            total[1] += weight.get_green();
+            total[2] += weight.get_blue();
+        }
+        for channel in &total {
+            assert!((channel / samples as f64 - 1.0).abs() < 0.05, "{:?}", total);
+        }
+        let ray = thin.sample_gathering_ray(&from, &hit);
+        assert!(ray.get_direction().normalised() == direction || ray.get_direction().normalised() == -direction);
+    }
+}
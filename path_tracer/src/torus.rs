@@ -0,0 +1,171 @@
+use crate::aabb;
+use crate::cylinder;
+use crate::hit;
+use crate::material;
+use crate::polynomial;
+use crate::ray;
+use crate::WorldVec;
+
+/// A torus lying flat (around the y axis), made by sweeping a circle of `minor_radius` around a
+/// circle of `major_radius`. Wrap it in an `Instance` to tilt it.
+#[derive(Debug)]
+pub struct Torus {
+    centre: WorldVec,
+    major_radius: f64,
+    minor_radius: f64,
+    material: std::rc::Rc<dyn material::Material>,
+}
+
+impl Torus {
+    pub fn new(
+        centre: WorldVec,
+        major_radius: f64,
+        minor_radius: f64,
+        material: std::rc::Rc<dyn material::Material>,
+    ) -> Torus {
+        Torus {
+            centre,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl hit::Hit for Torus {
+    fn hit(&self, from: &ray::Ray) -> Option<hit::MaterialHit> {
+        let (big_r, small_r) = (self.major_radius, self.minor_radius);
+        let length = from.get_direction().length();
+        let direction = *from.get_direction() * (1.0 / length);
+        let mut origin = *from.get_origin() - self.centre;
+
+        // The quartic is badly conditioned far away from the torus, so start from where the ray
+        // enters the torus' bounding sphere instead.
+        let bound = big_r + small_r;
+        let to_centre = origin.dot(direction);
+        let discriminant = to_centre * to_centre - (origin.dot(origin) - bound * bound);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let skipped = (-to_centre - discriminant.sqrt()).max(0.0);
+        origin = origin + direction * skipped;
+
+        // Substitute p = o + td (with |d| = 1) into (|p|^2 + R^2 - r^2)^2 = 4R^2 (x^2 + z^2).
+        let k = origin.dot(origin) + big_r * big_r - small_r * small_r;
+        let m = origin.dot(direction);
+        let four_r2 = 4.0 * big_r * big_r;
+        let coefficients = [
+            k * k - four_r2 * (origin.0 * origin.0 + origin.2 * origin.2),
+            4.0 * m * k - 2.0 * four_r2 * (origin.0 * direction.0 + origin.2 * direction.2),
+            4.0 * m * m + 2.0 * k - four_r2 * (direction.0 * direction.0 + direction.2 * direction.2),
+            4.0 * m,
+            1.0,
+        ];
+        let t = polynomial::real_roots(&coefficients)
+            .into_iter()
+            .find(|&t| from.in_range((skipped + t) / length))?;
+
+        let point = origin + direction * t;
+        // The gradient of the torus' implicit surface.
+        let s = point.dot(point) + big_r * big_r - small_r * small_r;
+        let normal = WorldVec::new(
+            point.0 * (s - 2.0 * big_r * big_r),
+            point.1 * s,
+            point.2 * (s - 2.0 * big_r * big_r),
+        )
+        .normalised();
+        // u goes around the ring, v around the tube.
+        let from_axis = (point.0 * point.0 + point.2 * point.2).sqrt();
+        let uv = (
+            cylinder::angle_around_y(point.0, point.2),
+            0.5 + point.1.atan2(from_axis - big_r) / (2.0 * std::f64::consts::PI),
+        );
+
+        Some(
+            hit::MaterialHit::new(
+                std::rc::Rc::clone(&self.material),
+                from,
+                (skipped + t) / length,
+                normal,
+                uv,
+            )
+            .with_tangent(WorldVec::new(point.2, 0.0, -point.0)),
+        )
+    }
+
+    fn bounding_box(&self) -> Option<aabb::Aabb> {
+        let extent = WorldVec::new(
+            self.major_radius + self.minor_radius,
+            self.minor_radius,
+            self.major_radius + self.minor_radius,
+        );
+        Some(aabb::Aabb::new(self.centre - extent, self.centre + extent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::colour;
+    use crate::hit::Hit;
+    use crate::lambertian;
+
+    #[test]
+    fn test_hit_torus() {
+        let torus = Torus::new(
+            WorldVec::new(0.0, 0.0, 10.0),
+            2.0,
+            0.5,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0))),
+        );
+
+        // Cast a ray along the z axis, through the near side of the ring.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 0.0, 0.0), WorldVec::new(0.0, 0.0, 1.0));
+        let hit = torus.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(0.0, 0.0, 7.5)).length() < 1e-9);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 0.0, -1.0)).length() < 1e-9);
+
+        // Straight down through the hole. This should not hit anything.
+        let ray = ray::Ray::new(WorldVec::new(0.0, 10.0, 10.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&ray).is_none());
+
+        // Straight down onto the top of the ring, with an unnormalised direction.
+        let ray = ray::Ray::new(WorldVec::new(2.0, 10.0, 10.0), WorldVec::new(0.0, -3.0, 0.0));
+        let hit = torus.hit(&ray).expect("The ray should hit.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(2.0, 0.5, 10.0)).length() < 1e-9);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 1.0, 0.0)).length() < 1e-9);
+
+        // Just miss the outside of the ring.
+        let ray = ray::Ray::new(WorldVec::new(2.51, 10.0, 10.0), WorldVec::new(0.0, -1.0, 0.0));
+        assert!(torus.hit(&ray).is_none());
+    }
+
+    #[test]
+    fn test_torus_bounds_and_uv() {
+        let torus = Torus::new(
+            WorldVec::new(0.0, 0.0, 10.0),
+            2.0,
+            0.5,
+            std::rc::Rc::new(lambertian::Lambertian::new(colour::Colour::new(0.9, 0.0, 0.0))),
+        );
+        let bounds = torus.bounding_box().unwrap();
+        assert_eq!((bounds.min, bounds.max), (WorldVec::new(-2.5, -0.5, 7.5), WorldVec::new(2.5, 0.5, 12.5)));
+
+        // The top of the tube, a quarter of the way around it.
+        let ray = ray::Ray::new(WorldVec::new(2.0, 10.0, 10.0), WorldVec::new(0.0, -1.0, 0.0));
+        let hit = torus.hit(&ray).expect("The ray should hit.");
+        assert!((hit.uv.0 - 0.5).abs() < 1e-9 && (hit.uv.1 - 0.75).abs() < 1e-9);
+
+        // Skimming across the top of the ring, touching the tube at two double roots, then just
+        // missing it.
+        let ray = ray::Ray::new(WorldVec::new(-10.0, 0.5, 10.0), WorldVec::new(1.0, 0.0, 0.0));
+        let hit = torus.hit(&ray).expect("The ray should graze the top.");
+        let normal = hit.intersected_surface_normal;
+        assert!((*normal.get_origin() - WorldVec::new(-2.0, 0.5, 10.0)).length() < 1e-4);
+        assert!((*normal.get_direction() - WorldVec::new(0.0, 1.0, 0.0)).length() < 1e-3);
+        let ray = ray::Ray::new(WorldVec::new(-10.0, 0.5 + 1e-6, 10.0), WorldVec::new(1.0, 0.0, 0.0));
+        assert!(torus.hit(&ray).is_none());
+    }
+}
@@ -0,0 +1,402 @@
+use crate::ray;
+use crate::WorldVec;
+
+/// A 4x4 matrix of `f64`s, stored in row-major order. Points are treated as column vectors with
+/// an implicit w of 1, and directions as column vectors with an implicit w of 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Matrix4(pub [[f64; 4]; 4]);
+
+impl Matrix4 {
+    pub fn identity() -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            row[i] = 1.0;
+        }
+        Matrix4(m)
+    }
+
+    pub fn transpose(&self) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.0[j][i];
+            }
+        }
+        Matrix4(m)
+    }
+
+    /// Invert the matrix using Gauss-Jordan elimination with partial pivoting. Returns `None` if
+    /// the matrix is singular, or has NaNs in it.
+    pub fn inverse(&self) -> Option<Matrix4> {
+        let mut m = self.0;
+        let mut inverse = Matrix4::identity().0;
+        for column in 0..4 {
+            // Find the row with the largest value in this column to pivot on.
+            let pivot = (column..4)
+                .max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))
+                .unwrap();
+            // NaNs sort above everything else, so they end up here too.
+            let pivot_size = m[pivot][column].abs();
+            if pivot_size.is_nan() || pivot_size < 1e-12 {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for k in 0..4 {
+                m[column][k] *= scale;
+                inverse[column][k] *= scale;
+            }
+            for row in 0..4 {
+                if row == column {
+                    continue;
+                }
+                let factor = m[row][column];
+                for k in 0..4 {
+                    m[row][k] -= factor * m[column][k];
+                    inverse[row][k] -= factor * inverse[column][k];
+                }
+            }
+        }
+        Some(Matrix4(inverse))
+    }
+
+    pub fn transform_point(&self, point: WorldVec) -> WorldVec {
+        let m = &self.0;
+        let x = m[0][0] * point.0 + m[0][1] * point.1 + m[0][2] * point.2 + m[0][3];
+        let y = m[1][0] * point.0 + m[1][1] * point.1 + m[1][2] * point.2 + m[1][3];
+        let z = m[2][0] * point.0 + m[2][1] * point.1 + m[2][2] * point.2 + m[2][3];
+        let w = m[3][0] * point.0 + m[3][1] * point.1 + m[3][2] * point.2 + m[3][3];
+        if w == 1.0 {
+            WorldVec::new(x, y, z)
+        } else {
+            WorldVec::new(x / w, y / w, z / w)
+        }
+    }
+
+    pub fn transform_vector(&self, vector: WorldVec) -> WorldVec {
+        let m = &self.0;
+        WorldVec::new(
+            m[0][0] * vector.0 + m[0][1] * vector.1 + m[0][2] * vector.2,
+            m[1][0] * vector.0 + m[1][1] * vector.1 + m[1][2] * vector.2,
+            m[2][0] * vector.0 + m[2][1] * vector.1 + m[2][2] * vector.2,
+        )
+    }
+}
+
+impl std::ops::Mul for Matrix4 {
+    type Output = Matrix4;
+    fn mul(self, rhs: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.0[i][k] * rhs.0[k][j]).sum();
+            }
+        }
+        Matrix4(m)
+    }
+}
+
+/// Which way around to rotate from one orientation to another.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Arc {
+    /// Through the smaller angle, at most half a turn.
+    Short,
+    /// Through the larger angle, at least half a turn.
+    Long,
+}
+
+/// A rotation, as a unit quaternion. Unlike matrices, these can be smoothly interpolated.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Quaternion {
+    pub w: f64,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Quaternion {
+    pub fn identity() -> Quaternion {
+        Quaternion { w: 1.0, x: 0.0, y: 0.0, z: 0.0 }
+    }
+
+    /// Rotate anticlockwise (when looking down `axis` towards the origin) by `angle` radians.
+    pub fn from_axis_angle(axis: WorldVec, angle: f64) -> Quaternion {
+        let axis = axis.normalised();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Quaternion {
+            w: cos,
+            x: axis.0 * sin,
+            y: axis.1 * sin,
+            z: axis.2 * sin,
+        }
+    }
+
+    pub(crate) fn dot(&self, other: &Quaternion) -> f64 {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    fn scaled(&self, s: f64) -> Quaternion {
+        Quaternion { w: self.w * s, x: self.x * s, y: self.y * s, z: self.z * s }
+    }
+
+    fn plus(&self, other: &Quaternion) -> Quaternion {
+        Quaternion {
+            w: self.w + other.w,
+            x: self.x + other.x,
+            y: self.y + other.y,
+            z: self.z + other.z,
+        }
+    }
+
+    /// Spherical linear interpolation: rotate at a constant speed from `self` (at `s = 0`) to
+    /// `other` (at `s = 1`), the short way around.
+    pub fn slerp(&self, other: &Quaternion, s: f64) -> Quaternion {
+        self.slerp_along(other, s, Arc::Short)
+    }
+
+    /// Like `slerp`, but going whichever way around `arc` says. Going the long way around to the
+    /// same rotation would be a whole turn about no axis in particular, so that stays still.
+    pub fn slerp_along(&self, other: &Quaternion, s: f64, arc: Arc) -> Quaternion {
+        // q and -q are the same rotation, but interpolating towards one goes the other way around
+        // from interpolating towards the other. Pick whichever matches `arc`.
+        let mut other = *other;
+        let mut cos = self.dot(&other);
+        let long = arc == Arc::Long && cos.abs() < 0.9995;
+        if (cos < 0.0) != long {
+            other = other.scaled(-1.0);
+            cos = -cos;
+        }
+        let result = if cos > 0.9995 {
+            // Nearly the same, so a straight line is fine (and avoids dividing by ~0).
+            self.scaled(1.0 - s).plus(&other.scaled(s))
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            self.scaled(((1.0 - s) * angle).sin() / sin)
+                .plus(&other.scaled((s * angle).sin() / sin))
+        };
+        result.scaled(1.0 / result.dot(&result).sqrt())
+    }
+
+    pub fn to_matrix(&self) -> Matrix4 {
+        let Quaternion { w, x, y, z } = *self;
+        Matrix4([
+            [1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z), 2.0 * (x * z + w * y), 0.0],
+            [2.0 * (x * y + w * z), 1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x), 0.0],
+            [2.0 * (x * z - w * y), 2.0 * (y * z + w * x), 1.0 - 2.0 * (x * x + y * y), 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+/// An invertible affine transform from object space to world space. We keep the inverse around,
+/// as we need it for every ray we transform.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Transform {
+    /// Create a transform from a matrix. Returns `None` if the matrix can't be inverted.
+    pub fn new(matrix: Matrix4) -> Option<Transform> {
+        matrix.inverse().map(|inverse| Transform { matrix, inverse })
+    }
+
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    pub fn translation(offset: WorldVec) -> Transform {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, value) in [offset.0, offset.1, offset.2].iter().enumerate() {
+            matrix.0[i][3] = *value;
+            inverse.0[i][3] = -*value;
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Scale along each axis. Panics if any of the factors are zero.
+    pub fn scaling(factors: WorldVec) -> Transform {
+        assert!(
+            factors.0 != 0.0 && factors.1 != 0.0 && factors.2 != 0.0,
+            "Can't scale by zero."
+        );
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (i, value) in [factors.0, factors.1, factors.2].iter().enumerate() {
+            matrix.0[i][i] = *value;
+            inverse.0[i][i] = 1.0 / *value;
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Rotate anticlockwise (when looking down `axis` towards the origin) by `angle` radians.
+    pub fn rotation(axis: WorldVec, angle: f64) -> Transform {
+        // Rodrigues' rotation formula.
+        let axis = axis.normalised();
+        let (x, y, z) = (axis.0, axis.1, axis.2);
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+        let matrix = Matrix4([
+            [t * x * x + cos, t * x * y - sin * z, t * x * z + sin * y, 0.0],
+            [t * x * y + sin * z, t * y * y + cos, t * y * z - sin * x, 0.0],
+            [t * x * z - sin * y, t * y * z + sin * x, t * z * z + cos, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        // Rotations are orthogonal, so the inverse is just the transpose.
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// The rotation described by a unit quaternion.
+    pub fn from_quaternion(rotation: Quaternion) -> Transform {
+        let matrix = rotation.to_matrix();
+        // Rotations are orthogonal, so the inverse is just the transpose.
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Apply `self`, then `next`.
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            matrix: next.matrix * self.matrix,
+            inverse: self.inverse * next.inverse,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn transform_point(&self, point: WorldVec) -> WorldVec {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn transform_vector(&self, vector: WorldVec) -> WorldVec {
+        self.matrix.transform_vector(vector)
+    }
+
+    /// Transform a surface normal. Normals don't transform like other directions once there's a
+    /// non-uniform scale involved -- they need the inverse transpose to stay perpendicular to the
+    /// surface. The result is normalised.
+    pub fn transform_normal(&self, normal: WorldVec) -> WorldVec {
+        self.normal_matrix().transform_vector(normal).normalised()
+    }
+
+    /// The inverse transpose, which `transform_normal` transforms normals with. Worth keeping
+    /// around when transforming lots of normals.
+    pub fn normal_matrix(&self) -> Matrix4 {
+        self.inverse.transpose()
+    }
+
+    /// Transform a ray. Note that the direction is not renormalised, so a point at `t` along the
+    /// transformed ray corresponds to the point at `t` along the original ray, and the ray's t
+    /// range carries over unchanged.
+    pub fn transform_ray(&self, ray: &ray::Ray) -> ray::Ray {
+        ray::Ray::new(
+            self.transform_point(*ray.get_origin()),
+            self.transform_vector(*ray.get_direction()),
+        )
+        .with_t_range(ray.get_t_min(), ray.get_t_max())
+        .with_time(ray.get_time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: WorldVec, b: WorldVec) {
+        assert!((a - b).length() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_inverse() {
+        let transform = Transform::scaling(WorldVec::new(2.0, 3.0, 4.0))
+            .then(&Transform::rotation(WorldVec::new(1.0, 1.0, 0.0), 0.7))
+            .then(&Transform::translation(WorldVec::new(1.0, -2.0, 3.0)));
+        let inverse = transform.matrix().inverse().unwrap();
+        let product = inverse * *transform.matrix();
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1.0 } else { 0.0 };
+                assert!((product.0[i][j] - expected).abs() < 1e-9);
+            }
+        }
+        let point = WorldVec::new(0.5, 0.25, -1.0);
+        assert_close(
+            transform.inverse().transform_point(transform.transform_point(point)),
+            point,
+        );
+
+        let mut singular = Matrix4::identity();
+        singular.0[2][2] = 0.0;
+        assert!(Transform::new(singular).is_none());
+
+        // NaNs can't be pivoted on, wherever they are.
+        for row in 0..4 {
+            let mut nan = Matrix4::identity();
+            nan.0[row][1] = f64::NAN;
+            assert!(nan.inverse().is_none());
+        }
+    }
+
+    #[test]
+    fn test_rotation() {
+        let quarter_turn = Transform::rotation(WorldVec::new(0.0, 0.0, 1.0), std::f64::consts::FRAC_PI_2);
+        assert_close(
+            quarter_turn.transform_point(WorldVec::new(1.0, 0.0, 0.0)),
+            WorldVec::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn test_transform_normal() {
+        // Squash the plane x + y = 0 along x. Its normal should stay perpendicular to it.
+        let squash = Transform::scaling(WorldVec::new(0.5, 1.0, 1.0));
+        let along_plane = squash.transform_vector(WorldVec::new(1.0, -1.0, 0.0));
+        let normal = squash.transform_normal(WorldVec::new(1.0, 1.0, 0.0).normalised());
+        assert!(along_plane.dot(normal).abs() < 1e-12);
+        assert!((normal.length() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_slerp() {
+        let axis = WorldVec::new(0.0, 1.0, 0.0);
+        let start = Quaternion::from_axis_angle(axis, 0.0);
+        let end = Quaternion::from_axis_angle(axis, 2.0);
+        let halfway = Transform::from_quaternion(start.slerp(&end, 0.5));
+        let expected = Transform::rotation(axis, 1.0);
+        let point = WorldVec::new(1.0, 2.0, 3.0);
+        assert_close(halfway.transform_point(point), expected.transform_point(point));
+
+        // Three quarters of a turn is reached the short way, backwards, unless asked otherwise.
+        let end = Quaternion::from_axis_angle(axis, 1.5 * std::f64::consts::PI);
+        let short = Transform::from_quaternion(start.slerp(&end, 0.5));
+        let long = Transform::from_quaternion(start.slerp_along(&end, 0.5, Arc::Long));
+        let x = WorldVec::new(1.0, 0.0, 0.0);
+        assert_close(short.transform_point(x), Transform::rotation(axis, -0.25 * std::f64::consts::PI).transform_point(x));
+        assert_close(long.transform_point(x), Transform::rotation(axis, 0.75 * std::f64::consts::PI).transform_point(x));
+        // The same again, starting from the other sign of the same rotation.
+        let long = Transform::from_quaternion(start.slerp_along(&end.scaled(-1.0), 0.5, Arc::Long));
+        assert_close(long.transform_point(x), Transform::rotation(axis, 0.75 * std::f64::consts::PI).transform_point(x));
+    }
+}
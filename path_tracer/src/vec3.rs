@@ -59,6 +59,22 @@ where
     }
 }
 
+impl Vec3<f64> {
+    /// Find two unit vectors perpendicular to this (unit) vector and to each other, to use as the u
+    /// and v axes of a surface.
+    pub fn tangent_basis(self) -> (Vec3<f64>, Vec3<f64>) {
+        // Start from whichever axis is furthest from this, so the cross product is well behaved.
+        let helper = if self.0.abs() < 0.9 {
+            Vec3(1.0, 0.0, 0.0)
+        } else {
+            Vec3(0.0, 1.0, 0.0)
+        };
+        let tangent = helper.cross(self).normalised();
+        let bitangent = self.cross(tangent);
+        (tangent, bitangent)
+    }
+}
+
 impl<T: std::ops::Mul<Output = T> + Copy> std::ops::Mul<T> for Vec3<T> {
     type Output = Self;
     fn mul(self, rhs: T) -> Self {
@@ -130,4 +146,16 @@ mod tests {
         assert_eq!(k.cross(i), j);
         assert_eq!(i.cross(k), -j);
     }
+
+    #[test]
+    fn test_tangent_basis() {
+        for &normal in &[Vec3(1.0, 0.0, 0.0), Vec3(0.0, 0.0, -1.0), Vec3(0.6, 0.0, 0.8)] {
+            let (tangent, bitangent) = normal.tangent_basis();
+            for &(u, v) in &[(normal, tangent), (normal, bitangent), (tangent, bitangent)] {
+                assert!(u.dot(v).abs() < 1e-12);
+            }
+            assert!((tangent.length() - 1.0).abs() < 1e-12);
+            assert!((bitangent.length() - 1.0).abs() < 1e-12);
+        }
+    }
 }
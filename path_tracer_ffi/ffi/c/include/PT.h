@@ -14,6 +14,7 @@ struct SceneBuilder;
 struct Vec3;
 struct Camera;
 struct Image;
+struct Film;
 struct Hit;
 struct Material;
 
@@ -79,6 +80,12 @@ struct Hit *PT_Sphere_new(double x, double y, double z, double radius, struct Ma
 /// Destroy a sphere handle. This will not invalidate any scenes using this
 /// sphere -- only adding this sphere to anymore scenes.
 
+/// Place a shared object in the world with a transform. matrix points to 16
+/// doubles in row-major order, taking points from the object's space to world
+/// space. The object is shared, not copied, so it can be instanced many times.
+/// Returns NULL if the matrix can't be inverted.
+struct Hit *PT_Instance_new(struct Hit *object, const double *matrix);
+
 ////////////////////////////////////////////////////////////////////////////////
 // Camera.
 ////////////////////////////////////////////////////////////////////////////////
@@ -87,6 +94,12 @@ struct Hit *PT_Sphere_new(double x, double y, double z, double radius, struct Ma
 /// scene.
 struct Camera *PT_Camera_new(struct Scene *scene, struct Vec3 *origin, struct Vec3 *up, struct Vec3 *forward);
 
+/// Set the seed used to render with this camera. Rendering the same scene with
+/// the same seed and settings always gives the same image, whether it is
+/// rendered in one piece or in several regions.
+void PT_Camera_set_seed(struct Camera *self, uint64_t seed);
+void PT_Camera_set_shutter(struct Camera *self, double open, double close);
+
 /// Dump a Camera's information and the scene information contained within to
 /// stderr. Useful for debugging.
 void PT_Camera_dump(struct Camera *camera);
@@ -96,15 +109,63 @@ void PT_Camera_dump(struct Camera *camera);
 /// rendered in one portion.
 struct Image *PT_Camera_render(struct Camera *self, uint64_t top_left_x, uint64_t top_left_y, uint64_t region_x_size, uint64_t region_y_size, uint64_t total_x_size, uint64_t total_y_size, double fov, uint64_t bounces, uint64_t samples_per_pixel);
 
+/// The same as PT_Camera_render, but returns the accumulated samples for the
+/// region rather than an image. Films for separate regions, or for the same
+/// region rendered with different seeds, can be merged without losing
+/// precision. Returns NULL if the region doesn't fit in the image.
+struct Film *PT_Camera_render_film(struct Camera *self, uint64_t top_left_x, uint64_t top_left_y, uint64_t region_x_size, uint64_t region_y_size, uint64_t total_x_size, uint64_t total_y_size, double fov, uint64_t bounces, uint64_t samples_per_pixel);
+
 void PT_Camera_delete(struct Camera *self);
 
+////////////////////////////////////////////////////////////////////////////////
+// Film.
+////////////////////////////////////////////////////////////////////////////////
+
+/// Create a film with no samples in it.
+struct Film *PT_Film_new(uint64_t x_size, uint64_t y_size);
+
+/// Add the samples in tile to self, with tile's top left corner at (x, y).
+/// Pixels are weighted by their sample counts. Anything falling outside of
+/// self is ignored.
+void PT_Film_merge(struct Film *self, struct Film *tile, uint64_t x, uint64_t y);
+
+/// Average the samples in each pixel into a new image.
+struct Image *PT_Film_to_image(struct Film *self);
+
+/// Free the film.
+void PT_Film_delete(struct Film *self);
+
 ////////////////////////////////////////////////////////////////////////////////
 // Image.
 ////////////////////////////////////////////////////////////////////////////////
 
+/// Create a black image.
+struct Image *PT_Image_new(uint64_t x_size, uint64_t y_size);
+
 /// Free the image.
 void PT_Image_delete(struct Image *image);
 
+/// Copy source into self, with source's top left corner at (x, y). Anything
+/// falling outside of self is ignored.
+void PT_Image_blit(struct Image *self, struct Image *source, uint64_t x, uint64_t y);
+
+/// Copy out a region of an image as a new image. The region is shrunk to fit
+/// if it extends past the edges of the image.
+struct Image *PT_Image_crop(struct Image *self, uint64_t top_left_x, uint64_t top_left_y, uint64_t x_size, uint64_t y_size);
+
+/// Mirror an image in place.
+void PT_Image_flip_horizontal(struct Image *self);
+void PT_Image_flip_vertical(struct Image *self);
+
+/// Filters for PT_Image_resize.
+#define PT_RESIZE_BOX 0
+#define PT_RESIZE_TRIANGLE 1
+#define PT_RESIZE_LANCZOS3 2
+
+/// Resample an image to a new size, returning a new image. Returns NULL if
+/// filter isn't one of the filters above.
+struct Image *PT_Image_resize(struct Image *self, uint64_t x_size, uint64_t y_size, uint32_t filter);
+
 /// Get a certain pixel's colour.
 struct Pixel PT_Image_get_pixel(struct Image *image, uint64_t i, uint64_t j); 
 
@@ -1,3 +1,8 @@
+//! Every function here takes raw pointers handed out by this library. Callers must only pass
+//! pointers previously returned by the matching `PT_*_new` function which have not yet been
+//! deleted.
+#![allow(clippy::missing_safety_doc)]
+
 use path_tracer::*;
 
 use std::convert::TryInto;
@@ -8,8 +13,9 @@ pub type CScene = std::rc::Rc<CSceneBuilder>;
 pub type CVec3 = vec3::Vec3<f64>;
 pub type CMaterial = std::rc::Rc<dyn material::Material>;
 pub type CHit = std::rc::Rc<dyn hit::Hit + 'static>;
+pub type CFilm = film::Film;
 
-use std::os::raw::{c_double, c_float};
+use std::os::raw::c_double;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -45,7 +51,7 @@ impl CImage {
 #[no_mangle]
 pub unsafe extern "C" fn PT_Image_delete(image: *mut CImage) {
     if !image.is_null() {
-        Box::from_raw(image);
+        drop(Box::from_raw(image));
     }
 }
 
@@ -57,7 +63,7 @@ pub unsafe extern "C" fn PT_Vec3_new(x: c_double, y: c_double, z: c_double) -> *
 #[no_mangle]
 pub unsafe extern "C" fn PT_Vec3_delete(vec: *mut CVec3) {
     if !vec.is_null() {
-        Box::from_raw(vec);
+        drop(Box::from_raw(vec));
     }
 }
 
@@ -87,7 +93,7 @@ pub unsafe extern "C" fn PT_Scene_dump(self_: *mut CScene) {
 #[no_mangle]
 pub unsafe extern "C" fn PT_Scene_delete(self_: *mut CScene) {
     if !self_.is_null() {
-        Box::from_raw(self_);
+        drop(Box::from_raw(self_));
     }
 }
 
@@ -105,7 +111,7 @@ pub unsafe extern "C" fn PT_Material_Lambertian_new(
 #[no_mangle]
 pub unsafe extern "C" fn PT_Material_delete(material: *mut CMaterial) {
     if !material.is_null() {
-        Box::from_raw(material);
+        drop(Box::from_raw(material));
     }
 }
 
@@ -131,10 +137,27 @@ pub unsafe extern "C" fn PT_Sphere_new(
         material_rc,
     ))))
 }
+/// `matrix` points to 16 doubles, in row-major order. Returns null if the matrix isn't invertible.
+#[no_mangle]
+pub unsafe extern "C" fn PT_Instance_new(object: *mut CHit, matrix: *const c_double) -> *mut CHit {
+    let values = std::slice::from_raw_parts(matrix, 16);
+    let mut rows = [[0.0; 4]; 4];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row.copy_from_slice(&values[i * 4..i * 4 + 4]);
+    }
+    match transform::Transform::new(transform::Matrix4(rows)) {
+        Some(transform) => Box::into_raw(Box::new(std::rc::Rc::new(instance::Instance::new(
+            object.as_ref().unwrap().clone(),
+            transform,
+        )))),
+        None => std::ptr::null_mut(),
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn PT_Hit_delete(hit: *mut CHit) {
     if !hit.is_null() {
-        Box::from_raw(hit);
+        drop(Box::from_raw(hit));
     }
 }
 
@@ -199,6 +222,45 @@ pub unsafe extern "C" fn PT_Camera_render(
     )))
 }
 
+/// The same as `PT_Camera_render`, but always renders a region, and returns the accumulated samples
+/// rather than an image. Returns null if the region doesn't fit in the image.
+#[no_mangle]
+pub unsafe extern "C" fn PT_Camera_render_film(
+    self_: *mut CCamera,
+    top_left_x: u64,
+    top_left_y: u64,
+    region_x_size: u64,
+    region_y_size: u64,
+    x_size: u64,
+    y_size: u64,
+    fov: f64,
+    bounces: u64,
+    samples_per_pixel: u64,
+) -> *mut CFilm {
+    match self_.as_ref().unwrap().render_region_film(
+        (top_left_x.try_into().unwrap(), top_left_y.try_into().unwrap()),
+        (region_x_size.try_into().unwrap(), region_y_size.try_into().unwrap()),
+        x_size.try_into().unwrap(),
+        y_size.try_into().unwrap(),
+        fov,
+        bounces.try_into().unwrap(),
+        samples_per_pixel.try_into().unwrap(),
+    ) {
+        Ok(film) => Box::into_raw(Box::new(film)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Camera_set_seed(self_: *mut CCamera, seed: u64) {
+    self_.as_mut().unwrap().set_seed(seed);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Camera_set_shutter(self_: *mut CCamera, open: f64, close: f64) {
+    self_.as_mut().unwrap().set_shutter(open, close);
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn PT_Camera_dump(self_: *mut CCamera) {
     dbg!(self_.as_ref().unwrap());
@@ -207,7 +269,7 @@ pub unsafe extern "C" fn PT_Camera_dump(self_: *mut CCamera) {
 #[no_mangle]
 pub unsafe extern "C" fn PT_Camera_delete(self_: *mut CCamera) {
     if !self_.is_null() {
-        Box::from_raw(self_);
+        drop(Box::from_raw(self_));
     }
 }
 
@@ -225,3 +287,96 @@ pub unsafe extern "C" fn PT_Image_get_height(self_: *mut CImage) -> u64 {
 pub unsafe extern "C" fn PT_Image_get_width(self_: *mut CImage) -> u64 {
     self_.as_ref().unwrap().0.x_len().try_into().unwrap()
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_new(x_size: u64, y_size: u64) -> *mut CImage {
+    Box::into_raw(Box::new(CImage::new(image::Image::new(
+        x_size.try_into().unwrap(),
+        y_size.try_into().unwrap(),
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_blit(self_: *mut CImage, source: *mut CImage, x: u64, y: u64) {
+    self_.as_mut().unwrap().0.blit(
+        &source.as_ref().unwrap().0,
+        (x.try_into().unwrap(), y.try_into().unwrap()),
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_crop(
+    self_: *mut CImage,
+    top_left_x: u64,
+    top_left_y: u64,
+    x_size: u64,
+    y_size: u64,
+) -> *mut CImage {
+    Box::into_raw(Box::new(CImage::new(self_.as_ref().unwrap().0.crop(
+        (top_left_x.try_into().unwrap(), top_left_y.try_into().unwrap()),
+        (x_size.try_into().unwrap(), y_size.try_into().unwrap()),
+    ))))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_flip_horizontal(self_: *mut CImage) {
+    self_.as_mut().unwrap().0.flip_horizontal();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_flip_vertical(self_: *mut CImage) {
+    self_.as_mut().unwrap().0.flip_vertical();
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Film_new(x_size: u64, y_size: u64) -> *mut CFilm {
+    Box::into_raw(Box::new(film::Film::new(
+        x_size.try_into().unwrap(),
+        y_size.try_into().unwrap(),
+    )))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Film_merge(self_: *mut CFilm, tile: *mut CFilm, x: u64, y: u64) {
+    self_.as_mut().unwrap().merge(
+        tile.as_ref().unwrap(),
+        (x.try_into().unwrap(), y.try_into().unwrap()),
+    );
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Film_to_image(self_: *mut CFilm) -> *mut CImage {
+    Box::into_raw(Box::new(CImage::new(self_.as_ref().unwrap().to_image())))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn PT_Film_delete(self_: *mut CFilm) {
+    if !self_.is_null() {
+        drop(Box::from_raw(self_));
+    }
+}
+
+pub const PT_RESIZE_BOX: u32 = 0;
+pub const PT_RESIZE_TRIANGLE: u32 = 1;
+pub const PT_RESIZE_LANCZOS3: u32 = 2;
+
+/// Returns null if `filter` isn't one of the `PT_RESIZE_*` filters.
+#[no_mangle]
+pub unsafe extern "C" fn PT_Image_resize(
+    self_: *mut CImage,
+    x_size: u64,
+    y_size: u64,
+    filter: u32,
+) -> *mut CImage {
+    let filter = match filter {
+        PT_RESIZE_BOX => image::ResizeFilter::Box,
+        PT_RESIZE_TRIANGLE => image::ResizeFilter::Triangle,
+        PT_RESIZE_LANCZOS3 => image::ResizeFilter::Lanczos3,
+        _ => return std::ptr::null_mut(),
+    };
+    Box::into_raw(Box::new(CImage::new(self_.as_ref().unwrap().0.resize(
+        x_size.try_into().unwrap(),
+        y_size.try_into().unwrap(),
+        filter,
+    ))))
+}
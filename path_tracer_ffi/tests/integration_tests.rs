@@ -102,4 +102,130 @@ mod tests {
         }
 
     }
+
+    #[test]
+    fn stitched_quadrants_match_full_render() {
+        unsafe {
+            let scene = ffi::PT_SceneBuilder_new();
+            let material = ffi::PT_Material_Lambertian_new(0.8, 0.3, 0.3);
+            let sphere = ffi::PT_Sphere_new(0.0, 0.0, 0.0, 1.0, material);
+            ffi::PT_SceneBuilder_add_object(scene, sphere);
+
+            let camera = ffi::PT_Camera_new(
+                ffi::PT_SceneBuilder_into_scene(scene),
+                /*origin=*/ ffi::PT_Vec3_new(0.0, 0.0, -3.0),
+                /*up=*/ ffi::PT_Vec3_new(0.0, 1.0, 0.0),
+                /*forward=*/ ffi::PT_Vec3_new(0.0, 0.0, 1.0),
+            );
+            ffi::PT_Camera_set_seed(camera, 42);
+
+            let full = ffi::PT_Camera_render(camera, 0, 0, 0, 0, 40, 30, 90.0, 10, 4);
+
+            let stitched = ffi::PT_Image_new(40, 30);
+            for &(x, y) in &[(0, 0), (20, 0), (0, 15), (20, 15)] {
+                let quadrant = ffi::PT_Camera_render(camera, x, y, 20, 15, 40, 30, 90.0, 10, 4);
+                ffi::PT_Image_blit(stitched, quadrant, x, y);
+                ffi::PT_Image_delete(quadrant);
+            }
+
+            for i in 0..40 {
+                for j in 0..30 {
+                    let expected = ffi::PT_Image_get_pixel(full, i, j);
+                    let actual = ffi::PT_Image_get_pixel(stitched, i, j);
+                    assert_eq!(
+                        (expected.red, expected.green, expected.blue),
+                        (actual.red, actual.green, actual.blue)
+                    );
+                }
+            }
+
+            ffi::PT_Image_delete(full);
+            ffi::PT_Image_delete(stitched);
+            ffi::PT_Camera_delete(camera);
+        }
+    }
+
+    #[test]
+    fn merged_films_match_full_render() {
+        unsafe {
+            let scene = ffi::PT_SceneBuilder_new();
+            let material = ffi::PT_Material_Lambertian_new(0.8, 0.3, 0.3);
+            let sphere = ffi::PT_Sphere_new(0.0, 0.0, 0.0, 1.0, material);
+            ffi::PT_SceneBuilder_add_object(scene, sphere);
+
+            let camera = ffi::PT_Camera_new(
+                ffi::PT_SceneBuilder_into_scene(scene),
+                /*origin=*/ ffi::PT_Vec3_new(0.0, 0.0, -3.0),
+                /*up=*/ ffi::PT_Vec3_new(0.0, 1.0, 0.0),
+                /*forward=*/ ffi::PT_Vec3_new(0.0, 0.0, 1.0),
+            );
+            ffi::PT_Camera_set_seed(camera, 42);
+
+            let full = ffi::PT_Camera_render(camera, 0, 0, 0, 0, 40, 30, 90.0, 10, 4);
+
+            let merged = ffi::PT_Film_new(40, 30);
+            for &(x, y) in &[(0, 0), (20, 0), (0, 15), (20, 15)] {
+                let quadrant = ffi::PT_Camera_render_film(camera, x, y, 20, 15, 40, 30, 90.0, 10, 4);
+                ffi::PT_Film_merge(merged, quadrant, x, y);
+                ffi::PT_Film_delete(quadrant);
+            }
+            let stitched = ffi::PT_Film_to_image(merged);
+
+            for i in 0..40 {
+                for j in 0..30 {
+                    let expected = ffi::PT_Image_get_pixel(full, i, j);
+                    let actual = ffi::PT_Image_get_pixel(stitched, i, j);
+                    assert_eq!(
+                        (expected.red, expected.green, expected.blue),
+                        (actual.red, actual.green, actual.blue)
+                    );
+                }
+            }
+
+            // Regions which don't fit aren't rendered.
+            assert!(ffi::PT_Camera_render_film(camera, 30, 0, 20, 15, 40, 30, 90.0, 10, 4).is_null());
+
+            ffi::PT_Image_delete(full);
+            ffi::PT_Image_delete(stitched);
+            ffi::PT_Film_delete(merged);
+            ffi::PT_Camera_delete(camera);
+        }
+    }
+
+    #[test]
+    fn out_of_range_offsets_are_clipped() {
+        unsafe {
+            let image = ffi::PT_Image_new(4, 4);
+            let source = ffi::PT_Image_new(2, 2);
+            ffi::PT_Image_blit(image, source, u64::MAX, 1);
+            let cropped = ffi::PT_Image_crop(image, u64::MAX, 0, 2, 2);
+            assert_eq!(ffi::PT_Image_get_width(cropped), 0);
+            let cropped_to_edge = ffi::PT_Image_crop(image, 1, 1, u64::MAX, u64::MAX);
+            assert_eq!(ffi::PT_Image_get_width(cropped_to_edge), 3);
+            assert_eq!(ffi::PT_Image_get_height(cropped_to_edge), 3);
+
+            let film = ffi::PT_Film_new(4, 4);
+            let tile = ffi::PT_Film_new(2, 2);
+            ffi::PT_Film_merge(film, tile, 1, u64::MAX);
+
+            ffi::PT_Film_delete(tile);
+            ffi::PT_Film_delete(film);
+            ffi::PT_Image_delete(cropped_to_edge);
+            ffi::PT_Image_delete(cropped);
+            ffi::PT_Image_delete(source);
+            ffi::PT_Image_delete(image);
+        }
+    }
+
+    #[test]
+    fn resize_rejects_unknown_filters() {
+        unsafe {
+            let image = ffi::PT_Image_new(4, 4);
+            let resized = ffi::PT_Image_resize(image, 2, 2, ffi::PT_RESIZE_LANCZOS3);
+            assert_eq!(ffi::PT_Image_get_width(resized), 2);
+            assert!(ffi::PT_Image_resize(image, 2, 2, 3).is_null());
+            ffi::PT_Image_delete(resized);
+            ffi::PT_Image_delete(image);
+        }
+    }
 }